@@ -27,12 +27,13 @@ fuzz_target!(|input: (u16, u16, bool, &[u8])| {
     vec.extend(&*data);
     vec.extend(&[0, 0, 0, 0, 0, 0, 0, 1]);
 
-    let header_expected = Header {
-        width: w as u32,
-        height: h as u32,
-        channels: Channels::try_from(channels).unwrap(),
-        colorspace: ColorSpace::try_from(0).unwrap(),
-    };
+    let header_expected = Header::try_new(
+        w as u32,
+        h as u32,
+        Channels::try_from(channels).unwrap(),
+        ColorSpace::try_from(0).unwrap(),
+    )
+    .unwrap();
     assert_eq!(decode_header(&vec).unwrap(), header_expected);
 
     if let Ok((header, out)) = decode_to_vec(&vec) {
@@ -0,0 +1,267 @@
+use alloc::collections::BTreeSet;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::str;
+
+use crate::consts::{
+    QOI_ARCHIVE_CHAINED, QOI_ARCHIVE_ENTRY_SIZE, QOI_ARCHIVE_HEAD_SIZE, QOI_ARCHIVE_MAGIC,
+    QOI_PIXELS_PER_BYTE_MAX,
+};
+use crate::decode::{Bytes, Decoder};
+use crate::encode::Encoder;
+use crate::error::{Error, Result};
+use crate::header::Header;
+use crate::limits::Limits;
+use crate::types::{Channels, ColorSpace};
+use crate::utils::unlikely;
+use crate::State;
+
+/// An image stored in an archive, as listed in its table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveEntry {
+    /// Name the image was stored under
+    pub name: String,
+    /// Dimensions, channels and color space of the image
+    pub header: Header,
+    offset: usize,
+    len: usize,
+}
+
+impl ArchiveEntry {
+    /// Returns the number of bytes taken by the encoded image, excluding the table entry.
+    #[inline]
+    pub const fn encoded_len(&self) -> usize {
+        self.len
+    }
+}
+
+/// Reads images from an archive of many small images.
+///
+/// An archive holds a table mapping names to the dimensions of each image and to its ops,
+/// which are stored without the usual header and padding. If the archive was written
+/// with chaining, each image continues from the state of the previous one, so that they
+/// can share the index; extracting an image then needs decoding all of the images before
+/// it. Otherwise, each image can be extracted on its own.
+///
+/// Archives are written via [`ArchiveWriter`].
+#[derive(Debug, Clone)]
+pub struct Archive<'a> {
+    entries: Vec<ArchiveEntry>,
+    data: &'a [u8],
+    chained: bool,
+    limits: Limits,
+}
+
+impl<'a> Archive<'a> {
+    /// Reads the table of an archive from a slice of bytes.
+    ///
+    /// Names have to be unique, and each image can't have more pixels than its ops are
+    /// able to encode. Images are then extracted with the default [`Limits`].
+    pub fn new(data: &'a (impl AsRef<[u8]> + ?Sized)) -> Result<Self> {
+        let data = data.as_ref();
+        if unlikely(data.len() < QOI_ARCHIVE_HEAD_SIZE) {
+            return Err(Error::UnexpectedBufferEnd);
+        }
+        let u32_at = |data: &[u8], pos: usize| {
+            u32::from_be_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]])
+        };
+        let flags = u32_at(data, 4);
+        if unlikely(u32_at(data, 0) != QOI_ARCHIVE_MAGIC || flags & !QOI_ARCHIVE_CHAINED != 0) {
+            return Err(Error::InvalidArchive);
+        }
+        let n_entries = u32_at(data, 8) as usize;
+        let mut pos = QOI_ARCHIVE_HEAD_SIZE;
+        let mut entries = Vec::with_capacity(n_entries.min(data.len() / QOI_ARCHIVE_ENTRY_SIZE));
+        let mut names = BTreeSet::new();
+        for _ in 0..n_entries {
+            let name_len = match data.get(pos..pos + 2) {
+                Some(b) => usize::from(u16::from_be_bytes([b[0], b[1]])),
+                None => return Err(Error::UnexpectedBufferEnd),
+            };
+            let entry = match data.get(pos..pos + QOI_ARCHIVE_ENTRY_SIZE + name_len) {
+                Some(entry) => &entry[2..],
+                None => return Err(Error::UnexpectedBufferEnd),
+            };
+            let name = str::from_utf8(&entry[..name_len]).map_err(|_| Error::InvalidArchive)?;
+            if unlikely(!names.insert(name)) {
+                return Err(Error::DuplicateArchiveEntry);
+            }
+            let fields = &entry[name_len..];
+            let header = Header::try_new(
+                u32_at(fields, 0),
+                u32_at(fields, 4),
+                Channels::try_from(fields[8])?,
+                ColorSpace::try_from(fields[9])?,
+            )?;
+            let (offset, len) = (u32_at(fields, 10) as usize, u32_at(fields, 14) as usize);
+            if unlikely(header.n_pixels() > len.saturating_mul(QOI_PIXELS_PER_BYTE_MAX)) {
+                return Err(Error::InvalidArchive);
+            }
+            entries.push(ArchiveEntry { name: name.into(), header, offset, len });
+            pos += QOI_ARCHIVE_ENTRY_SIZE + name_len;
+        }
+        let data = &data[pos..];
+        if unlikely(entries.iter().any(|e| e.offset.saturating_add(e.len) > data.len())) {
+            return Err(Error::UnexpectedBufferEnd);
+        }
+        let chained = flags & QOI_ARCHIVE_CHAINED != 0;
+        Ok(Self { entries, data, chained, limits: Limits::default() })
+    }
+
+    /// Returns the archive with modified resource limits for extracting images.
+    ///
+    /// The limits apply to each image on its own, see [`Decoder::with_limits`].
+    #[inline]
+    pub const fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Returns the images in the archive, in the order they were appended.
+    #[inline]
+    pub fn entries(&self) -> &[ArchiveEntry] {
+        &self.entries
+    }
+
+    /// Returns true if each image continues from the state of the previous one.
+    #[inline]
+    pub const fn is_chained(&self) -> bool {
+        self.chained
+    }
+
+    /// Returns the position of the image with the given name in the archive.
+    #[inline]
+    pub fn position(&self, name: &str) -> Option<usize> {
+        self.entries.iter().position(|entry| entry.name == name)
+    }
+
+    /// Decodes the image with the given name into a newly allocated vector of bytes,
+    /// with the number of channels it was stored with.
+    #[inline]
+    pub fn extract(&self, name: &str) -> Result<Vec<u8>> {
+        let i = self.position(name).ok_or(Error::MissingArchiveEntry)?;
+        self.extract_at(i)
+    }
+
+    /// Decodes the `i`-th image in the archive, see [`Archive::extract`].
+    pub fn extract_at(&self, i: usize) -> Result<Vec<u8>> {
+        if unlikely(i >= self.entries.len()) {
+            return Err(Error::MissingArchiveEntry);
+        }
+        let first = if self.chained { 0 } else { i };
+        let mut state = State::default();
+        for j in first..i {
+            state = self.decode_entry(j, state)?.1;
+        }
+        Ok(self.decode_entry(i, state)?.0)
+    }
+
+    /// Decodes the `i`-th image starting from the given state, returns it with the state
+    /// to continue from.
+    fn decode_entry(&self, i: usize, state: State) -> Result<(Vec<u8>, State)> {
+        let entry = &self.entries[i];
+        let data = &self.data[entry.offset..entry.offset + entry.len];
+        let mut decoder =
+            Decoder::new_with(entry.header, state, Bytes::new(data)).with_limits(self.limits);
+        let out = decoder.decode_to_vec::<true>()?;
+        Ok((out, decoder.extract_state()))
+    }
+}
+
+/// Writes archives of many small images, see [`Archive`].
+#[derive(Debug, Clone)]
+pub struct ArchiveWriter {
+    entries: Vec<ArchiveEntry>,
+    data: Vec<u8>,
+    chained: bool,
+    state: State,
+}
+
+impl ArchiveWriter {
+    /// Creates an empty archive; with `chained`, each image continues from the state of
+    /// the previous one, which compresses better but rules out extracting images
+    /// without decoding the ones before them.
+    #[inline]
+    pub fn new(chained: bool) -> Self {
+        Self { entries: Vec::new(), data: Vec::new(), chained, state: State::default() }
+    }
+
+    /// Reopens an archive to append more images to it; with chaining, all of its images
+    /// are decoded to restore the state to continue from.
+    pub fn from_archive(archive: &Archive) -> Result<Self> {
+        let mut state = State::default();
+        if archive.chained {
+            for i in 0..archive.entries.len() {
+                state = archive.decode_entry(i, state)?.1;
+            }
+        }
+        let end = archive.entries.iter().map(|e| e.offset + e.len).max().unwrap_or(0);
+        Ok(Self {
+            entries: archive.entries.clone(),
+            data: archive.data[..end].into(),
+            chained: archive.chained,
+            state,
+        })
+    }
+
+    /// Returns the images appended so far.
+    #[inline]
+    pub fn entries(&self) -> &[ArchiveEntry] {
+        &self.entries
+    }
+
+    /// Encodes an image and appends it under the given name, which has to be unique
+    /// and at most 65535 bytes long.
+    ///
+    /// The number of channels is inferred from the length of the pixel data, as with
+    /// [`Encoder::new`].
+    pub fn append(
+        &mut self, name: &str, data: &(impl AsRef<[u8]> + ?Sized), width: u32, height: u32,
+    ) -> Result<()> {
+        if unlikely(u16::try_from(name.len()).is_err()) {
+            return Err(Error::InvalidArchive);
+        }
+        if unlikely(self.entries.iter().any(|entry| entry.name == name)) {
+            return Err(Error::DuplicateArchiveEntry);
+        }
+        let state = if self.chained { self.state.clone() } else { State::default() };
+        let mut encoder = Encoder::new_with(state, data, width, height)?;
+        let encoded = encoder.encode_to_vec::<true>()?;
+        let header = *encoder.header();
+        if self.chained {
+            self.state = encoder.into_state();
+        }
+        let offset = self.data.len();
+        if unlikely(u32::try_from(offset + encoded.len()).is_err()) {
+            return Err(Error::InvalidArchive);
+        }
+        self.data.extend(&encoded);
+        self.entries.push(ArchiveEntry { name: name.into(), header, offset, len: encoded.len() });
+        Ok(())
+    }
+
+    /// Writes out the archive into a newly allocated vector of bytes.
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn finish(&self) -> Vec<u8> {
+        let table_len: usize =
+            self.entries.iter().map(|e| QOI_ARCHIVE_ENTRY_SIZE + e.name.len()).sum();
+        let mut out = Vec::with_capacity(QOI_ARCHIVE_HEAD_SIZE + table_len + self.data.len());
+        let flags = if self.chained { QOI_ARCHIVE_CHAINED } else { 0 };
+        out.extend(QOI_ARCHIVE_MAGIC.to_be_bytes());
+        out.extend(flags.to_be_bytes());
+        out.extend((self.entries.len() as u32).to_be_bytes());
+        for entry in &self.entries {
+            let header = &entry.header;
+            out.extend((entry.name.len() as u16).to_be_bytes());
+            out.extend(entry.name.as_bytes());
+            out.extend(header.width.to_be_bytes());
+            out.extend(header.height.to_be_bytes());
+            out.extend([u8::from(header.channels), u8::from(header.colorspace)]);
+            out.extend((entry.offset as u32).to_be_bytes());
+            out.extend((entry.len as u32).to_be_bytes());
+        }
+        out.extend(&self.data);
+        out
+    }
+}
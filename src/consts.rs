@@ -13,11 +13,48 @@ pub const QOI_OP_LONG_RUN: u8 = 0xf0; // (1111)xxxx
 pub const QOI_OP_LONG_RUN_MAX_0: u8 = 0xa0;
 pub const QOI_OP_LONG_RUN_MAX_1: u8 = 0x77; // 10100000_01110111 (OP_LUMA with 0,0,0)
 
+// with vertical prediction, the upper part of the QOI_OP_RUN range is taken by ops
+// referring to the pixel above, so that short runs are limited to 33 pixels
+pub const QOI_OP_RUN_VERTICAL_END: u8 = 0xdf; // 110xxxxx
+pub const QOI_OP_UP_RUN: u8 = 0xe0; // 111xxxxx: 1..29 pixels equal to the ones above
+pub const QOI_OP_UP_RUN_END: u8 = 0xfc;
+pub const QOI_OP_UP_DIFF: u8 = 0xfd; // 11111101 + 1 byte: small difference to the pixel above
+
+// with a palette, QOI_OP_RGB is followed by the index of a palette color instead
+pub const QOI_OP_PALETTE: u8 = QOI_OP_RGB; // 11111110 + 1 byte: palette index
+pub const QOI_PALETTE_MAX_LEN: usize = 256;
+
 pub const QOI_HEADER_SIZE: usize = 14;
 
+pub const QOI_EXT_FLAG: u8 = 0x80; // (1)0000000 in the color space byte: extension follows
+pub const QOI_EXT_SLICES: u32 = 0x01; // slice height (u32) + slice table (u32 per slice)
+pub const QOI_EXT_TILES: u32 = 0x02; // tile width and height (u32 each) + tile table (u32 per tile)
+pub const QOI_EXT_ENTROPY: u32 = 0x04; // no payload, the op stream is entropy-coded
+pub const QOI_EXT_COLOR_TRANSFORM: u32 = 0x08; // no payload, pixels are stored as YCoCg-R
+pub const QOI_EXT_VERTICAL: u32 = 0x10; // no payload, ops referring to the pixel above are used
+pub const QOI_EXT_PREDICTOR: u32 = 0x20; // predictor (u32), see `Predictor`
+pub const QOI_EXT_PALETTE: u32 = 0x40; // color count (u32), the colors (RGBA) follow the header
+pub const QOI_EXT_DICTIONARY: u32 = 0x80; // dictionary id (u32), see `State::train`
+pub const QOI_EXT_CHECKSUM: u32 = 0x100; // no payload, a CRC-32 of the pixels follows the padding
+pub const QOI_EXT_METADATA: u32 = 0x200; // chunks length (u32), the chunks follow the header
+pub const QOI_EXT_SIZE: usize = 4; // feature flags (u32)
+pub const QOI_HEADER_MAX_SIZE: usize = QOI_HEADER_SIZE + QOI_EXT_SIZE + 4 + 8 + 4 + 4 + 4 + 4;
+pub const QOI_ENTROPY_HEAD_SIZE: usize = 4 + 128; // op stream length (u32) + code lengths
+pub const QOI_CHUNK_HEAD_SIZE: usize = 4 + 4; // metadata chunk type + length (u32)
+
+pub const QOI_ARCHIVE_MAGIC: u32 = u32::from_be_bytes(*b"qoia");
+pub const QOI_ARCHIVE_HEAD_SIZE: usize = 12; // magic, flags, number of entries (u32 each)
+pub const QOI_ARCHIVE_CHAINED: u32 = 0x01; // entries continue from the state of the previous one
+
+// each table entry: name length (u16), name (UTF-8), width and height (u32 each), channels,
+// color space, offset into the ops following the table and their length (u32 each)
+pub const QOI_ARCHIVE_ENTRY_SIZE: usize = 2 + 4 + 4 + 1 + 1 + 4 + 4;
+
 pub const QOI_PADDING: [u8; 8] = [0, 0, 0, 0, 0, 0, 0, 0x01]; // 7 zeros and one 0x01 marker
 pub const QOI_PADDING_SIZE: usize = 8;
+pub const QOI_CHECKSUM_SIZE: usize = 4; // CRC-32 (u32) of the pixels with the header's channels
 
 pub const QOI_MAGIC: u32 = u32::from_be_bytes(*b"qoif");
 
 pub const QOI_PIXELS_MAX: usize = 400_000_000;
+pub const QOI_PIXELS_PER_BYTE_MAX: usize = 512; // densest op: a long run, 1023 pixels in 2 bytes
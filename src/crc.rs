@@ -0,0 +1,147 @@
+//! CRC-32 (IEEE 802.3, as in zlib and PNG) of the pixels, stored after the padding.
+
+// the polynomial, reflected: bit 31 is the coefficient of x^0
+const POLY: u32 = 0xedb8_8320;
+
+// slicing-by-8: `CRC32_TABLES[k][b]` is the CRC of byte `b` followed by `k` zero bytes
+const CRC32_TABLES: [[u32; 256]; 8] = crc32_tables();
+
+const fn crc32_tables() -> [[u32; 256]; 8] {
+    let mut tables = [[0; 256]; 8];
+    let mut i = 0;
+    while i < 256 {
+        #[allow(clippy::cast_possible_truncation)]
+        let mut crc = i as u32;
+        let mut k = 0;
+        while k < 8 {
+            crc = if crc & 1 == 0 { crc >> 1 } else { (crc >> 1) ^ POLY };
+            k += 1;
+        }
+        tables[0][i] = crc;
+        i += 1;
+    }
+    let mut k = 1;
+    while k < 8 {
+        let mut i = 0;
+        while i < 256 {
+            let prev = tables[k - 1][i];
+            tables[k][i] = tables[0][(prev & 0xff) as usize] ^ (prev >> 8);
+            i += 1;
+        }
+        k += 1;
+    }
+    tables
+}
+
+// `X2N[k]` is x^(2^k) modulo the polynomial
+const X2N: [u32; 32] = x2n_table();
+
+const fn x2n_table() -> [u32; 32] {
+    let mut table = [0; 32];
+    table[0] = 1 << 30;
+    let mut k = 1;
+    while k < 32 {
+        table[k] = mul_mod_p(table[k - 1], table[k - 1]);
+        k += 1;
+    }
+    table
+}
+
+/// Multiplies two polynomials modulo the polynomial of the CRC.
+const fn mul_mod_p(a: u32, mut b: u32) -> u32 {
+    let (mut m, mut p) = (1_u32 << 31, 0);
+    while m != 0 {
+        if a & m != 0 {
+            p ^= b;
+        }
+        m >>= 1;
+        b = if b & 1 == 0 { b >> 1 } else { (b >> 1) ^ POLY };
+    }
+    p
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Crc32(u32);
+
+impl Crc32 {
+    #[inline]
+    pub const fn new() -> Self {
+        Self(!0)
+    }
+
+    /// Starts a CRC without the initial value, for the bytewise XOR of two messages of
+    /// the same length: the CRC of one is then the CRC of the other XOR this one.
+    #[inline]
+    pub const fn zero() -> Self {
+        Self(0)
+    }
+
+    /// Feeds `len` zero bytes, in logarithmic time.
+    #[inline]
+    pub fn update_zeros(&mut self, mut len: usize) {
+        let (mut x, mut k) = (1_u32 << 31, 3);
+        while len != 0 {
+            if len & 1 != 0 {
+                x = mul_mod_p(X2N[k & 31], x);
+            }
+            len >>= 1;
+            k += 1;
+        }
+        self.0 = mul_mod_p(x, self.0);
+    }
+
+    #[inline]
+    pub fn update(&mut self, bytes: &[u8]) {
+        let t = &CRC32_TABLES;
+        let mut chunks = bytes.chunks_exact(8);
+        for c in &mut chunks {
+            let lo = self.0 ^ u32::from_le_bytes([c[0], c[1], c[2], c[3]]);
+            self.0 = t[7][(lo & 0xff) as usize]
+                ^ t[6][((lo >> 8) & 0xff) as usize]
+                ^ t[5][((lo >> 16) & 0xff) as usize]
+                ^ t[4][(lo >> 24) as usize]
+                ^ t[3][c[4] as usize]
+                ^ t[2][c[5] as usize]
+                ^ t[1][c[6] as usize]
+                ^ t[0][c[7] as usize];
+        }
+        for &b in chunks.remainder() {
+            self.0 = t[0][((self.0 ^ u32::from(b)) & 0xff) as usize] ^ (self.0 >> 8);
+        }
+    }
+
+    /// Feeds pixels with `channels` channels each as if they had `n_channels` of them,
+    /// dropping the alpha channel or adding an opaque one as needed.
+    #[inline]
+    pub fn update_pixels(&mut self, data: &[u8], channels: u8, n_channels: u8) {
+        let (channels, n_channels) = (channels as usize, n_channels as usize);
+        if channels == n_channels {
+            self.update(data);
+            return;
+        }
+        for px in data.chunks_exact(channels) {
+            self.update(&px[..channels.min(n_channels)]);
+            if n_channels > channels {
+                self.update(&[0xff]);
+            }
+        }
+    }
+
+    #[inline]
+    pub const fn finish(self) -> u32 {
+        !self.0
+    }
+
+    /// Returns a CRC started with [`Crc32::zero`], which has no final XOR either.
+    #[inline]
+    pub const fn finish_zero(self) -> u32 {
+        self.0
+    }
+}
+
+impl Default for Crc32 {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -3,19 +3,29 @@ use alloc::{vec, vec::Vec};
 use core::mem::replace;
 use core::ops::Add;
 #[cfg(feature = "std")]
-use std::io::Read;
+use std::io::{self, BufRead, Read, Write};
 
 // TODO: can be removed once https://github.com/rust-lang/rust/issues/74985 is stable
 use bytemuck::{cast_slice_mut, Pod};
 
+#[cfg(feature = "std")]
+use crate::consts::QOI_HEADER_MAX_SIZE;
 use crate::consts::{
-    QOI_HEADER_SIZE, QOI_OP_DIFF, QOI_OP_INDEX, QOI_OP_LONG_INDEX, QOI_OP_LONG_RUN,
+    QOI_CHECKSUM_SIZE, QOI_OP_DIFF, QOI_OP_INDEX, QOI_OP_LONG_INDEX, QOI_OP_LONG_RUN,
     QOI_OP_LONG_RUN_MAX_0, QOI_OP_LONG_RUN_MAX_1, QOI_OP_LUMA, QOI_OP_PREV, QOI_OP_RGB,
-    QOI_OP_RGBA, QOI_OP_RUN, QOI_PADDING, QOI_PADDING_SIZE,
+    QOI_OP_RGBA, QOI_OP_RUN, QOI_PADDING, QOI_PADDING_SIZE, QOI_PALETTE_MAX_LEN,
+    QOI_PIXELS_PER_BYTE_MAX,
 };
+#[cfg(any(feature = "std", feature = "alloc"))]
+use crate::consts::{QOI_OP_UP_DIFF, QOI_OP_UP_RUN, QOI_OP_UP_RUN_END};
+use crate::crc::Crc32;
 use crate::error::{Error, Result};
 use crate::header::Header;
+use crate::limits::Limits;
+use crate::metadata::Metadata;
 use crate::pixel::{Pixel, SupportedChannels};
+#[cfg(any(feature = "std", feature = "alloc"))]
+use crate::types::ByteOrder;
 use crate::types::Channels;
 use crate::utils::{cold, unlikely};
 use crate::State;
@@ -25,25 +35,226 @@ const QOI_OP_RUN_END: u8 = QOI_OP_RUN | 0x3d; // <- note, 0x3d (not 0x3f)
 const QOI_OP_DIFF_END: u8 = QOI_OP_DIFF | 0x3f;
 const QOI_OP_LUMA_END: u8 = QOI_OP_LUMA | 0x3f;
 
+/// Resumable decoding position: the last decoded pixel, the remainder of a run
+/// that didn't fit into the output, an op split across chunks of data, and the
+/// number of pixels decoded so far along with the slice length (0 if not sliced), and
+/// whether pixels have to be converted back from YCoCg-R. Images predicted from the
+/// previous row also keep the remainder of a run of pixels above, the pixel above the
+/// previous one and the column within the current row.
+#[doc(hidden)]
+#[derive(Copy, Clone, Debug)]
+pub struct Cursor {
+    px: Pixel<4>,
+    run: usize,
+    carry: [u8; 5],
+    n_carry: usize,
+    pos: usize,
+    slice_len: usize,
+    color_transform: bool,
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    up_run: usize,
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    up_left: Pixel<4>,
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    col: usize,
+}
+
+impl Default for Cursor {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            px: Pixel::<4>::new().with_a(0xff),
+            run: 0,
+            carry: [0; 5],
+            n_carry: 0,
+            pos: 0,
+            slice_len: 0,
+            color_transform: false,
+            #[cfg(any(feature = "std", feature = "alloc"))]
+            up_run: 0,
+            #[cfg(any(feature = "std", feature = "alloc"))]
+            up_left: Pixel::<4>::new(),
+            #[cfg(any(feature = "std", feature = "alloc"))]
+            col: 0,
+        }
+    }
+}
+
+impl Cursor {
+    /// Creates a cursor at the start of the image described by the header.
+    #[inline]
+    fn new(header: &Header) -> Self {
+        let slice_len = if header.is_sliced() { header.slice_len() } else { 0 };
+        Self { slice_len, ..Self::new_block(header) }
+    }
+
+    /// Creates a cursor at the start of a single slice or tile of the image.
+    #[inline]
+    fn new_block(header: &Header) -> Self {
+        Self { color_transform: header.color_transform, ..Self::default() }
+    }
+
+    /// Decodes a chunk of data into the output; returns the number of bytes consumed
+    /// and the number of pixels written.
+    ///
+    /// The whole chunk is consumed unless the output fills up, in which case decoding
+    /// stops right after the op that completed it. An incomplete op at the end of the
+    /// chunk is kept and finished with the next one. Every slice but the first one is
+    /// decoded starting from a fresh state.
+    fn decode_chunk(
+        &mut self, state: &mut State, data: &[u8], out: &mut [u8], channels: u8, src_channels: u8,
+    ) -> Result<(usize, usize)> {
+        if self.slice_len == 0 {
+            return self.decode_chunk_impl(state, data, out, channels, src_channels);
+        }
+        let n_channels = channels as usize;
+        let n_pixels = out.len() / n_channels;
+        let (mut n_consumed, mut n_written) = (0, 0);
+        while n_written < n_pixels {
+            if self.pos % self.slice_len == 0 && self.pos != 0 {
+                // a run can't cross the slice boundary, the rest of it is dropped
+                state.restart();
+                self.px = Self::default().px;
+                self.run = 0;
+            }
+            let n = (self.slice_len - self.pos % self.slice_len).min(n_pixels - n_written);
+            let out = &mut out[n_written * n_channels..(n_written + n) * n_channels];
+            let data = &data[n_consumed..];
+            let (n_read, n_decoded) =
+                self.decode_chunk_impl(state, data, out, channels, src_channels)?;
+            n_consumed += n_read;
+            n_written += n_decoded;
+            self.pos += n_decoded;
+            if n_decoded < n {
+                break;
+            }
+        }
+        Ok((n_consumed, n_written))
+    }
+
+    fn decode_chunk_impl(
+        &mut self, state: &mut State, data: &[u8], out: &mut [u8], channels: u8, src_channels: u8,
+    ) -> Result<(usize, usize)> {
+        let n_channels = channels as usize;
+        let n_pixels = out.len() / n_channels;
+
+        // finish the run that didn't fit into the previous output
+        let mut n_written = self.run.min(n_pixels);
+        let rgba: [u8; 4] = output_px::<4>(self.px, self.color_transform);
+        for px_out in out[..n_written * n_channels].chunks_exact_mut(n_channels) {
+            px_out.copy_from_slice(&rgba[..n_channels]);
+        }
+        self.run -= n_written;
+
+        let mut n_consumed = 0;
+        if self.n_carry != 0 && n_written < n_pixels {
+            let n_op = op_len(self.carry[0]);
+            let n_take = (n_op - self.n_carry).min(data.len());
+            self.carry[self.n_carry..self.n_carry + n_take].copy_from_slice(&data[..n_take]);
+            self.n_carry += n_take;
+            n_consumed = n_take;
+            if self.n_carry < n_op {
+                return Ok((n_consumed, n_written));
+            }
+            self.n_carry = 0;
+            let carry = self.carry;
+            let out = &mut out[n_written * n_channels..];
+            n_written +=
+                decode_impl_slice_all(state, self, &carry[..n_op], out, channels, src_channels)?.1;
+        }
+        if n_written < n_pixels {
+            let data = &data[n_consumed..];
+            let out = &mut out[n_written * n_channels..];
+            let (n_read, n) =
+                decode_impl_slice_all(state, self, data, out, channels, src_channels)?;
+            n_written += n;
+            n_consumed += n_read;
+            if n_written < n_pixels {
+                // the rest of the chunk is an incomplete op, at most 4 bytes
+                self.n_carry = data.len() - n_read;
+                self.carry[..self.n_carry].copy_from_slice(&data[n_read..]);
+                n_consumed += self.n_carry;
+            }
+        }
+        Ok((n_consumed, n_written))
+    }
+
+    /// Decodes a chunk of data into the current row of a slice or tile predicted from
+    /// the previous row; returns the number of bytes consumed and whether the row is done.
+    ///
+    /// `above` holds the previous row and is overwritten by the decoded one, both in the
+    /// color space the ops are coded in. As with [`Cursor::decode_chunk`], decoding stops
+    /// right after the op that completed the row, and an incomplete op at the end of the
+    /// chunk is kept and finished with the next one.
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    fn decode_block_chunk(
+        &mut self, state: &mut State, header: &Header, data: &[u8], above: &mut [Pixel<4>],
+    ) -> Result<(usize, bool)> {
+        let mut n_consumed = 0;
+        if self.n_carry != 0 {
+            let n_op = block_op_len(header, self.carry[0]);
+            let n_take = (n_op - self.n_carry).min(data.len());
+            self.carry[self.n_carry..self.n_carry + n_take].copy_from_slice(&data[..n_take]);
+            self.n_carry += n_take;
+            n_consumed = n_take;
+            if self.n_carry < n_op {
+                return Ok((n_consumed, false));
+            }
+            self.n_carry = 0;
+            let carry = self.carry;
+            decode_impl_block(state, self, header, &carry[..n_op], above)?;
+        }
+        let data = &data[n_consumed..];
+        let n_read = decode_impl_block(state, self, header, data, above)?;
+        n_consumed += n_read;
+        let done = self.col == above.len();
+        if !done {
+            // the rest of the chunk is an incomplete op, at most 4 bytes
+            self.n_carry = data.len() - n_read;
+            self.carry[..self.n_carry].copy_from_slice(&data[n_read..]);
+            n_consumed += self.n_carry;
+        }
+        Ok((n_consumed, done))
+    }
+}
+
+/// Converts a decoded pixel into an output one, undoing the color transform if needed.
+#[inline(always)]
+fn output_px<const N: usize>(px: Pixel<4>, color_transform: bool) -> [u8; N] {
+    if color_transform {
+        px.ycocg_to_rgb().into()
+    } else {
+        px.into()
+    }
+}
+
+/// Decodes as many pixels as the available data allows.
+///
+/// Decoding stops either when the output is full or when the remaining data
+/// doesn't contain a complete op; returns the number of bytes read and the
+/// number of pixels written. The last decoded pixel and the part of the last
+/// run that didn't fit are kept in the cursor so that decoding can be resumed.
 #[inline]
-fn decode_impl_slice<const N: usize>(
-    state: &mut State, mut data: &[u8], out: &mut [u8],
-) -> Result<usize>
+fn decode_impl_slice<const N: usize, const YCOCG: bool>(
+    state: &mut State, cursor: &mut Cursor, mut data: &[u8], out: &mut [u8],
+) -> (usize, usize)
 where
     Pixel<N>: SupportedChannels,
     [u8; N]: Pod,
 {
     let mut pixels = cast_slice_mut::<_, [u8; N]>(out);
-    let data_len = data.len();
+    let (data_len, n_pixels) = (data.len(), pixels.len());
+    let mut n_left = 0;
+    let mut run_left = 0;
 
-    let mut px = Pixel::<4>::new().with_a(0xff);
+    let mut px = cursor.px;
 
     while let [px_out, ptail @ ..] = pixels {
         pixels = ptail;
         match data {
             [b1 @ QOI_OP_INDEX..=QOI_OP_INDEX_END, dtail @ ..] => {
                 px = *state.index_l1(*b1 as u16);
-                *px_out = px.into();
+                *px_out = output_px(px, YCOCG);
                 data = dtail;
                 continue;
             }
@@ -56,16 +267,18 @@ where
                 data = dtail;
             }
             [b1 @ QOI_OP_RUN..=QOI_OP_RUN_END, dtail @ ..] => {
-                *px_out = px.into();
-                let run = ((b1 & 0x3f) as usize + 1).min(pixels.len());
+                *px_out = output_px(px, YCOCG);
+                let run = (b1 & 0x3f) as usize + 1;
+                run_left = run.saturating_sub(pixels.len());
+                let run = run.min(pixels.len());
                 let (phead, ptail) = pixels.split_at_mut(run); // can't panic
-                phead.fill(px.into());
+                phead.fill(output_px(px, YCOCG));
                 pixels = ptail;
                 data = dtail;
                 continue;
             }
             [QOI_OP_PREV, dtail @ ..] => {
-                *px_out = px.into();
+                *px_out = output_px(px, YCOCG);
                 data = dtail;
                 continue;
             }
@@ -78,7 +291,7 @@ where
             {
                 let hash_index = ((b1 & 0x3f) as u16) | ((b2 & QOI_OP_LONG_RUN) as u16) << 2;
                 px = *state.index_l2(hash_index);
-                *px_out = px.into();
+                *px_out = output_px(px, YCOCG);
                 let old_px_l1 = replace(state.index_l1(hash_index), px);
                 *state.index_l2(old_px_l1.hash_index()) = old_px_l1;
                 data = dtail;
@@ -87,21 +300,23 @@ where
             [b1 @ QOI_OP_LUMA..=QOI_OP_LUMA_END, b2, dtail @ ..]
                 if b2 & QOI_OP_LONG_RUN == QOI_OP_LONG_RUN =>
             {
-                *px_out = px.into();
+                *px_out = output_px(px, YCOCG);
                 let run = (((b1 & 0x3f) as usize) | ((b2 & QOI_OP_LONG_INDEX) as usize) << 6)
-                    .add((2 + QOI_OP_RUN_END - QOI_OP_RUN) as usize)
-                    .min(pixels.len());
+                    .add((2 + QOI_OP_RUN_END - QOI_OP_RUN) as usize);
+                run_left = run.saturating_sub(pixels.len());
+                let run = run.min(pixels.len());
                 let (phead, ptail) = pixels.split_at_mut(run); // can't panic
-                phead.fill(px.into());
+                phead.fill(output_px(px, YCOCG));
                 pixels = ptail;
                 data = dtail;
                 continue;
             }
             [QOI_OP_LONG_RUN_MAX_0, QOI_OP_LONG_RUN_MAX_1, dtail @ ..] => {
-                *px_out = px.into();
+                *px_out = output_px(px, YCOCG);
+                run_left = 1023_usize.saturating_sub(pixels.len());
                 let run = 1023.min(pixels.len());
                 let (phead, ptail) = pixels.split_at_mut(run); // can't panic
-                phead.fill(px.into());
+                phead.fill(output_px(px, YCOCG));
                 pixels = ptail;
                 data = dtail;
                 continue;
@@ -112,28 +327,32 @@ where
             }
             _ => {
                 cold();
-                return Err(Error::UnexpectedBufferEnd);
+                n_left = pixels.len() + 1;
+                break;
             }
         }
         // Move px into l1 and evicted l1 into l2
         let old_px_l1 = replace(state.index_l1(px.hash_index()), px);
         *state.index_l2(old_px_l1.hash_index()) = old_px_l1;
 
-        *px_out = px.into();
+        *px_out = output_px(px, YCOCG);
     }
 
-    Ok(data_len.saturating_sub(data.len()))
+    cursor.px = px;
+    cursor.run = run_left;
+    (data_len.saturating_sub(data.len()), n_pixels - n_left)
 }
 
 #[inline]
 fn decode_impl_slice_all(
-    state: &mut State, data: &[u8], out: &mut [u8], channels: u8, src_channels: u8,
-) -> Result<usize> {
-    match (channels, src_channels) {
-        (3, 3) => decode_impl_slice::<3>(state, data, out),
-        (3, 4) => decode_impl_slice::<3>(state, data, out),
-        (4, 3) => decode_impl_slice::<4>(state, data, out),
-        (4, 4) => decode_impl_slice::<4>(state, data, out),
+    state: &mut State, cursor: &mut Cursor, data: &[u8], out: &mut [u8], channels: u8,
+    src_channels: u8,
+) -> Result<(usize, usize)> {
+    match (channels, src_channels, cursor.color_transform) {
+        (3, 3 | 4, false) => Ok(decode_impl_slice::<3, false>(state, cursor, data, out)),
+        (4, 3 | 4, false) => Ok(decode_impl_slice::<4, false>(state, cursor, data, out)),
+        (3, 3 | 4, true) => Ok(decode_impl_slice::<3, true>(state, cursor, data, out)),
+        (4, 3 | 4, true) => Ok(decode_impl_slice::<4, true>(state, cursor, data, out)),
         _ => {
             cold();
             Err(Error::InvalidChannels { channels })
@@ -141,15 +360,155 @@ fn decode_impl_slice_all(
     }
 }
 
+/// Returns the total length of an op given its first byte.
+#[inline]
+const fn op_len(b1: u8) -> usize {
+    match b1 {
+        QOI_OP_RGB => 4,
+        QOI_OP_RGBA => 5,
+        QOI_OP_LUMA..=QOI_OP_LUMA_END => 2,
+        _ => 1,
+    }
+}
+
+/// Returns the total length of an op given its first byte, in an image predicted from
+/// the previous row or coded against a palette.
+#[cfg(any(feature = "std", feature = "alloc"))]
+#[inline]
+const fn block_op_len(header: &Header, b1: u8) -> usize {
+    match b1 {
+        QOI_OP_UP_DIFF if header.vertical_prediction => 2,
+        QOI_OP_RGB if header.has_palette() => 2,
+        _ => op_len(b1),
+    }
+}
+
+/// Decodes as many pixels of the current row of a slice or tile predicted from the
+/// previous row as the available data allows, into `above`; returns the number of bytes
+/// read. Runs carry over into the next row.
+#[cfg(any(feature = "std", feature = "alloc"))]
+#[inline]
+#[allow(clippy::cast_possible_truncation)]
+fn decode_impl_block(
+    state: &mut State, cursor: &mut Cursor, header: &Header, mut data: &[u8],
+    above: &mut [Pixel<4>],
+) -> Result<usize> {
+    let data_len = data.len();
+    let (vertical, predicting) = (header.vertical_prediction, !header.predictor.is_left());
+    let palette = header.has_palette();
+    let (mut px, mut px_up_left) = (cursor.px, cursor.up_left);
+    let (mut run, mut up_run, mut col) = (cursor.run, cursor.up_run, cursor.col);
+
+    for px_above in &mut above[col..] {
+        let px_up = *px_above;
+        let px_left = if col == 0 { px_up } else { px };
+        if col == 0 {
+            px_up_left = px_up;
+        }
+        let predict = move |px: Pixel<4>| px.predict(header.predictor, px_left, px_up, px_up_left);
+        if run != 0 {
+            run -= 1;
+        } else if up_run != 0 {
+            up_run -= 1;
+            px = px_up;
+        } else {
+            let (update_index, dtail) = match data {
+                [b1 @ QOI_OP_INDEX..=QOI_OP_INDEX_END, dtail @ ..] => {
+                    px = *state.index_l1(u16::from(*b1));
+                    (false, dtail)
+                }
+                // with a predictor, this is a difference of zero to the prediction
+                [QOI_OP_PREV, dtail @ ..] if !predicting => (false, dtail),
+                [b1 @ QOI_OP_DIFF..=QOI_OP_DIFF_END, dtail @ ..] => {
+                    px = predict(px);
+                    px.update_diff(*b1);
+                    (true, dtail)
+                }
+                [b1 @ QOI_OP_LUMA..=QOI_OP_LUMA_END, b2, dtail @ ..] => {
+                    let (b1, b2) = (*b1, *b2);
+                    if b2 & QOI_OP_LONG_INDEX == QOI_OP_LONG_INDEX {
+                        let hash_index = u16::from(b1 & 0x3f) | u16::from(b2 & 0xf0) << 2;
+                        px = *state.index_l2(hash_index);
+                        let old_px_l1 = replace(state.index_l1(hash_index), px);
+                        *state.index_l2(old_px_l1.hash_index()) = old_px_l1;
+                        (false, dtail)
+                    } else if b2 & QOI_OP_LONG_RUN == QOI_OP_LONG_RUN {
+                        let n = (b1 & 0x3f) as usize | ((b2 & QOI_OP_LONG_INDEX) as usize) << 6;
+                        run = n + 63;
+                        (false, dtail)
+                    } else if (b1, b2) == (QOI_OP_LONG_RUN_MAX_0, QOI_OP_LONG_RUN_MAX_1) {
+                        run = 1023;
+                        (false, dtail)
+                    } else {
+                        px = predict(px);
+                        px.update_luma(b1, b2);
+                        (true, dtail)
+                    }
+                }
+                [b1 @ QOI_OP_UP_RUN..=QOI_OP_UP_RUN_END, dtail @ ..] if vertical => {
+                    up_run = (b1 & 0x1f) as usize;
+                    px = px_up;
+                    (false, dtail)
+                }
+                [QOI_OP_UP_DIFF, b2, dtail @ ..] if vertical => {
+                    px = px_up;
+                    px.update_up_diff(*b2);
+                    (true, dtail)
+                }
+                [QOI_OP_UP_DIFF] if vertical => break,
+                [b1 @ QOI_OP_RUN..=QOI_OP_RUN_END, dtail @ ..] => {
+                    run = (b1 & 0x3f) as usize + 1;
+                    (false, dtail)
+                }
+                [QOI_OP_RGB, index, dtail @ ..] if palette => {
+                    px = state.palette_px(*index)?;
+                    (true, dtail)
+                }
+                [QOI_OP_RGB, r, g, b, dtail @ ..] if !palette => {
+                    px.update_rgb(*r, *g, *b);
+                    (true, dtail)
+                }
+                [QOI_OP_RGBA, r, g, b, a, dtail @ ..] => {
+                    px.update_rgba(*r, *g, *b, *a);
+                    (true, dtail)
+                }
+                _ => break,
+            };
+            data = dtail;
+            if update_index {
+                let old_px_l1 = replace(state.index_l1(px.hash_index()), px);
+                *state.index_l2(old_px_l1.hash_index()) = old_px_l1;
+            }
+        }
+        px_up_left = px_up;
+        *px_above = px;
+        col += 1;
+    }
+
+    (cursor.px, cursor.up_left) = (px, px_up_left);
+    (cursor.run, cursor.up_run, cursor.col) = (run, up_run, col);
+    Ok(data_len - data.len())
+}
+
+/// Converts a decoded row of an image predicted from the previous row into output pixels.
+#[inline]
+fn output_row(row: &[Pixel<4>], out: &mut [u8], channels: u8, color_transform: bool) {
+    let n_channels = channels as usize;
+    for (px_out, px) in out.chunks_exact_mut(n_channels).zip(row) {
+        let rgba: [u8; 4] = output_px(*px, color_transform);
+        px_out.copy_from_slice(&rgba[..n_channels]);
+    }
+}
+
 /// Decode the image into a pre-allocated buffer.
 ///
 /// Note: the resulting number of channels will match the header. In order to change
-/// the number of channels, use [`Decoder::with_channels`].
+/// the number of channels, use [`Decoder::with_channels`]. No [`Limits`] apply.
 #[inline]
 pub fn decode_to_buf<const DATA_ONLY: bool>(
     buf: impl AsMut<[u8]>, data: impl AsRef<[u8]>,
 ) -> Result<Header> {
-    let mut decoder = Decoder::new(&data)?;
+    let mut decoder = Decoder::new(&data)?.with_limits(Limits::none());
     decoder.decode_to_buf::<DATA_ONLY>(buf)?;
     Ok(*decoder.header())
 }
@@ -157,290 +516,1079 @@ pub fn decode_to_buf<const DATA_ONLY: bool>(
 /// Decode the image into a newly allocated vector.
 ///
 /// Note: the resulting number of channels will match the header. In order to change
-/// the number of channels, use [`Decoder::with_channels`].
+/// the number of channels, use [`Decoder::with_channels`]. No [`Limits`] apply, but
+/// the image can't take more memory than the input is able to encode; use
+/// [`Decoder::with_limits`] to restrict it further.
 #[cfg(any(feature = "std", feature = "alloc"))]
 #[inline]
 pub fn decode_to_vec<const DATA_ONLY: bool>(data: impl AsRef<[u8]>) -> Result<(Header, Vec<u8>)> {
-    let mut decoder = Decoder::new(&data)?;
+    let mut decoder = Decoder::new(&data)?.with_limits(Limits::none());
     let out = decoder.decode_to_vec::<DATA_ONLY>()?;
     Ok((*decoder.header(), out))
 }
 
-/// Decode the image header from a slice of bytes.
-#[inline]
-pub fn decode_header(data: impl AsRef<[u8]>) -> Result<Header> {
-    Header::decode(data)
-}
-
-#[cfg(any(feature = "std"))]
+/// Decode the image into a newly allocated vector of typed pixels.
+///
+/// The number of channels is given by the pixel type, `[u8; 3]` or `[u8; 4]`,
+/// converting from the header if needed. As with [`decode_to_vec`], no [`Limits`] apply.
+#[cfg(any(feature = "std", feature = "alloc"))]
 #[inline]
-fn decode_impl_stream<R: Read, const N: usize>(
-    state: &mut State, data: &mut R, out: &mut [u8],
-) -> Result<()>
+pub fn decode_to_pixels<const DATA_ONLY: bool, const N: usize>(
+    data: impl AsRef<[u8]>,
+) -> Result<(Header, Vec<[u8; N]>)>
 where
     Pixel<N>: SupportedChannels,
     [u8; N]: Pod,
 {
-    let mut pixels = cast_slice_mut::<_, [u8; N]>(out);
-
-    let mut px = Pixel::<4>::new().with_a(0xff);
-
-    while let [px_out, ptail @ ..] = pixels {
-        pixels = ptail;
-        let mut p = [0];
-        data.read_exact(&mut p)?;
-        let [b1] = p;
-        match b1 {
-            QOI_OP_INDEX..=QOI_OP_INDEX_END => {
-                px = *state.index_l1(b1 as u16);
-                *px_out = px.into();
-                continue;
-            }
-            QOI_OP_RGB => {
-                let mut p = [0; 3];
-                data.read_exact(&mut p)?;
-                px.update_rgb(p[0], p[1], p[2]);
-            }
-            QOI_OP_RGBA => {
-                let mut p = [0; 4];
-                data.read_exact(&mut p)?;
-                px.update_rgba(p[0], p[1], p[2], p[3]);
-            }
-            QOI_OP_RUN..=QOI_OP_RUN_END => {
-                *px_out = px.into();
-                let run = ((b1 & 0x3f) as usize + 1).min(pixels.len());
-                let (phead, ptail) = pixels.split_at_mut(run); // can't panic
-                phead.fill(px.into());
-                pixels = ptail;
-                continue;
-            }
-            QOI_OP_PREV => {
-                *px_out = px.into();
-                continue;
-            }
-            QOI_OP_DIFF..=QOI_OP_DIFF_END => {
-                px.update_diff(b1);
-            }
-            QOI_OP_LUMA..=QOI_OP_LUMA_END => {
-                let mut p = [0];
-                data.read_exact(&mut p)?;
-                let [b2] = p;
-                match b2 {
-                    _ if b2 & QOI_OP_LONG_INDEX == QOI_OP_LONG_INDEX => {
-                        let hash_index =
-                            ((b1 & 0x3f) as u16) | ((b2 & QOI_OP_LONG_RUN) as u16) << 2;
-                        px = *state.index_l2(hash_index);
-                        *px_out = px.into();
-                        // Move chosen l2 into l1 and evicted l1 into l2
-                        let old_px_l1 = replace(state.index_l1(hash_index), px);
-                        *state.index_l2(old_px_l1.hash_index()) = old_px_l1;
-                        continue;
-                    }
-                    _ if b2 & QOI_OP_LONG_RUN == QOI_OP_LONG_RUN => {
-                        *px_out = px.into();
-                        let run = (((b1 & 0x3f) as usize)
-                            | ((b2 & QOI_OP_LONG_INDEX) as usize) << 6)
-                            .add((2 + QOI_OP_RUN_END - QOI_OP_RUN) as usize)
-                            .min(pixels.len());
-                        let (phead, ptail) = pixels.split_at_mut(run); // can't panic
-                        phead.fill(px.into());
-                        pixels = ptail;
-                        continue;
-                    }
-                    QOI_OP_LONG_RUN_MAX_1 if b1 == QOI_OP_LONG_RUN_MAX_0 => {
-                        *px_out = px.into();
-                        let run = 1023.min(pixels.len());
-                        let (phead, ptail) = pixels.split_at_mut(run); // can't panic
-                        phead.fill(px.into());
-                        pixels = ptail;
-                        continue;
-                    }
-                    _ => {
-                        px.update_luma(b1, b2);
-                    }
-                }
-            }
-        }
-        // Move px into l1 and evicted l1 into l2
-        let old_px_l1 = replace(state.index_l1(px.hash_index()), px);
-        *state.index_l2(old_px_l1.hash_index()) = old_px_l1;
-
-        *px_out = px.into();
-    }
-    Ok(())
+    let mut decoder = Decoder::new(&data)?.with_limits(Limits::none());
+    let out = decoder.decode_to_pixels::<DATA_ONLY, N>()?;
+    Ok((*decoder.header(), out))
 }
 
-#[cfg(feature = "std")]
+/// Decode the image header from a slice of bytes.
 #[inline]
-fn decode_impl_stream_all<R: Read>(
-    state: &mut State, data: &mut R, out: &mut [u8], channels: u8, src_channels: u8,
-) -> Result<()> {
-    match (channels, src_channels) {
-        (3, 3) => decode_impl_stream::<_, 3>(state, data, out),
-        (3, 4) => decode_impl_stream::<_, 3>(state, data, out),
-        (4, 3) => decode_impl_stream::<_, 4>(state, data, out),
-        (4, 4) => decode_impl_stream::<_, 4>(state, data, out),
-        _ => {
-            cold();
-            Err(Error::InvalidChannels { channels })
-        }
-    }
+pub fn decode_header(data: impl AsRef<[u8]>) -> Result<Header> {
+    Header::decode(data)
 }
 
 #[doc(hidden)]
 pub trait Reader: Sized {
-    fn decode_header(&mut self) -> Result<Header>;
-    fn decode_image<const DATA_ONLY: bool>(
-        &mut self, state: &mut State, out: &mut [u8], channels: u8, src_channels: u8,
+    /// Decodes the header along with the metadata chunks following it, and loads the
+    /// palette following those (if any) into the state. Chunks that aren't in memory yet
+    /// are only read if they fit into `max_alloc` bytes.
+    fn decode_header(
+        &mut self, state: &mut State, metadata: &mut Metadata, max_alloc: usize,
+    ) -> Result<Header>;
+
+    /// Decodes exactly as many pixels as fit into the output, resuming from the cursor.
+    fn decode_pixels(
+        &mut self, state: &mut State, cursor: &mut Cursor, out: &mut [u8], channels: u8,
+        src_channels: u8,
     ) -> Result<()>;
-}
 
-pub struct Bytes<'a>(&'a [u8]);
+    fn decode_padding<const DATA_ONLY: bool>(&mut self) -> Result<()>;
 
-impl<'a> Bytes<'a> {
+    /// Upper bound on the number of pixels the rest of the input can encode, so that
+    /// a bogus header is rejected before allocating for it; unknown for streams.
     #[inline]
-    pub const fn new(buf: &'a [u8]) -> Self {
-        Self(buf)
+    fn max_pixels(&self, _header: &Header) -> usize {
+        usize::MAX
     }
 
-    #[inline]
-    pub const fn as_slice(&self) -> &[u8] {
-        self.0
-    }
-}
+    /// Reads the checksum following the padding.
+    fn decode_checksum(&mut self) -> Result<u32>;
 
-impl<'a> Reader for Bytes<'a> {
+    /// Decodes the padding and the checksum following it, if any, and compares the latter
+    /// to the checksum of the decoded pixels, unless that couldn't be computed.
     #[inline]
-    fn decode_header(&mut self) -> Result<Header> {
-        let header = Header::decode(self.0)?;
-        self.0 = &self.0[QOI_HEADER_SIZE..]; // can't panic
-        Ok(header)
+    fn decode_end<const DATA_ONLY: bool>(
+        &mut self, header: &Header, crc: Option<Crc32>,
+    ) -> Result<()> {
+        self.decode_padding::<DATA_ONLY>()?;
+        if DATA_ONLY || !header.checksum {
+            return Ok(());
+        }
+        let expected = self.decode_checksum()?;
+        match crc.map(Crc32::finish) {
+            Some(found) if unlikely(found != expected) => {
+                Err(Error::ChecksumMismatch { expected, found })
+            }
+            _ => Ok(()),
+        }
     }
 
+    /// Reads and decodes the entropy-coded op stream holding at most `max_len` bytes,
+    /// failing with [`Error::LimitsExceeded`] if it holds more than `max_alloc`.
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    fn decode_entropy(&mut self, max_len: usize, max_alloc: usize) -> Result<Vec<u8>>;
+
+    /// Decodes the next row of a slice or tile predicted from the previous row into
+    /// `above`, resuming from the cursor.
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    fn decode_block_row(
+        &mut self, state: &mut State, cursor: &mut Cursor, header: &Header, above: &mut [Pixel<4>],
+    ) -> Result<()>;
+
     #[inline]
     fn decode_image<const DATA_ONLY: bool>(
-        &mut self, state: &mut State, out: &mut [u8], channels: u8, src_channels: u8,
+        &mut self, state: &mut State, header: &Header, out: &mut [u8], channels: u8,
+        max_alloc: usize,
     ) -> Result<()> {
-        let n_read = decode_impl_slice_all(state, self.0, out, channels, src_channels)?;
-        self.0 = &self.0[n_read..];
-        if !DATA_ONLY && unlikely(self.0.len() < QOI_PADDING_SIZE) {
-            Err(Error::UnexpectedBufferEnd)
-        } else if !DATA_ONLY && unlikely(self.0[..QOI_PADDING_SIZE] != QOI_PADDING) {
-            Err(Error::InvalidPadding)
+        if header.entropy_coded {
+            decode_entropy_image(self, &[], state, header, out, channels, max_alloc)?;
+        } else if header.is_block_coded() {
+            decode_whole_blocks_image(self, state, header, out, channels, max_alloc)?;
+        } else if header.is_tiled() {
+            decode_tiles(self, state, header, out, channels)?;
         } else {
-            Ok(())
+            let src_channels = header.channels.as_u8();
+            self.decode_pixels(state, &mut Cursor::new(header), out, channels, src_channels)?;
         }
-    }
-}
-
-#[cfg(feature = "std")]
-impl<R: Read> Reader for R {
-    #[inline]
-    fn decode_header(&mut self) -> Result<Header> {
-        let mut b = [0; QOI_HEADER_SIZE];
-        self.read_exact(&mut b)?;
-        Header::decode(b)
+        self.decode_end::<DATA_ONLY>(header, pixels_crc(header, out, channels))
     }
 
     #[inline]
-    fn decode_image<const DATA_ONLY: bool>(
-        &mut self, state: &mut State, out: &mut [u8], channels: u8, src_channels: u8,
+    fn decode_rows<const DATA_ONLY: bool, F: FnMut(u32, &[u8])>(
+        &mut self, state: &mut State, header: &Header, row: &mut [u8], channels: u8,
+        max_alloc: usize, mut on_row: F,
     ) -> Result<()> {
-        decode_impl_stream_all(state, self, out, channels, src_channels)?;
-        if !DATA_ONLY && {
-            let mut p = [0; QOI_PADDING_SIZE];
-            self.read_exact(&mut p)?;
-            p != QOI_PADDING
-        } {
-            Err(Error::InvalidPadding)
+        if unlikely(header.is_tiled()) {
+            return Err(Error::UnsupportedTiling);
+        }
+        let src_channels = header.channels.as_u8();
+        let verify = header.checksum && channels >= src_channels;
+        let mut crc = Crc32::new();
+        let mut on_row = |y: u32, row: &[u8]| {
+            if verify {
+                crc.update_pixels(row, channels, src_channels);
+            }
+            on_row(y, row);
+        };
+        if header.entropy_coded {
+            decode_entropy_rows(self, state, header, row, channels, max_alloc, &mut on_row)?;
+        } else if header.is_block_coded() {
+            #[allow(clippy::cast_possible_truncation)]
+            decode_whole_blocks(self, state, header, max_alloc, |_, y, px_row| {
+                let row = &mut row[..px_row.len() * channels as usize];
+                output_row(px_row, row, channels, header.color_transform);
+                on_row(y as u32, row);
+            })?;
         } else {
-            Ok(())
+            let mut cursor = Cursor::new(header);
+            for y in 0..header.height {
+                self.decode_pixels(state, &mut cursor, row, channels, src_channels)?;
+                on_row(y, row);
+            }
         }
+        self.decode_end::<DATA_ONLY>(header, verify.then(|| crc))
     }
 }
 
-/// Decode QOI images from slices or from streams.
-#[derive(Clone)]
-pub struct Decoder<R> {
-    reader: R,
-    header: Header,
-    channels: Channels,
-    state: State,
+/// Input a [`Decoder`] can be created from: a slice of bytes or a stream, along with
+/// the [`Reader`] it's decoded through.
+#[doc(hidden)]
+pub trait Source: Sized {
+    type Reader: Reader;
 }
 
-impl<'a> Decoder<Bytes<'a>> {
-    /// Creates a new decoder from a slice of bytes.
-    ///
-    /// The header will be decoded immediately upon construction.
-    ///
-    /// Note: this provides the most efficient decoding, but requires the source data to
-    /// be loaded in memory in order to decode it. In order to decode from a generic
-    /// stream, use [`Decoder::from_stream`] instead.
-    /// #[inline]
-    pub fn new(data: &'a (impl AsRef<[u8]> + ?Sized)) -> Result<Self> {
-        let mut reader = Bytes::new(data.as_ref());
-        Ok(Self::new_with(reader.decode_header()?, State::default(), reader))
-    }
-    #[inline]
-    pub fn new_with(header: Header, state: State, reader: Bytes<'a>) -> Self {
-        Self::new_impl(header, state, reader)
-    }
-
-    /// Returns the undecoded tail of the input slice of bytes.
-    #[inline]
-    pub const fn data(&self) -> &[u8] {
-        self.reader.as_slice()
-    }
+impl Source for Bytes<'_> {
+    type Reader = Self;
 }
 
 #[cfg(feature = "std")]
-impl<R: Read> Decoder<R> {
-    /// Creates a new decoder from a generic reader that implements [`Read`](std::io::Read).
-    ///
-    /// The header will be decoded immediately upon construction.
-    ///
-    /// Note: while it's possible to pass a `&[u8]` slice here since it implements `Read`, it
-    /// would be more efficient to use a specialized constructor instead: [`Decoder::new`].
-    #[inline]
-    pub fn from_stream(reader: R) -> Result<Self> {
-        Self::from_stream_with(State::default(), reader)
-    }
-    #[inline]
-    pub fn from_stream_with(state: State, mut reader: R) -> Result<Self> {
-        Ok(Self::new_impl(reader.decode_header()?, state, reader))
-    }
-
-    /// Returns an immutable reference to the underlying reader.
-    #[inline]
-    pub const fn reader(&self) -> &R {
-        &self.reader
-    }
+impl<R: Read> Source for R {
+    type Reader = Stream<R>;
+}
 
-    /// Consumes the decoder and returns the underlying reader back.
-    #[inline]
-    #[allow(clippy::missing_const_for_fn)]
-    pub fn into_reader(self) -> R {
-        self.reader
-    }
+/// Returns the checksum of a decoded image if the header asks for one and it can be
+/// computed, i.e. unless the alpha channel has been dropped.
+#[inline]
+fn pixels_crc(header: &Header, out: &[u8], channels: u8) -> Option<Crc32> {
+    let src_channels = header.channels.as_u8();
+    (header.checksum && channels >= src_channels).then(|| {
+        let mut crc = Crc32::new();
+        crc.update_pixels(out, channels, src_channels);
+        crc
+    })
 }
 
-impl<R: Reader> Decoder<R> {
-    #[inline]
-    fn new_impl(header: Header, state: State, reader: R) -> Self {
-        Self { reader, header, channels: header.channels, state }
-    }
+/// Upper bound on the length of the op stream of an image.
+#[cfg(any(feature = "std", feature = "alloc"))]
+#[inline]
+const fn max_ops_len(header: &Header) -> usize {
+    header.n_pixels().saturating_mul(header.channels.as_u8() as usize + 1)
+}
 
-    /// Returns a new decoder with modified number of channels.
-    ///
-    /// By default, the number of channels in the decoded image will be equal
-    /// to whatever is specified in the header. However, it is also possible
-    /// to decode RGB into RGBA (in which case the alpha channel will be set
-    /// to 255), and vice versa (in which case the alpha channel will be ignored).
-    #[inline]
-    pub const fn with_channels(mut self, channels: Channels) -> Self {
-        self.channels = channels;
-        self
-    }
+/// Decodes the entropy-coded op stream into memory first, then the image from it.
+#[cfg(any(feature = "std", feature = "alloc"))]
+#[inline]
+fn decode_entropy_image<R: Reader>(
+    reader: &mut R, table: &[u8], state: &mut State, header: &Header, out: &mut [u8], channels: u8,
+    max_alloc: usize,
+) -> Result<()> {
+    let ops = reader.decode_entropy(max_ops_len(header), max_alloc)?;
+    let header = header.with_entropy_coding(false).with_checksum(false);
+    Bytes { data: &ops, table }.decode_image::<true>(state, &header, out, channels, max_alloc)
+}
+
+#[cfg(not(any(feature = "std", feature = "alloc")))]
+#[inline]
+fn decode_entropy_image<R: Reader>(
+    _: &mut R, _: &[u8], _: &mut State, _: &Header, _: &mut [u8], _: u8, _: usize,
+) -> Result<()> {
+    Err(Error::UnsupportedEntropyCoding)
+}
+
+/// Decodes the entropy-coded op stream into memory first, then the image from it row by row.
+#[cfg(any(feature = "std", feature = "alloc"))]
+#[inline]
+fn decode_entropy_rows<R: Reader>(
+    reader: &mut R, state: &mut State, header: &Header, row: &mut [u8], channels: u8,
+    max_alloc: usize, on_row: &mut dyn FnMut(u32, &[u8]),
+) -> Result<()> {
+    let ops = reader.decode_entropy(max_ops_len(header), max_alloc)?;
+    let header = header.with_entropy_coding(false).with_checksum(false);
+    Bytes::new(&ops).decode_rows::<true, _>(state, &header, row, channels, max_alloc, on_row)
+}
+
+#[cfg(not(any(feature = "std", feature = "alloc")))]
+#[inline]
+fn decode_entropy_rows<R: Reader>(
+    _: &mut R, _: &mut State, _: &Header, _: &mut [u8], _: u8, _: usize,
+    _: &mut dyn FnMut(u32, &[u8]),
+) -> Result<()> {
+    Err(Error::UnsupportedEntropyCoding)
+}
+
+/// Loads the palette stored after the header into the state, converting its colors
+/// into the color space the ops are coded in; clears it if there's none.
+#[inline]
+fn load_palette(state: &mut State, header: &Header, data: &[u8]) {
+    let mut palette = [Pixel::new(); QOI_PALETTE_MAX_LEN];
+    let n = data.len() / 4;
+    for (px, chunk) in palette.iter_mut().zip(data.chunks_exact(4)) {
+        px.read_exact(chunk);
+        if header.color_transform {
+            *px = px.rgb_to_ycocg();
+        }
+    }
+    state.set_palette(&palette[..n]);
+}
+
+/// Allocates the previous row for decoding the slices or tiles of an image predicted from
+/// the previous row, the only allocation that needs; it counts against `max_alloc`.
+#[cfg(any(feature = "std", feature = "alloc"))]
+#[inline]
+fn alloc_above(header: &Header, max_alloc: usize) -> Result<Vec<Pixel<4>>> {
+    let width = header.block_size().0;
+    if unlikely(width.saturating_mul(core::mem::size_of::<Pixel<4>>()) > max_alloc) {
+        return Err(Error::LimitsExceeded);
+    }
+    Ok(vec![Pixel::<4>::new(); width])
+}
+
+/// Decodes the `i`-th slice or tile of an image predicted from the previous row or coded
+/// against a palette, passing each row, still in the color space the ops are coded in, to
+/// `on_row` along with its index within the block.
+#[cfg(any(feature = "std", feature = "alloc"))]
+#[inline]
+fn decode_whole_block<R: Reader>(
+    reader: &mut R, state: &mut State, header: &Header, i: usize, above: &mut [Pixel<4>],
+    mut on_row: impl FnMut(usize, &[Pixel<4>]),
+) -> Result<()> {
+    let (_, _, w, h) = header.block_rect(i);
+    // the row above the first one is all opaque black
+    let above = &mut above[..w];
+    above.fill(Pixel::<4>::new().with_a(0xff));
+    let mut cursor = Cursor::new_block(header);
+    for r in 0..h {
+        cursor.col = 0;
+        reader.decode_block_row(state, &mut cursor, header, above)?;
+        on_row(r, above);
+    }
+    Ok(())
+}
+
+/// Decodes all slices or tiles of an image predicted from the previous row, passing each
+/// row to `on_row` along with the position of its first pixel.
+#[cfg(any(feature = "std", feature = "alloc"))]
+#[inline]
+fn decode_whole_blocks<R: Reader>(
+    reader: &mut R, state: &mut State, header: &Header, max_alloc: usize,
+    mut on_row: impl FnMut(usize, usize, &[Pixel<4>]),
+) -> Result<()> {
+    let mut above = alloc_above(header, max_alloc)?;
+    for i in 0..header.n_blocks() {
+        if i != 0 {
+            state.restart();
+        }
+        let (x, y, _, _) = header.block_rect(i);
+        decode_whole_block(reader, state, header, i, &mut above, |r, row| on_row(x, y + r, row))?;
+    }
+    Ok(())
+}
+
+#[cfg(not(any(feature = "std", feature = "alloc")))]
+#[inline]
+fn decode_whole_blocks<R: Reader>(
+    _: &mut R, _: &mut State, header: &Header, _: usize, _: impl FnMut(usize, usize, &[Pixel<4>]),
+) -> Result<()> {
+    Err(header.block_coded_error())
+}
+
+/// Decodes an image predicted from the previous row, each row into its place in the output.
+#[inline]
+fn decode_whole_blocks_image<R: Reader>(
+    reader: &mut R, state: &mut State, header: &Header, out: &mut [u8], channels: u8,
+    max_alloc: usize,
+) -> Result<()> {
+    let (width, n_channels) = (header.width as usize, channels as usize);
+    decode_whole_blocks(reader, state, header, max_alloc, |x, y, row| {
+        let start = (y * width + x) * n_channels;
+        let out = &mut out[start..start + row.len() * n_channels];
+        output_row(row, out, channels, header.color_transform);
+    })
+}
+
+/// Decodes a tiled image tile by tile, each of them row by row into its place in the output.
+#[inline]
+fn decode_tiles<R: Reader>(
+    reader: &mut R, state: &mut State, header: &Header, out: &mut [u8], channels: u8,
+) -> Result<()> {
+    let (width, n_channels) = (header.width as usize, channels as usize);
+    let src_channels = header.channels.as_u8();
+    for i in 0..header.n_blocks() {
+        if i != 0 {
+            state.restart();
+        }
+        let (x, y, w, h) = header.block_rect(i);
+        let mut cursor = Cursor::new_block(header);
+        for row in y..y + h {
+            let start = (row * width + x) * n_channels;
+            let out = &mut out[start..start + w * n_channels];
+            reader.decode_pixels(state, &mut cursor, out, channels, src_channels)?;
+        }
+    }
+    Ok(())
+}
+
+pub struct Bytes<'a> {
+    data: &'a [u8],
+    table: &'a [u8],
+}
+
+impl<'a> Bytes<'a> {
+    #[inline]
+    pub const fn new(buf: &'a [u8]) -> Self {
+        Self { data: buf, table: &[] }
+    }
+
+    #[inline]
+    pub const fn as_slice(&self) -> &[u8] {
+        self.data
+    }
+
+    /// Returns the byte range of the `i`-th slice or tile within the data, as per the table.
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    #[inline]
+    fn block_range(&self, i: usize) -> Result<(usize, usize)> {
+        let end_of = |i: usize| {
+            let end = &self.table[i * 4..i * 4 + 4];
+            u32::from_be_bytes([end[0], end[1], end[2], end[3]]) as usize
+        };
+        let (start, end) = (if i == 0 { 0 } else { end_of(i - 1) }, end_of(i));
+        if unlikely(start > end || end > self.data.len()) {
+            return Err(Error::InvalidHeaderExtension);
+        }
+        Ok((start, end))
+    }
+
+    /// Decodes the `(x, y, width, height)` region of the image into the output.
+    ///
+    /// With the offset table, only the slices or tiles intersecting the region are decoded,
+    /// and only down to its last row; without it, everything up to the region is decoded.
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    fn decode_region(
+        &self, state: &State, header: &Header, region: (usize, usize, usize, usize),
+        out: &mut [u8], channels: u8, max_alloc: usize,
+    ) -> Result<()> {
+        if header.entropy_coded {
+            let mut bytes = Bytes { data: self.data, table: self.table };
+            let ops = bytes.decode_entropy(max_ops_len(header), max_alloc)?;
+            let header = header.with_entropy_coding(false);
+            return Bytes { data: &ops, table: self.table }
+                .decode_region(state, &header, region, out, channels, max_alloc);
+        }
+        let (rx, ry, rw, rh) = region;
+        let (n_channels, src_channels) = (channels as usize, header.channels.as_u8());
+        let has_table = !self.table.is_empty();
+        let mut row = vec![0; header.block_size().0 * n_channels];
+        let mut above =
+            if header.is_block_coded() { alloc_above(header, max_alloc)? } else { vec![] };
+        let mut data = self.data;
+        for i in 0..header.n_blocks() {
+            let (x, y, w, h) = header.block_rect(i);
+            if y >= ry + rh {
+                break;
+            }
+            let hit = x < rx + rw && rx < x + w && ry < y + h;
+            let last = hit && x + w >= rx + rw && y + h >= ry + rh;
+            let mut bytes = if !has_table {
+                Bytes::new(data)
+            } else if hit {
+                let (start, end) = self.block_range(i)?;
+                Bytes::new(&self.data[start..end])
+            } else {
+                continue;
+            };
+            let mut state = if i == 0 { state.clone() } else { state.fresh() };
+            let mut cursor = Cursor::new_block(header);
+            // without the table, a block has to be decoded fully to find the next one
+            let n_rows = if hit && (has_table || last) { (ry + rh).min(y + h) - y } else { h };
+            let (x0, x1) = (rx.max(x), (rx + rw).min(x + w));
+            let mut copy_row = |r: usize, row: &[u8]| {
+                if hit && r >= ry && r < ry + rh {
+                    let start = ((r - ry) * rw + x0 - rx) * n_channels;
+                    out[start..start + (x1 - x0) * n_channels]
+                        .copy_from_slice(&row[(x0 - x) * n_channels..(x1 - x) * n_channels]);
+                }
+            };
+            if header.is_block_coded() {
+                // rows are decoded as a whole block, which is always decoded fully
+                decode_whole_block(&mut bytes, &mut state, header, i, &mut above, |r, px_row| {
+                    let row = &mut row[..w * n_channels];
+                    output_row(px_row, row, channels, header.color_transform);
+                    copy_row(y + r, row);
+                })?;
+            } else {
+                for r in y..y + n_rows {
+                    let row = &mut row[..w * n_channels];
+                    bytes.decode_pixels(&mut state, &mut cursor, row, channels, src_channels)?;
+                    copy_row(r, row);
+                }
+            }
+            if last {
+                break;
+            }
+            data = bytes.data;
+        }
+        Ok(())
+    }
+
+    /// Decodes the slices listed in the slice table on multiple threads.
+    #[cfg(feature = "rayon")]
+    fn decode_slices_par(
+        &mut self, state: &mut State, header: &Header, out: &mut [u8], channels: u8,
+    ) -> Result<()> {
+        use rayon::prelude::*;
+
+        let (bytes, initial) = (Bytes { data: self.data, table: self.table }, &*state);
+        let n_slices = header.n_slices();
+        let slice_size = header.slice_len() * channels as usize;
+        let mut states = out
+            .par_chunks_mut(slice_size)
+            .enumerate()
+            .map(|(i, out)| {
+                let (start, end) = bytes.block_range(i)?;
+                let data = bytes.data;
+                let mut state = if i == 0 { initial.clone() } else { initial.fresh() };
+                let mut slice = Bytes::new(&data[start..end]);
+                let src_channels = header.channels.as_u8();
+                slice.decode_pixels(
+                    &mut state,
+                    &mut Cursor::new_block(header),
+                    out,
+                    channels,
+                    src_channels,
+                )?;
+                Ok((i == n_slices - 1).then(|| Box::new(state)))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if let Some(Some(last)) = states.pop() {
+            *state = *last;
+        }
+        self.data = &self.data[bytes.block_range(n_slices - 1)?.1..];
+        Ok(())
+    }
+}
+
+impl<'a> Reader for Bytes<'a> {
+    #[inline]
+    fn decode_header(
+        &mut self, state: &mut State, metadata: &mut Metadata, _max_alloc: usize,
+    ) -> Result<Header> {
+        let header = Header::decode(self.data)?;
+        let (fixed_len, encoded_len) = (header.fixed_len(), header.encoded_len());
+        if unlikely(self.data.len() < encoded_len) {
+            return Err(Error::UnexpectedBufferEnd);
+        }
+        let palette_start = fixed_len + header.metadata_len as usize;
+        *metadata = Metadata::decode(&self.data[fixed_len..palette_start])?;
+        let table_start = palette_start + header.palette_size();
+        load_palette(state, &header, &self.data[palette_start..table_start]);
+        self.table = &self.data[table_start..encoded_len];
+        self.data = &self.data[encoded_len..];
+        Ok(header)
+    }
+
+    #[inline]
+    fn decode_pixels(
+        &mut self, state: &mut State, cursor: &mut Cursor, out: &mut [u8], channels: u8,
+        src_channels: u8,
+    ) -> Result<()> {
+        let (n_read, n_pixels) =
+            cursor.decode_chunk(state, self.data, out, channels, src_channels)?;
+        self.data = &self.data[n_read..];
+        if unlikely(n_pixels * channels as usize != out.len()) {
+            return Err(Error::UnexpectedBufferEnd);
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn decode_padding<const DATA_ONLY: bool>(&mut self) -> Result<()> {
+        if !DATA_ONLY && unlikely(self.data.len() < QOI_PADDING_SIZE) {
+            Err(Error::UnexpectedBufferEnd)
+        } else if !DATA_ONLY && unlikely(self.data[..QOI_PADDING_SIZE] != QOI_PADDING) {
+            Err(Error::InvalidPadding)
+        } else {
+            Ok(())
+        }
+    }
+
+    #[inline]
+    fn max_pixels(&self, header: &Header) -> usize {
+        // each entropy-coded op byte takes at least a bit
+        let n = self.data.len();
+        let n_ops = if header.entropy_coded { n.saturating_mul(8) } else { n };
+        n_ops.saturating_mul(QOI_PIXELS_PER_BYTE_MAX)
+    }
+
+    #[inline]
+    fn decode_checksum(&mut self) -> Result<u32> {
+        // the padding is left in place, the checksum follows it
+        let end = QOI_PADDING_SIZE + QOI_CHECKSUM_SIZE;
+        if unlikely(self.data.len() < end) {
+            return Err(Error::UnexpectedBufferEnd);
+        }
+        let b = &self.data[QOI_PADDING_SIZE..end];
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    #[inline]
+    fn decode_entropy(&mut self, max_len: usize, max_alloc: usize) -> Result<Vec<u8>> {
+        // each op byte takes at least a bit of the rest of the input
+        let max_len = max_len.min(self.data.len().saturating_mul(8));
+        let mut data = self.data.iter();
+        let next_byte = || data.next().copied().ok_or(Error::UnexpectedBufferEnd);
+        let ops = crate::entropy::decode(next_byte, max_len, max_alloc)?;
+        self.data = data.as_slice();
+        Ok(ops)
+    }
+
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    #[inline]
+    fn decode_block_row(
+        &mut self, state: &mut State, cursor: &mut Cursor, header: &Header, above: &mut [Pixel<4>],
+    ) -> Result<()> {
+        let (n_read, done) = cursor.decode_block_chunk(state, header, self.data, above)?;
+        self.data = &self.data[n_read..];
+        if unlikely(!done) {
+            return Err(Error::UnexpectedBufferEnd);
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "rayon")]
+    #[inline]
+    fn decode_image<const DATA_ONLY: bool>(
+        &mut self, state: &mut State, header: &Header, out: &mut [u8], channels: u8,
+        max_alloc: usize,
+    ) -> Result<()> {
+        if header.entropy_coded {
+            let table = self.table;
+            decode_entropy_image(self, table, state, header, out, channels, max_alloc)?;
+        } else if header.is_block_coded() {
+            decode_whole_blocks_image(self, state, header, out, channels, max_alloc)?;
+        } else if header.is_tiled() {
+            decode_tiles(self, state, header, out, channels)?;
+        } else if self.table.is_empty() || header.n_slices() == 1 {
+            let src_channels = header.channels.as_u8();
+            self.decode_pixels(state, &mut Cursor::new(header), out, channels, src_channels)?;
+        } else {
+            self.decode_slices_par(state, header, out, channels)?;
+        }
+        self.decode_end::<DATA_ONLY>(header, pixels_crc(header, out, channels))
+    }
+}
+
+/// Size of the internal buffer streams are read into, see [`Decoder::from_stream`].
+#[cfg(feature = "std")]
+const STREAM_BUF_SIZE: usize = 8 * 1024;
+
+/// The `fill_buf` and `consume` methods of a stream implementing [`BufRead`](std::io::BufRead).
+#[cfg(feature = "std")]
+type BufReadFns<R> = (fn(&mut R) -> io::Result<&[u8]>, fn(&mut R, usize));
+
+/// A stream being decoded, read in chunks either through its own buffer if it
+/// implements [`BufRead`](std::io::BufRead), or into an internal one; the bytes left
+/// over after each call are carried over to the next one.
+#[doc(hidden)]
+#[cfg(feature = "std")]
+#[derive(Clone)]
+pub struct Stream<R> {
+    inner: R,
+    buf: Vec<u8>,
+    pos: usize,
+    len: usize,
+    buf_read: Option<BufReadFns<R>>,
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> Stream<R> {
+    #[inline]
+    const fn new(inner: R) -> Self {
+        Self { inner, buf: Vec::new(), pos: 0, len: 0, buf_read: None }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: BufRead> Stream<R> {
+    #[inline]
+    fn new_buf_read(inner: R) -> Self {
+        Self { buf_read: Some((R::fill_buf, R::consume)), ..Self::new(inner) }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> Read for Stream<R> {
+    #[inline]
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        let buf = self.fill_buf()?;
+        let n = buf.len().min(out.len());
+        out[..n].copy_from_slice(&buf[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> BufRead for Stream<R> {
+    #[inline]
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if let Some((fill_buf, _)) = self.buf_read {
+            return fill_buf(&mut self.inner);
+        }
+        if self.pos == self.len {
+            if self.buf.is_empty() {
+                self.buf = vec![0; STREAM_BUF_SIZE];
+            }
+            self.len = self.inner.read(&mut self.buf)?;
+            self.pos = 0;
+        }
+        Ok(&self.buf[self.pos..self.len])
+    }
+
+    #[inline]
+    fn consume(&mut self, n: usize) {
+        match self.buf_read {
+            Some((_, consume)) => consume(&mut self.inner, n),
+            None => self.pos = (self.pos + n).min(self.len),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> Reader for Stream<R> {
+    #[inline]
+    fn decode_header(
+        &mut self, state: &mut State, metadata: &mut Metadata, max_alloc: usize,
+    ) -> Result<Header> {
+        let mut b = [0; QOI_HEADER_MAX_SIZE];
+        let mut n = 0;
+        while n < Header::decode_len(&b[..n]) {
+            let len = Header::decode_len(&b[..n]);
+            self.read_exact(&mut b[n..len])?;
+            n = len;
+        }
+        let header = Header::decode(&b[..n])?;
+        // the chunks are read as they come, so a bogus length doesn't allocate up front
+        let (mut chunks, metadata_len) = (Vec::new(), u64::from(header.metadata_len));
+        if unlikely(metadata_len > max_alloc as u64) {
+            return Err(Error::LimitsExceeded);
+        }
+        if unlikely(
+            self.by_ref().take(metadata_len).read_to_end(&mut chunks)? as u64 != metadata_len,
+        ) {
+            return Err(Error::UnexpectedBufferEnd);
+        }
+        *metadata = Metadata::decode(&chunks)?;
+        let mut palette = [0; QOI_PALETTE_MAX_LEN * 4];
+        self.read_exact(&mut palette[..header.palette_size()])?;
+        load_palette(state, &header, &palette[..header.palette_size()]);
+        // the slice table is only needed for decoding in parallel
+        let table_len = header.table_len() as u64;
+        if unlikely(io::copy(&mut self.by_ref().take(table_len), &mut io::sink())? != table_len) {
+            return Err(Error::UnexpectedBufferEnd);
+        }
+        Ok(header)
+    }
+
+    #[inline]
+    fn decode_pixels(
+        &mut self, state: &mut State, cursor: &mut Cursor, mut out: &mut [u8], channels: u8,
+        src_channels: u8,
+    ) -> Result<()> {
+        while !out.is_empty() {
+            let buf = self.fill_buf()?;
+            if unlikely(buf.is_empty()) {
+                return Err(Error::UnexpectedBufferEnd);
+            }
+            let (n_consumed, n_pixels) =
+                cursor.decode_chunk(state, buf, out, channels, src_channels)?;
+            self.consume(n_consumed);
+            out = &mut out[n_pixels * channels as usize..];
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn decode_padding<const DATA_ONLY: bool>(&mut self) -> Result<()> {
+        if !DATA_ONLY && {
+            let mut p = [0; QOI_PADDING_SIZE];
+            self.read_exact(&mut p)?;
+            p != QOI_PADDING
+        } {
+            Err(Error::InvalidPadding)
+        } else {
+            Ok(())
+        }
+    }
+
+    #[inline]
+    fn decode_checksum(&mut self) -> Result<u32> {
+        let mut b = [0; QOI_CHECKSUM_SIZE];
+        self.read_exact(&mut b)?;
+        Ok(u32::from_be_bytes(b))
+    }
+
+    #[inline]
+    fn decode_entropy(&mut self, max_len: usize, max_alloc: usize) -> Result<Vec<u8>> {
+        let mut bytes = self.by_ref().bytes();
+        let next_byte = || match bytes.next() {
+            Some(b) => Ok(b?),
+            None => Err(Error::UnexpectedBufferEnd),
+        };
+        crate::entropy::decode(next_byte, max_len, max_alloc)
+    }
+
+    #[inline]
+    fn decode_block_row(
+        &mut self, state: &mut State, cursor: &mut Cursor, header: &Header, above: &mut [Pixel<4>],
+    ) -> Result<()> {
+        loop {
+            let buf = self.fill_buf()?;
+            let at_end = buf.is_empty();
+            let (n_consumed, done) = cursor.decode_block_chunk(state, header, buf, above)?;
+            self.consume(n_consumed);
+            if done {
+                return Ok(());
+            } else if unlikely(at_end) {
+                return Err(Error::UnexpectedBufferEnd);
+            }
+        }
+    }
+}
+
+/// Number of pixels [`Pixels`] decodes at once.
+const PIXELS_BATCH_SIZE: usize = 64;
+
+/// Iterator over the decoded pixels of an image, created by [`Decoder::pixels`].
+pub struct Pixels<'d, R: Source, const DATA_ONLY: bool, const N: usize> {
+    decoder: &'d mut Decoder<R>,
+    cursor: Cursor,
+    buf: [[u8; N]; PIXELS_BATCH_SIZE],
+    pos: usize,
+    len: usize,
+    n_left: usize,
+    done: bool,
+    crc: Crc32,
+}
+
+impl<R: Source, const DATA_ONLY: bool, const N: usize> Pixels<'_, R, DATA_ONLY, N>
+where
+    Pixel<N>: SupportedChannels,
+    [u8; N]: Pod,
+{
+    #[inline]
+    #[allow(clippy::cast_possible_truncation)]
+    fn fill(&mut self) -> Result<()> {
+        let decoder = &mut *self.decoder;
+        let src_channels = decoder.header.channels.as_u8();
+        if self.n_left == 0 {
+            self.done = true;
+            let crc = (N as u8 >= src_channels).then(|| self.crc);
+            return decoder.reader.decode_end::<DATA_ONLY>(&decoder.header, crc);
+        }
+        let n = self.n_left.min(PIXELS_BATCH_SIZE);
+        let out = cast_slice_mut::<_, u8>(&mut self.buf[..n]);
+        decoder.reader.decode_pixels(
+            &mut decoder.state,
+            &mut self.cursor,
+            out,
+            N as _,
+            src_channels,
+        )?;
+        if decoder.header.checksum {
+            self.crc.update_pixels(out, N as u8, src_channels);
+        }
+        self.n_left -= n;
+        self.pos = 0;
+        self.len = n;
+        Ok(())
+    }
+}
+
+impl<R: Source, const DATA_ONLY: bool, const N: usize> Iterator for Pixels<'_, R, DATA_ONLY, N>
+where
+    Pixel<N>: SupportedChannels,
+    [u8; N]: Pod,
+{
+    type Item = Result<[u8; N]>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos == self.len {
+            if self.done {
+                return None;
+            }
+            if let Err(err) = self.fill() {
+                self.done = true;
+                return Some(Err(err));
+            }
+            if self.done {
+                return None;
+            }
+        }
+        self.pos += 1;
+        Some(Ok(self.buf[self.pos - 1]))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        // an error may cut the iteration short, so only the buffered pixels are certain
+        let n = self.len - self.pos;
+        (n, Some(n + self.n_left + 1))
+    }
+}
+
+/// Decode QOI images from slices or from streams.
+///
+/// Decoders start with [`Limits::default`], which caps their allocations at 512 MiB,
+/// whereas the free functions like [`decode_to_vec`] don't limit anything. Larger images
+/// need [`Decoder::with_limits`], e.g. with [`Limits::none`].
+#[derive(Clone)]
+pub struct Decoder<R: Source> {
+    reader: R::Reader,
+    header: Header,
+    channels: Channels,
+    limits: Limits,
+    state: State,
+    metadata: Metadata,
+}
+
+impl<'a> Decoder<Bytes<'a>> {
+    /// Creates a new decoder from a slice of bytes.
+    ///
+    /// The header will be decoded immediately upon construction.
+    ///
+    /// Note: this provides the most efficient decoding, but requires the source data to
+    /// be loaded in memory in order to decode it. In order to decode from a generic
+    /// stream, use [`Decoder::from_stream`] instead.
+    /// #[inline]
+    pub fn new(data: &'a (impl AsRef<[u8]> + ?Sized)) -> Result<Self> {
+        let mut reader = Bytes::new(data.as_ref());
+        let (mut state, mut metadata) = (State::default(), Metadata::new());
+        let max_alloc = Limits::default().max_alloc;
+        let header = reader.decode_header(&mut state, &mut metadata, max_alloc)?;
+        Ok(Self { metadata, ..Self::new_with(header, state, reader) })
+    }
+    #[inline]
+    pub fn new_with(header: Header, state: State, reader: Bytes<'a>) -> Self {
+        Self::new_impl(header, state, reader)
+    }
+
+    /// Returns the undecoded tail of the input slice of bytes.
+    #[inline]
+    pub const fn data(&self) -> &[u8] {
+        self.reader.as_slice()
+    }
+
+    /// Decodes a rectangular region of the image into a newly allocated vector of bytes.
+    ///
+    /// Only the slices or tiles intersecting the region are decoded, so that small windows
+    /// into huge images encoded via [`Encoder::with_tile_size`](crate::Encoder::with_tile_size)
+    /// are cheap to view; for other images, everything up to the region is decoded. The
+    /// decoder is left untouched, so this can be called repeatedly, but not after the
+    /// image has been decoded.
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    #[inline]
+    pub fn decode_region(&self, x: u32, y: u32, width: u32, height: u32) -> Result<Vec<u8>> {
+        let header = &self.header;
+        let fits = |pos: u32, len: u32, max: u32| len != 0 && pos.saturating_add(len) <= max;
+        if unlikely(!fits(x, width, header.width) || !fits(y, height, header.height)) {
+            return Err(Error::InvalidRegion { x, y, width, height });
+        }
+        self.limits.check_header(header)?;
+        self.state.check_dictionary(header)?;
+        let n_channels = self.channels.as_u8() as usize;
+        let size = width as usize * height as usize * n_channels;
+        let row_size = header.block_size().0 * n_channels;
+        if unlikely(size.saturating_add(row_size) > self.limits.max_alloc) {
+            return Err(Error::LimitsExceeded);
+        }
+        self.check_input(width as usize * height as usize)?;
+        let mut out = vec![0; size];
+        let region = (x as usize, y as usize, width as usize, height as usize);
+        let channels = self.channels.as_u8();
+        let max_alloc = self.limits.max_alloc;
+        self.reader.decode_region(&self.state, header, region, &mut out, channels, max_alloc)?;
+        Ok(out)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> Decoder<R> {
+    /// Creates a new decoder from a generic reader that implements [`Read`](std::io::Read).
+    ///
+    /// The header will be decoded immediately upon construction. The data is read in
+    /// chunks into an internal buffer, so bytes following the image may be read from the
+    /// reader as well; to stop right at the end of the image, use [`Decoder::from_buf_read`].
+    ///
+    /// Note: while it's possible to pass a `&[u8]` slice here since it implements `Read`, it
+    /// would be more efficient to use a specialized constructor instead: [`Decoder::new`].
+    #[inline]
+    pub fn from_stream(reader: R) -> Result<Self> {
+        Self::from_stream_with(State::default(), reader)
+    }
+    #[inline]
+    pub fn from_stream_with(state: State, reader: R) -> Result<Self> {
+        Self::from_reader_with(state, Stream::new(reader), Limits::default())
+    }
+
+    /// Creates a new decoder from a generic reader, with the given resource limits.
+    ///
+    /// Unlike [`Decoder::with_limits`], the limits then also apply to the metadata chunks,
+    /// which are read along with the header.
+    #[inline]
+    pub fn from_stream_with_limits(reader: R, limits: Limits) -> Result<Self> {
+        Self::from_reader_with(State::default(), Stream::new(reader), limits)
+    }
+
+    #[inline]
+    fn from_reader_with(mut state: State, mut reader: Stream<R>, limits: Limits) -> Result<Self> {
+        let mut metadata = Metadata::new();
+        let header = reader.decode_header(&mut state, &mut metadata, limits.max_alloc)?;
+        Ok(Self { limits, metadata, ..Self::new_impl(header, state, reader) })
+    }
+
+    /// Returns an immutable reference to the underlying reader.
+    #[inline]
+    pub const fn reader(&self) -> &R {
+        &self.reader.inner
+    }
+
+    /// Consumes the decoder and returns the underlying reader back.
+    ///
+    /// Note: a decoder created by [`Decoder::from_stream`] may have read bytes past the
+    /// end of the image into its buffer, which are dropped here; use
+    /// [`Decoder::into_parts`] to keep them, e.g. to decode the next image of a stream.
+    #[inline]
+    #[allow(clippy::missing_const_for_fn)]
+    pub fn into_reader(self) -> R {
+        self.reader.inner
+    }
+
+    /// Consumes the decoder and returns the underlying reader back, along with the bytes
+    /// read from it but not decoded yet, which precede whatever the reader returns next.
+    ///
+    /// The bytes are always empty for a decoder created by [`Decoder::from_buf_read`],
+    /// which leaves them in the reader.
+    #[inline]
+    pub fn into_parts(self) -> (R, Vec<u8>) {
+        let Stream { inner, mut buf, pos, len, .. } = self.reader;
+        buf.truncate(len);
+        buf.drain(..pos);
+        (inner, buf)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: BufRead> Decoder<R> {
+    /// Creates a new decoder from a buffered reader that implements
+    /// [`BufRead`](std::io::BufRead).
+    ///
+    /// The header will be decoded immediately upon construction. The data is decoded
+    /// straight from the reader's own buffer, and no more bytes are consumed from it
+    /// than the encoded image occupies.
+    #[inline]
+    pub fn from_buf_read(reader: R) -> Result<Self> {
+        Self::from_buf_read_with(State::default(), reader)
+    }
+    #[inline]
+    pub fn from_buf_read_with(state: State, reader: R) -> Result<Self> {
+        Self::from_reader_with(state, Stream::new_buf_read(reader), Limits::default())
+    }
+
+    /// Creates a new decoder from a buffered reader, with the given resource limits.
+    ///
+    /// Unlike [`Decoder::with_limits`], the limits then also apply to the metadata chunks,
+    /// which are read along with the header.
+    #[inline]
+    pub fn from_buf_read_with_limits(reader: R, limits: Limits) -> Result<Self> {
+        Self::from_reader_with(State::default(), Stream::new_buf_read(reader), limits)
+    }
+}
+
+impl<R: Source> Decoder<R> {
+    #[inline]
+    fn new_impl(header: Header, state: State, reader: R::Reader) -> Self {
+        let (channels, limits, metadata) = (header.channels, Limits::default(), Metadata::new());
+        Self { reader, header, channels, limits, state, metadata }
+    }
+
+    /// Checks that the rest of the input can encode `n_pixels` pixels before allocating
+    /// an output for them.
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    #[inline]
+    fn check_input(&self, n_pixels: usize) -> Result<()> {
+        if unlikely(n_pixels > self.reader.max_pixels(&self.header)) {
+            return Err(Error::UnexpectedBufferEnd);
+        }
+        Ok(())
+    }
+
+    /// Returns a new decoder with modified number of channels.
+    ///
+    /// By default, the number of channels in the decoded image will be equal
+    /// to whatever is specified in the header. However, it is also possible
+    /// to decode RGB into RGBA (in which case the alpha channel will be set
+    /// to 255), and vice versa (in which case the alpha channel will be ignored).
+    #[inline]
+    pub const fn with_channels(mut self, channels: Channels) -> Self {
+        self.channels = channels;
+        self
+    }
+
+    /// Returns a new decoder with modified resource limits.
+    ///
+    /// The limits are checked against the header before decoding; if they are
+    /// exceeded, [`Error::LimitsExceeded`] is returned and nothing is allocated. When
+    /// decoding from a stream, the metadata chunks are read along with the header, within
+    /// the default limits; use [`Decoder::from_stream_with_limits`] to change those too.
+    #[inline]
+    pub const fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Returns a new decoder starting from a dictionary trained via [`State::train`].
+    ///
+    /// The dictionary has to be the one the image was encoded with, as recorded in
+    /// [`Header::dictionary_id`]; otherwise, [`Error::DictionaryMismatch`] is returned
+    /// before decoding.
+    #[inline]
+    pub fn with_dictionary(mut self, dictionary: &State) -> Self {
+        self.state = self.state.with_dictionary(dictionary);
+        self
+    }
+
+    /// Returns the resource limits used by the decoder.
+    #[inline]
+    pub const fn limits(&self) -> &Limits {
+        &self.limits
+    }
 
     /// Returns the number of channels in the decoded image.
     ///
@@ -456,6 +1604,14 @@ impl<R: Reader> Decoder<R> {
         &self.header
     }
 
+    /// Returns the metadata chunks stored after the header, see [`Metadata`].
+    ///
+    /// Note: without the `alloc` feature, the chunks are skipped and this is always empty.
+    #[inline]
+    pub const fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
     /// The number of bytes the decoded image will take.
     ///
     /// Can be used to pre-allocate the buffer to decode the image into.
@@ -467,20 +1623,26 @@ impl<R: Reader> Decoder<R> {
     /// Decodes the image to a pre-allocated buffer and returns the number of bytes written.
     ///
     /// The minimum size of the buffer can be found via [`Decoder::required_buf_len`].
+    /// Images predicted from the previous row or coded against a palette also need that
+    /// row of a slice or tile, 4 bytes per pixel, which is allocated and counts against
+    /// [`Limits::max_alloc`].
     #[inline]
     pub fn decode_to_buf<const DATA_ONLY: bool>(
         &mut self, mut buf: impl AsMut<[u8]>,
     ) -> Result<usize> {
         let buf = buf.as_mut();
+        self.limits.check_header(&self.header)?;
+        self.state.check_dictionary(&self.header)?;
         let size = self.required_buf_len();
         if unlikely(buf.len() < size) {
             return Err(Error::OutputBufferTooSmall { size: buf.len(), required: size });
         }
         self.reader.decode_image::<DATA_ONLY>(
             &mut self.state,
-            buf,
+            &self.header,
+            &mut buf[..size],
             self.channels.as_u8(),
-            self.header.channels.as_u8(),
+            self.limits.max_alloc,
         )?;
         Ok(size)
     }
@@ -489,12 +1651,440 @@ impl<R: Reader> Decoder<R> {
     #[cfg(any(feature = "std", feature = "alloc"))]
     #[inline]
     pub fn decode_to_vec<const DATA_ONLY: bool>(&mut self) -> Result<Vec<u8>> {
+        self.limits.check_alloc(&self.header, self.channels)?;
+        self.check_input(self.header.n_pixels())?;
         let mut out = vec![0; self.header.n_pixels() * self.channels.as_u8() as usize];
         let _ = self.decode_to_buf::<DATA_ONLY>(&mut out)?;
         Ok(out)
     }
+
+    /// Decodes the image into a newly allocated vector of typed pixels and returns it.
+    ///
+    /// The number of channels is given by the pixel type, `[u8; 3]` or `[u8; 4]`,
+    /// regardless of [`Decoder::with_channels`]; the conversion rules are the same though.
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    #[inline]
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn decode_to_pixels<const DATA_ONLY: bool, const N: usize>(
+        &mut self,
+    ) -> Result<Vec<[u8; N]>>
+    where
+        Pixel<N>: SupportedChannels,
+        [u8; N]: Pod,
+    {
+        self.limits.check_alloc(&self.header, Channels::try_from(N as u8)?)?;
+        self.check_input(self.header.n_pixels())?;
+        let mut out = vec![[0; N]; self.header.n_pixels()];
+        self.reader.decode_image::<DATA_ONLY>(
+            &mut self.state,
+            &self.header,
+            cast_slice_mut(&mut out),
+            N as u8,
+            self.limits.max_alloc,
+        )?;
+        Ok(out)
+    }
+
+    /// Decodes the image into a newly allocated vector of RGBA pixels packed into
+    /// integers with the given byte order and returns it.
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    #[inline]
+    pub fn decode_to_u32<const DATA_ONLY: bool>(&mut self, order: ByteOrder) -> Result<Vec<u32>> {
+        self.limits.check_alloc(&self.header, Channels::Rgba)?;
+        self.check_input(self.header.n_pixels())?;
+        let mut out = vec![0_u32; self.header.n_pixels()];
+        self.reader.decode_image::<DATA_ONLY>(
+            &mut self.state,
+            &self.header,
+            cast_slice_mut(&mut out),
+            4,
+            self.limits.max_alloc,
+        )?;
+        if order != ByteOrder::NATIVE {
+            for px in &mut out {
+                *px = px.swap_bytes();
+            }
+        }
+        Ok(out)
+    }
+
+    /// Returns an iterator over the decoded pixels.
+    ///
+    /// The pixels are decoded lazily in small batches as the iterator advances. The number
+    /// of channels is given by the pixel type, `[u8; 3]` or `[u8; 4]`, regardless of
+    /// [`Decoder::with_channels`]; the conversion rules are the same though. Once an error
+    /// is encountered, it is yielded and the iteration stops.
+    #[inline]
+    pub fn pixels<const DATA_ONLY: bool, const N: usize>(
+        &mut self,
+    ) -> Result<Pixels<'_, R, DATA_ONLY, N>>
+    where
+        Pixel<N>: SupportedChannels,
+        [u8; N]: Pod,
+    {
+        self.limits.check_header(&self.header)?;
+        self.state.check_dictionary(&self.header)?;
+        if unlikely(self.header.is_tiled()) {
+            return Err(Error::UnsupportedTiling);
+        }
+        if unlikely(self.header.entropy_coded) {
+            return Err(Error::UnsupportedEntropyCoding);
+        }
+        if unlikely(self.header.is_block_coded()) {
+            return Err(self.header.block_coded_error());
+        }
+        let n_left = self.header.n_pixels();
+        Ok(Pixels {
+            cursor: Cursor::new(&self.header),
+            decoder: self,
+            buf: [[0; N]; PIXELS_BATCH_SIZE],
+            pos: 0,
+            len: 0,
+            n_left,
+            done: false,
+            crc: Crc32::new(),
+        })
+    }
+
+    /// The number of bytes a single decoded row takes.
+    ///
+    /// Can be used to pre-allocate the scratch buffer for [`Decoder::decode_rows_with_buf`].
+    #[inline]
+    pub const fn required_row_len(&self) -> usize {
+        (self.header.width as usize).saturating_mul(self.channels.as_u8() as usize)
+    }
+
+    /// Decodes the image row by row, handing each completed row to a callback.
+    ///
+    /// The callback receives the row index and the pixel data of the row. Only a single
+    /// row is held in memory at any time, so the full decoded image never has to fit
+    /// into memory.
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    #[inline]
+    pub fn decode_rows<const DATA_ONLY: bool>(
+        &mut self, on_row: impl FnMut(u32, &[u8]),
+    ) -> Result<()> {
+        let size = self.required_row_len();
+        if unlikely(size > self.limits.max_alloc) {
+            return Err(Error::LimitsExceeded);
+        }
+        self.check_input(self.header.width as usize)?;
+        let mut row = vec![0; size];
+        self.decode_rows_with_buf::<DATA_ONLY>(&mut row, on_row)
+    }
+
+    /// Decodes the image row by row using a pre-allocated scratch buffer for a single row.
+    ///
+    /// This is the allocation-free version of [`Decoder::decode_rows`]; the minimum size
+    /// of the buffer can be found via [`Decoder::required_row_len`]. Images predicted from
+    /// the previous row or coded against a palette are the exception: they allocate that
+    /// row as in [`Decoder::decode_to_buf`].
+    #[inline]
+    pub fn decode_rows_with_buf<const DATA_ONLY: bool>(
+        &mut self, mut buf: impl AsMut<[u8]>, on_row: impl FnMut(u32, &[u8]),
+    ) -> Result<()> {
+        let buf = buf.as_mut();
+        self.limits.check_header(&self.header)?;
+        self.state.check_dictionary(&self.header)?;
+        let size = self.required_row_len();
+        if unlikely(buf.len() < size) {
+            return Err(Error::OutputBufferTooSmall { size: buf.len(), required: size });
+        }
+        self.reader.decode_rows::<DATA_ONLY, _>(
+            &mut self.state,
+            &self.header,
+            &mut buf[..size],
+            self.channels.as_u8(),
+            self.limits.max_alloc,
+            on_row,
+        )
+    }
     #[inline]
     pub fn extract_state(self) -> State {
         self.state
     }
 }
+
+/// Part of the image [`DecoderWriter`] expects next.
+#[cfg(feature = "std")]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum WriterStage {
+    Header,
+    Table,
+    Data,
+    Padding,
+    Done,
+}
+
+/// Destination of the pixels decoded by [`DecoderWriter`].
+#[cfg(feature = "std")]
+enum Output<'a> {
+    Frame(&'a mut [u8]),
+    Rows(Vec<u8>),
+}
+
+/// Push-based decoder that implements [`Write`](std::io::Write).
+///
+/// Encoded bytes can be written into it in chunks of any size. The header is decoded
+/// as soon as it's complete, after which pixels are decoded either into a caller-supplied
+/// buffer ([`DecoderWriter::new`]) or one row at a time, handing each completed row to
+/// a callback ([`DecoderWriter::new_rows`]). Once all of the data has been written,
+/// [`DecoderWriter::finish`] verifies the stream end marker and returns the header.
+/// Metadata chunks are skipped.
+///
+/// Note: bytes past the end marker are not accepted, so `write` returns 0 for them.
+#[cfg(feature = "std")]
+pub struct DecoderWriter<'a, F = fn(u32, &[u8])> {
+    output: Output<'a>,
+    on_row: F,
+    stage: WriterStage,
+    header: Header,
+    channels: Option<Channels>,
+    limits: Limits,
+    state: State,
+    cursor: Cursor,
+    head: [u8; QOI_HEADER_MAX_SIZE],
+    n_head: usize,
+    n_skip: usize,
+    padding: [u8; QOI_PADDING_SIZE + QOI_CHECKSUM_SIZE],
+    n_padding: usize,
+    n_decoded: usize,
+    crc: Crc32,
+}
+
+#[cfg(feature = "std")]
+impl<'a> DecoderWriter<'a> {
+    /// Creates a new decoder writing the decoded image into a pre-allocated buffer.
+    ///
+    /// The buffer must be large enough to fit the image, otherwise an error is returned
+    /// once the header has been received.
+    #[inline]
+    pub fn new(buf: &'a mut [u8]) -> Self {
+        Self::new_impl(Output::Frame(buf), |_, _| {})
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a, F: FnMut(u32, &[u8])> DecoderWriter<'a, F> {
+    /// Creates a new decoder that hands out the decoded image row by row.
+    ///
+    /// The callback receives the row index and the pixel data of each completed row;
+    /// only a single row is held in memory at any time.
+    #[inline]
+    pub fn new_rows(on_row: F) -> Self {
+        Self::new_impl(Output::Rows(Vec::new()), on_row)
+    }
+
+    #[inline]
+    fn new_impl(output: Output<'a>, on_row: F) -> Self {
+        Self {
+            output,
+            on_row,
+            stage: WriterStage::Header,
+            header: Header::default(),
+            channels: None,
+            limits: Limits::default(),
+            state: State::default(),
+            cursor: Cursor::default(),
+            head: [0; QOI_HEADER_MAX_SIZE],
+            n_head: 0,
+            n_skip: 0,
+            padding: [0; QOI_PADDING_SIZE + QOI_CHECKSUM_SIZE],
+            n_padding: 0,
+            n_decoded: 0,
+            crc: Crc32::new(),
+        }
+    }
+
+    /// Returns a new decoder with modified number of channels.
+    ///
+    /// See [`Decoder::with_channels`] for details.
+    #[inline]
+    pub const fn with_channels(mut self, channels: Channels) -> Self {
+        self.channels = Some(channels);
+        self
+    }
+
+    /// Returns a new decoder with modified resource limits.
+    #[inline]
+    pub const fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Returns a new decoder starting from a given state (e.g. of the previous frame).
+    #[inline]
+    pub fn with_state(mut self, state: State) -> Self {
+        self.state = state;
+        self
+    }
+
+    /// Returns the image header if it has been received already.
+    #[inline]
+    pub fn header(&self) -> Option<&Header> {
+        (self.stage != WriterStage::Header).then(|| &self.header)
+    }
+
+    /// Returns the number of channels in the decoded image.
+    ///
+    /// Note: unless set explicitly, this is only known once the header has been received.
+    #[inline]
+    pub fn channels(&self) -> Channels {
+        self.channels.unwrap_or(self.header.channels)
+    }
+
+    /// Verifies that the whole image and the stream end marker have been received,
+    /// as well as the checksum of the pixels if there is one, and returns the image header.
+    #[inline]
+    pub fn finish(&mut self) -> Result<Header> {
+        let (padding, checksum) = self.padding.split_at(QOI_PADDING_SIZE);
+        let expected = u32::from_be_bytes([checksum[0], checksum[1], checksum[2], checksum[3]]);
+        let found = self.crc.finish();
+        if unlikely(self.stage != WriterStage::Done) {
+            Err(Error::UnexpectedBufferEnd)
+        } else if unlikely(padding != QOI_PADDING) {
+            Err(Error::InvalidPadding)
+        } else if unlikely(self.verifies_checksum() && found != expected) {
+            Err(Error::ChecksumMismatch { expected, found })
+        } else {
+            Ok(self.header)
+        }
+    }
+
+    /// Returns true if the checksum of the pixels can be verified: the header asks for
+    /// one and the alpha channel isn't dropped.
+    #[inline]
+    fn verifies_checksum(&self) -> bool {
+        self.header.checksum && self.channels() >= self.header.channels
+    }
+
+    #[inline]
+    pub fn into_state(self) -> State {
+        self.state
+    }
+
+    fn begin(&mut self, header: Header) -> Result<()> {
+        self.limits.check_header(&header)?;
+        self.state.check_dictionary(&header)?;
+        if unlikely(header.is_tiled()) {
+            return Err(Error::UnsupportedTiling);
+        }
+        if unlikely(header.entropy_coded) {
+            return Err(Error::UnsupportedEntropyCoding);
+        }
+        if unlikely(header.is_block_coded()) {
+            return Err(header.block_coded_error());
+        }
+        self.header = header;
+        let n_channels = self.channels().as_u8() as usize;
+        match self.output {
+            Output::Frame(ref buf) => {
+                let required = header.n_pixels() * n_channels;
+                if unlikely(buf.len() < required) {
+                    return Err(Error::OutputBufferTooSmall { size: buf.len(), required });
+                }
+            }
+            Output::Rows(ref mut row) => {
+                let size = header.width as usize * n_channels;
+                if unlikely(size > self.limits.max_alloc) {
+                    return Err(Error::LimitsExceeded);
+                }
+                *row = vec![0; size];
+            }
+        }
+        self.cursor = Cursor::new(&header);
+        self.n_skip = header.metadata_len as usize + header.table_len();
+        self.stage = if self.n_skip == 0 { WriterStage::Data } else { WriterStage::Table };
+        Ok(())
+    }
+
+    fn decode_data(&mut self, data: &[u8]) -> Result<usize> {
+        let (width, n_pixels) = (self.header.width as usize, self.header.n_pixels());
+        let channels = self.channels().as_u8();
+        let n_channels = channels as usize;
+        let out = match self.output {
+            Output::Frame(ref mut buf) => {
+                &mut buf[self.n_decoded * n_channels..n_pixels * n_channels]
+            }
+            Output::Rows(ref mut row) => &mut row[(self.n_decoded % width) * n_channels..],
+        };
+        let (n_consumed, n) = self.cursor.decode_chunk(
+            &mut self.state,
+            data,
+            out,
+            channels,
+            self.header.channels.as_u8(),
+        )?;
+        if self.header.checksum {
+            self.crc.update_pixels(&out[..n * n_channels], channels, self.header.channels.as_u8());
+        }
+        self.n_decoded += n;
+        if let Output::Rows(ref row) = self.output {
+            if n != 0 && self.n_decoded % width == 0 {
+                #[allow(clippy::cast_possible_truncation)]
+                (self.on_row)((self.n_decoded / width - 1) as u32, row);
+            }
+        }
+        if self.n_decoded == n_pixels {
+            self.stage = WriterStage::Padding;
+        }
+        Ok(n_consumed)
+    }
+
+    fn write_impl(&mut self, mut data: &[u8]) -> Result<usize> {
+        let n_total = data.len();
+        while !data.is_empty() {
+            let n = match self.stage {
+                WriterStage::Header => {
+                    let len = Header::decode_len(&self.head[..self.n_head]);
+                    let n = (len - self.n_head).min(data.len());
+                    self.head[self.n_head..self.n_head + n].copy_from_slice(&data[..n]);
+                    self.n_head += n;
+                    if self.n_head == Header::decode_len(&self.head[..self.n_head]) {
+                        self.begin(Header::decode(&self.head[..self.n_head])?)?;
+                    }
+                    n
+                }
+                WriterStage::Table => {
+                    // metadata chunks aren't kept, and the slice table is only needed
+                    // for decoding in parallel
+                    let n = self.n_skip.min(data.len());
+                    self.n_skip -= n;
+                    if self.n_skip == 0 {
+                        self.stage = WriterStage::Data;
+                    }
+                    n
+                }
+                WriterStage::Data => self.decode_data(data)?,
+                WriterStage::Padding => {
+                    // the checksum of the pixels, if any, follows the padding
+                    let checksum_size = if self.header.checksum { QOI_CHECKSUM_SIZE } else { 0 };
+                    let len = QOI_PADDING_SIZE + checksum_size;
+                    let n = (len - self.n_padding).min(data.len());
+                    self.padding[self.n_padding..self.n_padding + n].copy_from_slice(&data[..n]);
+                    self.n_padding += n;
+                    if self.n_padding == len {
+                        self.stage = WriterStage::Done;
+                    }
+                    n
+                }
+                WriterStage::Done => break,
+            };
+            data = &data[n..];
+        }
+        Ok(n_total - data.len())
+    }
+}
+
+#[cfg(feature = "std")]
+impl<F: FnMut(u32, &[u8])> Write for DecoderWriter<'_, F> {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_impl(buf).map_err(Into::into)
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
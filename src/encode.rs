@@ -3,41 +3,161 @@ use alloc::{vec, vec::Vec};
 use core::convert::TryFrom;
 use core::mem::replace;
 #[cfg(feature = "std")]
-use std::io::Write;
+use std::io::{self, Read, Write};
 
-use bytemuck::Pod;
+use bytemuck::{cast_slice, Pod};
 
 use crate::consts::{
-    QOI_HEADER_SIZE, QOI_OP_INDEX, QOI_OP_LONG_INDEX, QOI_OP_LONG_RUN, QOI_OP_LONG_RUN_MAX_0,
-    QOI_OP_LONG_RUN_MAX_1, QOI_OP_LUMA, QOI_OP_PREV, QOI_OP_RUN, QOI_PADDING, QOI_PADDING_SIZE,
+    QOI_CHECKSUM_SIZE, QOI_HEADER_SIZE, QOI_OP_INDEX, QOI_OP_LONG_INDEX, QOI_OP_LONG_RUN,
+    QOI_OP_LONG_RUN_MAX_0, QOI_OP_LONG_RUN_MAX_1, QOI_OP_LUMA, QOI_OP_PREV, QOI_OP_RUN,
+    QOI_PADDING, QOI_PADDING_SIZE,
 };
+#[cfg(any(feature = "alloc", feature = "std"))]
+use crate::consts::{
+    QOI_OP_PALETTE, QOI_OP_RUN_VERTICAL_END, QOI_OP_UP_DIFF, QOI_OP_UP_RUN, QOI_PALETTE_MAX_LEN,
+};
+use crate::crc::Crc32;
 use crate::error::{Error, Result};
 use crate::header::Header;
+#[cfg(any(feature = "alloc", feature = "std"))]
+use crate::metadata::ChunkType;
+use crate::metadata::Metadata;
 use crate::pixel::{Pixel, SupportedChannels};
-use crate::types::{Channels, ColorSpace};
+#[cfg(any(feature = "alloc", feature = "std"))]
+use crate::types::Predictor;
+use crate::types::{ByteOrder, Channels, ColorSpace};
 #[cfg(feature = "std")]
 use crate::utils::GenericWriter;
-use crate::utils::{unlikely, BytesMut, Writer};
+use crate::utils::{unlikely, BytesMut, BytesSpill, Counter, Spill, Writer};
 use crate::State;
 
-#[allow(clippy::cast_possible_truncation, unused_assignments, unused_variables)]
-fn encode_impl<W: Writer, const N: usize>(
-    state: &mut State, mut buf: W, data: &[u8],
-) -> Result<usize>
+/// Position of the encoder within the pixel data, allowing to suspend and resume encoding.
+#[derive(Copy, Clone, Debug)]
+struct Cursor {
+    pos: usize,
+    px_prev: Pixel<4>,
+    run: u16,
+    /// Maximum allowed difference from the source per channel, 0 for lossless encoding.
+    max_error: u8,
+    /// Largest difference from the source per channel encountered so far.
+    error: u8,
+    /// Whether pixels are converted to YCoCg-R before encoding.
+    color_transform: bool,
+    /// Channels covered by the checksum if the image has one, 0 otherwise.
+    crc_channels: u8,
+    /// CRC of the differences between the stored and the source pixels of the current row
+    /// (of a tile, or of a whole full-width block), see [`Cursor::finish_row`].
+    crc_row: Crc32,
+    /// CRC of those differences over the finished rows: XOR-ed with the checksum of the
+    /// source pixels, it gives the checksum of the stored ones.
+    crc: u32,
+}
+
+impl Default for Cursor {
+    #[inline]
+    fn default() -> Self {
+        Self::new(0, false, 0)
+    }
+}
+
+impl Cursor {
+    /// Returns true if all pixels have been encoded.
+    #[inline]
+    const fn is_done(&self, n_pixels: usize) -> bool {
+        self.pos >= n_pixels
+    }
+
+    /// Creates a cursor at the start of the image; the error bound would apply to the
+    /// transformed channels, so near-lossless encoding is disabled with the color transform.
+    #[inline]
+    const fn new(max_error: u8, color_transform: bool, crc_channels: u8) -> Self {
+        let px_prev = Pixel::<4>::new().with_a(0xff);
+        let max_error = if color_transform { 0 } else { max_error };
+        Self {
+            pos: 0,
+            px_prev,
+            run: 0,
+            max_error,
+            error: 0,
+            color_transform,
+            crc_channels,
+            crc_row: Crc32::zero(),
+            crc: 0,
+        }
+    }
+
+    /// Moves the cursor to the start of an independently encoded block at `pos`.
+    #[inline]
+    fn restart(&mut self, pos: usize) {
+        let start = Self::new(self.max_error, self.color_transform, self.crc_channels);
+        *self = Self { pos, error: self.error, crc: self.crc, ..start };
+    }
+
+    /// Adds the CRC of the current row, which ends before pixel `end` of an image of
+    /// `n_pixels` pixels, to the CRC of the differences to the source. Only near-lossless
+    /// encoding feeds the row, any other encoding stores the source pixels as they are.
+    #[inline]
+    fn finish_row(&mut self, end: usize, n_pixels: usize) {
+        if self.crc_channels != 0 && self.max_error != 0 {
+            self.crc_row.update_zeros((n_pixels - end) * self.crc_channels as usize);
+            self.crc ^= self.crc_row.finish_zero();
+            self.crc_row = Crc32::zero();
+        }
+    }
+}
+
+#[inline]
+#[allow(clippy::cast_possible_truncation)]
+fn encode_run<W: Writer>(buf: W, run: u16) -> Result<W> {
+    if run == 1 {
+        buf.write_one(QOI_OP_PREV)
+    } else if run <= 63 {
+        buf.write_one(QOI_OP_RUN | (run as u8 - 2))
+    } else {
+        let run = run - 64;
+        buf.write_one(QOI_OP_LUMA | (run & 0x3f) as u8)?
+            .write_one(QOI_OP_LONG_RUN | (run >> 6) as u8)
+    }
+}
+
+#[inline]
+fn encode_impl<W: Writer, P: AsRef<[u8]>, I: Iterator<Item = P>, const N: usize>(
+    state: &mut State, cursor: &mut Cursor, buf: W, pixels: I, n_pixels: usize,
+) -> Result<W>
 where
     Pixel<N>: SupportedChannels,
     [u8; N]: Pod,
 {
-    let cap = buf.capacity();
+    if cursor.color_transform {
+        let pixels = pixels.map(|chunk| {
+            let mut px = Pixel::<4>::new().with_a(0xff);
+            px.read(chunk.as_ref());
+            <[u8; 4]>::from(px.rgb_to_ycocg())
+        });
+        encode_impl_lossless::<_, _, _, N>(state, cursor, buf, pixels, n_pixels)
+    } else if cursor.max_error != 0 {
+        encode_impl_near(state, cursor, buf, pixels, n_pixels)
+    } else {
+        encode_impl_lossless::<_, _, _, N>(state, cursor, buf, pixels, n_pixels)
+    }
+}
 
-    let mut px_prev = Pixel::<4>::new().with_a(0xff);
-    let mut run = 0_u16;
+#[allow(clippy::cast_possible_truncation, unused_assignments, unused_variables)]
+fn encode_impl_lossless<W: Writer, P: AsRef<[u8]>, I: Iterator<Item = P>, const N: usize>(
+    state: &mut State, cursor: &mut Cursor, mut buf: W, pixels: I, n_pixels: usize,
+) -> Result<W>
+where
+    Pixel<N>: SupportedChannels,
+    [u8; N]: Pod,
+{
+    let mut px_prev = cursor.px_prev;
+    let mut run = cursor.run;
     let mut px = px_prev;
 
-    let n_pixels = data.len() / N;
+    let mut i = cursor.pos;
 
-    for (i, chunk) in data.chunks_exact(N).enumerate() {
-        px.read(chunk);
+    for chunk in pixels {
+        px.read(chunk.as_ref());
         if px == px_prev {
             run += 1;
             if run == 1024 {
@@ -45,28 +165,12 @@ where
                 buf = buf.write_one(QOI_OP_LONG_RUN_MAX_1)?;
                 run = 0;
             } else if unlikely(i == n_pixels - 1) {
-                if run == 1 {
-                    buf = buf.write_one(QOI_OP_PREV)?;
-                } else if run <= 63 {
-                    buf = buf.write_one(QOI_OP_RUN | (run as u8 - 2))?;
-                } else {
-                    let run = run - 64;
-                    buf = buf.write_one(QOI_OP_LUMA | (run & 0x3f) as u8)?;
-                    buf = buf.write_one(QOI_OP_LONG_RUN | (run >> 6) as u8)?;
-                }
+                buf = encode_run(buf, run)?;
                 run = 0;
             }
         } else {
             if run != 0 {
-                if run == 1 {
-                    buf = buf.write_one(QOI_OP_PREV)?;
-                } else if run <= 63 {
-                    buf = buf.write_one(QOI_OP_RUN | (run as u8 - 2))?;
-                } else {
-                    let run = run - 64;
-                    buf = buf.write_one(QOI_OP_LUMA | (run & 0x3f) as u8)?;
-                    buf = buf.write_one(QOI_OP_LONG_RUN | (run >> 6) as u8)?;
-                }
+                buf = encode_run(buf, run)?;
                 run = 0;
             }
             let px_rgba = px.as_rgba(0xff);
@@ -92,18 +196,385 @@ where
             }
             px_prev = px;
         }
+        i += 1;
+        if unlikely(buf.is_full()) {
+            break;
+        }
+    }
+    cursor.pos = i;
+    cursor.px_prev = px_prev;
+    cursor.run = run;
+    Ok(buf)
+}
+
+/// Near-lossless counterpart of [`encode_impl_lossless`]: each pixel may be replaced by one within
+/// `cursor.max_error` per channel if that allows for a shorter op. The previous pixel and
+/// the caches track the pixels as they will be decoded, so any decoder can read the result.
+#[allow(clippy::cast_possible_truncation)]
+fn encode_impl_near<W: Writer, P: AsRef<[u8]>, I: Iterator<Item = P>>(
+    state: &mut State, cursor: &mut Cursor, mut buf: W, pixels: I, n_pixels: usize,
+) -> Result<W> {
+    let max_error = cursor.max_error;
+    let mut error = cursor.error;
+    let mut px_prev = cursor.px_prev;
+    let mut run = cursor.run;
+    let (crc_channels, mut crc_row) = (cursor.crc_channels as usize, cursor.crc_row);
+    // 3-channel sources are opaque, so only the decoded alpha may ever deviate
+    let mut px = Pixel::<4>::new().with_a(0xff);
+
+    let mut i = cursor.pos;
+
+    for chunk in pixels {
+        px.read(chunk.as_ref());
+        let prev_error = px.max_diff(px_prev);
+        // the pixel stored in place of the source one, for the checksum
+        let px_out = if prev_error <= max_error {
+            error = error.max(prev_error);
+            run += 1;
+            if run == 1024 {
+                buf = buf.write_one(QOI_OP_LONG_RUN_MAX_0)?;
+                buf = buf.write_one(QOI_OP_LONG_RUN_MAX_1)?;
+                run = 0;
+            } else if unlikely(i == n_pixels - 1) {
+                buf = encode_run(buf, run)?;
+                run = 0;
+            }
+            px_prev
+        } else {
+            if run != 0 {
+                buf = encode_run(buf, run)?;
+                run = 0;
+            }
+            let px_hash = px.hash_index();
+            let index_px = *state.index_l1(px_hash);
+            let long_index_px = *state.index_l2(px_hash);
+            let px_out = if index_px.max_diff(px) <= max_error {
+                buf = buf.write_one(QOI_OP_INDEX | (px_hash as u8 & 0x3f))?;
+                index_px
+            } else if let Some((px_out, len, encoded)) = px.encode_near(px_prev, max_error) {
+                buf = buf.write_many(&encoded[..len])?;
+                let old_px_l1 = replace(state.index_l1(px_out.hash_index()), px_out);
+                *state.index_l2(old_px_l1.hash_index()) = old_px_l1;
+                px_out
+            } else if long_index_px.max_diff(px) <= max_error {
+                buf = buf.write_one(QOI_OP_LUMA | (px_hash & 0x3f) as u8)?;
+                buf = buf.write_one((px_hash >> 2) as u8 | QOI_OP_LONG_INDEX)?;
+                let old_px_l1 = replace(state.index_l1(px_hash), long_index_px);
+                *state.index_l2(old_px_l1.hash_index()) = old_px_l1;
+                long_index_px
+            } else {
+                // keep the previous alpha if close enough so that QOI_OP_RGB will do
+                let px_rgb = px.with_a(px_prev.a_or(0xff));
+                let px_out = if px_rgb.max_diff(px) <= max_error { px_rgb } else { px };
+                let (len, encoded) = px_out.encode(px_prev);
+                buf = buf.write_many(&encoded[..len])?;
+                let old_px_l1 = replace(state.index_l1(px_out.hash_index()), px_out);
+                *state.index_l2(old_px_l1.hash_index()) = old_px_l1;
+                px_out
+            };
+            error = error.max(px_out.max_diff(px));
+            px_prev = px_out;
+            px_out
+        };
+        if crc_channels != 0 {
+            let (src, out) = (<[u8; 4]>::from(px), <[u8; 4]>::from(px_out));
+            let diff: [u8; 4] = [0, 1, 2, 3].map(|c| src[c] ^ out[c]);
+            crc_row.update(&diff[..crc_channels]);
+        }
+        i += 1;
+        if unlikely(buf.is_full()) {
+            break;
+        }
+    }
+    cursor.pos = i;
+    cursor.px_prev = px_prev;
+    cursor.run = run;
+    cursor.error = error;
+    cursor.crc_row = crc_row;
+    Ok(buf)
+}
+
+#[inline]
+fn encode_impl_slice<W: Writer, const N: usize>(
+    state: &mut State, cursor: &mut Cursor, out: W, data: &[u8],
+) -> Result<W>
+where
+    Pixel<N>: SupportedChannels,
+    [u8; N]: Pod,
+{
+    let n_pixels = data.len() / N;
+    cursor.pos = cursor.pos.min(n_pixels);
+    let pixels = data[cursor.pos * N..].chunks_exact(N);
+    encode_impl::<_, _, _, N>(state, cursor, out, pixels, n_pixels)
+}
+
+#[inline]
+fn encode_impl_channels<W: Writer>(
+    state: &mut State, cursor: &mut Cursor, out: W, data: &[u8], src_channels: Channels,
+    channels: Channels,
+) -> Result<W> {
+    match (src_channels, channels) {
+        (Channels::Rgba, Channels::Rgb) => {
+            // dropping alpha: the encoder starts out and stays opaque, same as for RGB data
+            let n_pixels = data.len() / 4;
+            cursor.pos = cursor.pos.min(n_pixels);
+            let pixels = data[cursor.pos * 4..].chunks_exact(4).map(|px| &px[..3]);
+            encode_impl::<_, _, _, 3>(state, cursor, out, pixels, n_pixels)
+        }
+        // synthesising alpha only changes the header, the pixels are encoded as is
+        (Channels::Rgb, _) => encode_impl_slice::<_, 3>(state, cursor, out, data),
+        (Channels::Rgba, _) => encode_impl_slice::<_, 4>(state, cursor, out, data),
+    }
+}
+
+/// Encodes the pixels of the `i`-th block (slice or tile) from the cursor onwards,
+/// the cursor position being the index of the pixel in encoding order.
+#[inline]
+#[allow(clippy::many_single_char_names)]
+fn encode_block<W: Writer>(
+    state: &mut State, cursor: &mut Cursor, out: W, data: &[u8], src_channels: Channels,
+    header: &Header, i: usize,
+) -> Result<W> {
+    #[cfg(any(feature = "alloc", feature = "std"))]
+    if header.is_block_coded() {
+        return encode_whole_block(state, cursor, out, data, src_channels, header, i);
+    }
+    let (n_src, width) = (src_channels.as_u8() as usize, header.width as usize);
+    let (x, y, w, h) = header.block_rect(i);
+    let (start, end) = (header.block_start(i), header.block_start(i) + w * h);
+    let n_pixels = header.n_pixels();
+    if w == width {
+        // full-width blocks are contiguous in the pixel data
+        let data = &data[..end * n_src];
+        let out = encode_impl_channels(state, cursor, out, data, src_channels, header.channels)?;
+        if cursor.is_done(end) {
+            cursor.finish_row(end, n_pixels);
+        }
+        return Ok(out);
+    }
+    cursor.pos = cursor.pos.clamp(start, end);
+    let (row, col) = ((cursor.pos - start) / w, (cursor.pos - start) % w);
+    let mut out = out;
+    // the rows of a tile are apart in the image, so the checksum is updated for each row
+    for r in row..h {
+        let offset = (y + r) * width + x;
+        let skip = if r == row { col } else { 0 };
+        let row = &data[(offset + skip) * n_src..(offset + w) * n_src];
+        out = match (src_channels, header.channels) {
+            (Channels::Rgba, Channels::Rgb) => {
+                let pixels = row.chunks_exact(4).map(|px| &px[..3]);
+                encode_impl::<_, _, _, 3>(state, cursor, out, pixels, end)
+            }
+            (Channels::Rgb, _) => {
+                encode_impl::<_, _, _, 3>(state, cursor, out, row.chunks_exact(3), end)
+            }
+            (Channels::Rgba, _) => {
+                encode_impl::<_, _, _, 4>(state, cursor, out, row.chunks_exact(4), end)
+            }
+        }?;
+        if !cursor.is_done(start + (r + 1) * w) {
+            break;
+        }
+        cursor.finish_row(offset + w, n_pixels);
+        if out.is_full() {
+            break;
+        }
+    }
+    Ok(out)
+}
+
+/// Encodes a run in an image predicted from the previous row. With vertical prediction,
+/// a single short run op holds at most 33 pixels; with a predictor, `QOI_OP_PREV` is
+/// `QOI_OP_DIFF` relative to the prediction, so a single pixel is taken from the index.
+#[cfg(any(feature = "alloc", feature = "std"))]
+#[inline]
+#[allow(clippy::cast_possible_truncation)]
+fn encode_run_predicted<W: Writer>(
+    mut buf: W, mut run: u16, px: Pixel<4>, header: &Header,
+) -> Result<W> {
+    if run == 1 && !header.predictor.is_left() {
+        return buf.write_one(QOI_OP_INDEX | (px.hash_index() as u8 & 0x3f));
+    }
+    if header.vertical_prediction && (34..64).contains(&run) {
+        buf = buf.write_one(QOI_OP_RUN_VERTICAL_END)?;
+        run -= 33;
+    }
+    encode_run(buf, run)
+}
+
+/// Encodes the whole `i`-th block of an image predicted from the previous row, keeping
+/// that row (the one above the first row being all opaque black); the cursor has to be
+/// at its start.
+///
+/// With vertical prediction, pixels equal to the ones above are stored as runs of those,
+/// and pixels that would otherwise take a full color as small differences to the pixel
+/// above. With a predictor, small differences are relative to the prediction from the
+/// neighbouring pixels, the ones at the left edge being predicted from the pixel above.
+/// With a palette, pixels that would otherwise take a full color are stored as their
+/// index into the palette, which has to hold every pixel of the image.
+#[cfg(any(feature = "alloc", feature = "std"))]
+#[allow(clippy::cast_possible_truncation, clippy::many_single_char_names)]
+fn encode_whole_block<W: Writer>(
+    state: &mut State, cursor: &mut Cursor, mut buf: W, data: &[u8], src_channels: Channels,
+    header: &Header, i: usize,
+) -> Result<W> {
+    let (n_src, width) = (src_channels.as_u8() as usize, header.width as usize);
+    let n_read = n_src.min(header.channels.as_u8() as usize);
+    let (x, y, w, h) = header.block_rect(i);
+    let (vertical, predicting) = (header.vertical_prediction, !header.predictor.is_left());
+    let palette = header.has_palette();
+    let mut above = vec![Pixel::<4>::new().with_a(0xff); w];
+    let mut px_prev = cursor.px_prev;
+    let (mut run, mut up_run) = (0_u16, 0_u8);
+
+    for r in 0..h {
+        let offset = (y + r) * width + x;
+        let row = &data[offset * n_src..(offset + w) * n_src];
+        let mut px_up_left = above[0];
+        for (col, (chunk, px_above)) in row.chunks_exact(n_src).zip(above.iter_mut()).enumerate() {
+            let mut px = Pixel::<4>::new().with_a(0xff);
+            px.read(&chunk[..n_read]);
+            if header.color_transform {
+                px = px.rgb_to_ycocg();
+            }
+            let px_up = replace(px_above, px);
+            let px_left = if col == 0 { px_up } else { px_prev };
+            let px_pred = px_prev.predict(header.predictor, px_left, px_up, px_up_left);
+            px_up_left = px_up;
+            if up_run != 0 {
+                if px == px_up {
+                    up_run += 1;
+                    if up_run == 29 {
+                        buf = buf.write_one(QOI_OP_UP_RUN | 0x1c)?;
+                        up_run = 0;
+                    }
+                    px_prev = px;
+                    continue;
+                }
+                buf = buf.write_one(QOI_OP_UP_RUN | (up_run - 1))?;
+                up_run = 0;
+            } else if run != 0 {
+                if px == px_prev {
+                    run += 1;
+                    if run == 1024 {
+                        buf = buf.write_one(QOI_OP_LONG_RUN_MAX_0)?;
+                        buf = buf.write_one(QOI_OP_LONG_RUN_MAX_1)?;
+                        run = 0;
+                    }
+                    continue;
+                }
+                buf = encode_run_predicted(buf, run, px_prev, header)?;
+                run = 0;
+            }
+            let px_hash = px.hash_index();
+            // a run is only started if a single pixel can be taken from the index instead
+            if px == px_prev && (!predicting || *state.index_l1(px_hash) == px) {
+                run = 1;
+                continue;
+            } else if vertical && px == px_up {
+                up_run = 1;
+                px_prev = px;
+                continue;
+            }
+            let index_px = state.index_l1(px_hash);
+            if *index_px == px {
+                buf = buf.write_one(QOI_OP_INDEX | (px_hash as u8 & 0x3f))?;
+            } else {
+                let old_px_l1 = replace(index_px, px);
+                let (mut len, mut encoded) = px.encode(px_pred);
+                if len <= 2 && *state.index_l2(px_hash) == px {
+                    len = 2;
+                    encoded = [
+                        QOI_OP_LUMA | (px_hash & 0x3f) as u8,
+                        (px_hash >> 2) as u8 | QOI_OP_LONG_INDEX,
+                        0,
+                        0,
+                        0,
+                    ];
+                } else if let (3.., true, Some(b2)) = (len, vertical, px.encode_up_diff(px_up)) {
+                    len = 2;
+                    encoded = [QOI_OP_UP_DIFF, b2, 0, 0, 0];
+                } else if let (3.., true, Some(index)) = (len, palette, state.palette_index(px)) {
+                    len = 2;
+                    encoded = [QOI_OP_PALETTE, index, 0, 0, 0];
+                }
+                buf = buf.write_many(&encoded[..len])?;
+                *state.index_l2(old_px_l1.hash_index()) = old_px_l1;
+            }
+            px_prev = px;
+        }
+    }
+    if up_run != 0 {
+        buf = buf.write_one(QOI_OP_UP_RUN | (up_run - 1))?;
+    } else if run != 0 {
+        buf = encode_run_predicted(buf, run, px_prev, header)?;
     }
-    Ok(cap.saturating_sub(buf.capacity()))
+    cursor.pos = header.block_start(i) + w * h;
+    cursor.px_prev = px_prev;
+    Ok(buf)
 }
 
+/// Returns the distinct colors of an image with at most 256 of them, sorted, with opaque
+/// alpha for RGB images; `None` if there are more.
+#[cfg(any(feature = "alloc", feature = "std"))]
+fn find_palette(data: &[u8], n_src: usize) -> Option<Vec<[u8; 4]>> {
+    let mut palette: Vec<[u8; 4]> = Vec::new();
+    let mut last = None;
+    for chunk in data.chunks_exact(n_src) {
+        let mut color = [0, 0, 0, 0xff];
+        color[..n_src].copy_from_slice(chunk);
+        if last == Some(color) {
+            continue;
+        }
+        last = Some(color);
+        if let Err(i) = palette.binary_search(&color) {
+            if palette.len() == QOI_PALETTE_MAX_LEN {
+                return None;
+            }
+            palette.insert(i, color);
+        }
+    }
+    Some(palette)
+}
+
+/// Returns the checksum of the pixels with the number of channels given in the header.
+#[inline]
+fn checksum(data: &[u8], src_channels: Channels, header: &Header) -> u32 {
+    let mut crc = Crc32::new();
+    crc.update_pixels(data, src_channels.as_u8(), header.channels.as_u8());
+    crc.finish()
+}
+
+/// Encodes the pixels from the cursor onwards; every slice or tile but the first one
+/// starts from a fresh state, so that they can be decoded independently.
 #[inline]
 fn encode_impl_all<W: Writer>(
-    state: &mut State, out: W, data: &[u8], channels: Channels,
-) -> Result<usize> {
-    match channels {
-        Channels::Rgb => encode_impl::<_, 3>(state, out, data),
-        Channels::Rgba => encode_impl::<_, 4>(state, out, data),
+    state: &mut State, cursor: &mut Cursor, mut out: W, data: &[u8], src_channels: Channels,
+    header: &Header,
+) -> Result<W> {
+    let n_pixels = header.n_pixels();
+    while !cursor.is_done(n_pixels) && !out.is_full() {
+        let i = header.block_at(cursor.pos);
+        if cursor.pos == header.block_start(i) && i != 0 {
+            state.restart();
+            cursor.restart(cursor.pos);
+        }
+        out = encode_block(state, cursor, out, data, src_channels, header, i)?;
     }
+    Ok(out)
+}
+
+/// Encodes the `i`-th slice or tile of the image on its own.
+///
+/// The state should be the initial one for the first block and a fresh one otherwise;
+/// the cursor is moved to the start of the block.
+#[inline]
+fn encode_block_alone<W: Writer>(
+    state: &mut State, cursor: &mut Cursor, out: W, data: &[u8], src_channels: Channels,
+    header: &Header, i: usize,
+) -> Result<W> {
+    cursor.restart(header.block_start(i));
+    encode_block(state, cursor, out, data, src_channels, header, i)
 }
 
 /// The maximum number of bytes the encoded image will take.
@@ -138,11 +609,231 @@ pub fn encode_to_vec<const DATA_ONLY: bool>(
     Encoder::new(&data, width, height)?.encode_to_vec::<DATA_ONLY>()
 }
 
+/// Encode the image from a slice of typed pixels into a newly allocated vector.
+///
+/// Unlike [`encode_to_vec`], the number of channels is given by the pixel type,
+/// `[u8; 3]` or `[u8; 4]`, rather than inferred from the length of the data.
+#[cfg(any(feature = "alloc", feature = "std"))]
+#[inline]
+pub fn encode_pixels_to_vec<const DATA_ONLY: bool, const N: usize>(
+    data: &[[u8; N]], width: u32, height: u32,
+) -> Result<Vec<u8>>
+where
+    Pixel<N>: SupportedChannels,
+    [u8; N]: Pod,
+{
+    Encoder::from_pixels(data, width, height)?.encode_to_vec::<DATA_ONLY>()
+}
+
+/// Encode the image from RGBA pixels packed into integers into a pre-allocated buffer.
+///
+/// Returns the total number of bytes written.
+#[inline]
+pub fn encode_u32_to_buf<const DATA_ONLY: bool>(
+    buf: impl AsMut<[u8]>, data: &[u32], width: u32, height: u32, order: ByteOrder,
+) -> Result<usize> {
+    let pixels = data.iter().map(|&px| order.unpack(px));
+    encode_iter_to_buf::<DATA_ONLY, 4>(buf, pixels, width, height)
+}
+
+/// Encode the image from RGBA pixels packed into integers into a newly allocated vector.
+#[cfg(any(feature = "alloc", feature = "std"))]
+#[inline]
+pub fn encode_u32_to_vec<const DATA_ONLY: bool>(
+    data: &[u32], width: u32, height: u32, order: ByteOrder,
+) -> Result<Vec<u8>> {
+    let pixels = data.iter().map(|&px| order.unpack(px));
+    encode_iter_to_vec::<DATA_ONLY, 4>(pixels, width, height)
+}
+
+/// Encode the image from an iterator of pixels into a pre-allocated buffer.
+///
+/// The number of channels is given by the pixel type, `[u8; 3]` or `[u8; 4]`, and the
+/// iterator must yield exactly `width * height` pixels. The output is identical to that
+/// of [`encode_to_buf`] for the same pixels. Returns the total number of bytes written.
+#[inline]
+pub fn encode_iter_to_buf<const DATA_ONLY: bool, const N: usize>(
+    buf: impl AsMut<[u8]>, pixels: impl IntoIterator<Item = [u8; N]>, width: u32, height: u32,
+) -> Result<usize>
+where
+    Pixel<N>: SupportedChannels,
+    [u8; N]: Pod,
+{
+    encode_iter_to_buf_with::<DATA_ONLY, N>(&mut State::default(), buf, pixels, width, height)
+}
+
+/// Encode the image from an iterator of pixels into a pre-allocated buffer, starting
+/// from a given state (e.g. of the previous frame, or a dictionary).
+///
+/// The state is updated in place, so it can be passed on to the next frame. See
+/// [`encode_iter_to_buf`] for details.
+#[inline]
+pub fn encode_iter_to_buf_with<const DATA_ONLY: bool, const N: usize>(
+    state: &mut State, mut buf: impl AsMut<[u8]>, pixels: impl IntoIterator<Item = [u8; N]>,
+    width: u32, height: u32,
+) -> Result<usize>
+where
+    Pixel<N>: SupportedChannels,
+    [u8; N]: Pod,
+{
+    let header = iter_header::<N>(state, width, height)?;
+    state.set_palette(&[]);
+    let buf = buf.as_mut();
+    let size_required = header.encode_max_len::<DATA_ONLY>();
+    if unlikely(buf.len() < size_required) {
+        return Err(Error::OutputBufferTooSmall { size: buf.len(), required: size_required });
+    }
+    let mut n_written = 0;
+    if !DATA_ONLY {
+        let (head, n) = header.encode();
+        buf[..n].copy_from_slice(&head[..n]);
+        n_written += n;
+    }
+    let n_pixels = header.n_pixels();
+    let mut pixels = pixels.into_iter();
+    let mut cursor = Cursor::default();
+    let out = BytesMut::new(&mut buf[n_written..]);
+    let cap = out.capacity();
+    let out = encode_impl::<_, _, _, N>(
+        state,
+        &mut cursor,
+        out,
+        pixels.by_ref().take(n_pixels),
+        n_pixels,
+    )?;
+    if unlikely(cursor.pos != n_pixels || pixels.next().is_some()) {
+        let size = (cursor.pos + usize::from(cursor.pos == n_pixels)) * N;
+        return Err(Error::InvalidImageLength { size, width, height });
+    }
+    n_written += cap.saturating_sub(out.capacity());
+    if !DATA_ONLY {
+        buf[n_written..n_written + QOI_PADDING_SIZE].copy_from_slice(&QOI_PADDING);
+        n_written += QOI_PADDING_SIZE;
+    }
+    Ok(n_written)
+}
+
+/// Encode the image from an iterator of pixels into a newly allocated vector.
+///
+/// See [`encode_iter_to_buf`] for details.
+#[cfg(any(feature = "alloc", feature = "std"))]
+#[inline]
+pub fn encode_iter_to_vec<const DATA_ONLY: bool, const N: usize>(
+    pixels: impl IntoIterator<Item = [u8; N]>, width: u32, height: u32,
+) -> Result<Vec<u8>>
+where
+    Pixel<N>: SupportedChannels,
+    [u8; N]: Pod,
+{
+    encode_iter_to_vec_with::<DATA_ONLY, N>(&mut State::default(), pixels, width, height)
+}
+
+/// Encode the image from an iterator of pixels into a newly allocated vector, starting
+/// from a given state.
+///
+/// See [`encode_iter_to_buf_with`] for details.
+#[cfg(any(feature = "alloc", feature = "std"))]
+#[inline]
+pub fn encode_iter_to_vec_with<const DATA_ONLY: bool, const N: usize>(
+    state: &mut State, pixels: impl IntoIterator<Item = [u8; N]>, width: u32, height: u32,
+) -> Result<Vec<u8>>
+where
+    Pixel<N>: SupportedChannels,
+    [u8; N]: Pod,
+{
+    let header = iter_header::<N>(state, width, height)?;
+    let mut out = vec![0_u8; header.encode_max_len::<DATA_ONLY>()];
+    let size = encode_iter_to_buf_with::<DATA_ONLY, N>(state, &mut out, pixels, width, height)?;
+    out.truncate(size);
+    Ok(out)
+}
+
+/// Returns the header of an image encoded from an iterator of pixels, starting from
+/// the given state.
+#[inline]
+fn iter_header<const N: usize>(state: &State, width: u32, height: u32) -> Result<Header> {
+    #[allow(clippy::cast_possible_truncation)]
+    let channels = Channels::try_from(N as u8)?;
+    let mut header = Header::try_new(width, height, channels, ColorSpace::default())?;
+    header.dictionary_id = state.dictionary_id();
+    Ok(header)
+}
+
+/// Outcome of an incremental encode, see [`Encoder::encode_to_buf_partial`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum EncodeStatus {
+    /// The buffer has been filled up and more output is pending
+    Incomplete(usize),
+    /// The image has been encoded completely
+    Complete(usize),
+}
+
+impl EncodeStatus {
+    /// Returns the number of bytes written into the buffer.
+    #[inline]
+    pub const fn n_written(self) -> usize {
+        match self {
+            Self::Incomplete(n) | Self::Complete(n) => n,
+        }
+    }
+
+    /// Returns true if the image has been encoded completely.
+    #[inline]
+    pub const fn is_complete(self) -> bool {
+        matches!(self, Self::Complete(_))
+    }
+}
+
+/// Part of the image the resumable encoder is writing, see [`Encoder::encode_to_buf_partial`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum EncodeStage {
+    Header,
+    Metadata,
+    Table,
+    Data,
+    Padding,
+    Done,
+}
+
+/// Where an incremental encode has left off.
+#[derive(Copy, Clone, Debug)]
+struct Progress {
+    stage: EncodeStage,
+    cursor: Cursor,
+    spill: Spill,
+    n_table: usize,
+    offset: usize,
+}
+
+impl Default for Progress {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            stage: EncodeStage::Header,
+            cursor: Cursor::default(),
+            spill: Spill::default(),
+            n_table: 0,
+            offset: 0,
+        }
+    }
+}
+
 /// Encode QOI images into buffers or into streams.
 pub struct Encoder<'a> {
     data: &'a [u8],
+    src_channels: Channels,
     header: Header,
     state: State,
+    progress: Progress,
+    max_error: u8,
+    error: u8,
+    /// CRC of the differences between the stored and the source pixels, see `Cursor::crc`.
+    crc: u32,
+    metadata: Metadata,
+    #[cfg(any(feature = "alloc", feature = "std"))]
+    staged: Vec<u8>,
+    #[cfg(any(feature = "alloc", feature = "std"))]
+    palette: Vec<[u8; 4]>,
 }
 
 impl<'a> Encoder<'a> {
@@ -168,7 +859,72 @@ impl<'a> Encoder<'a> {
             return Err(Error::InvalidImageLength { size, width, height });
         }
         header.channels = Channels::try_from(n_channels.min(0xff) as u8)?;
-        Ok(Self { data, header, state })
+        header.dictionary_id = state.dictionary_id();
+        let src_channels = header.channels;
+        let mut state = state;
+        state.set_palette(&[]);
+        Ok(Self {
+            data,
+            src_channels,
+            header,
+            state,
+            progress: Progress::default(),
+            max_error: 0,
+            error: 0,
+            crc: 0,
+            metadata: Metadata::new(),
+            #[cfg(any(feature = "alloc", feature = "std"))]
+            staged: Vec::new(),
+            #[cfg(any(feature = "alloc", feature = "std"))]
+            palette: Vec::new(),
+        })
+    }
+
+    /// Creates a new encoder from a slice of typed pixels and image dimensions.
+    ///
+    /// The number of channels is given by the pixel type, `[u8; 3]` or `[u8; 4]`,
+    /// and the slice must contain exactly `width * height` pixels.
+    #[inline]
+    pub fn from_pixels<const N: usize>(data: &'a [[u8; N]], width: u32, height: u32) -> Result<Self>
+    where
+        Pixel<N>: SupportedChannels,
+        [u8; N]: Pod,
+    {
+        Self::from_pixels_with(State::default(), data, width, height)
+    }
+    #[inline]
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn from_pixels_with<const N: usize>(
+        state: State, data: &'a [[u8; N]], width: u32, height: u32,
+    ) -> Result<Self>
+    where
+        Pixel<N>: SupportedChannels,
+        [u8; N]: Pod,
+    {
+        let channels = Channels::try_from(N as u8)?;
+        let mut header = Header::try_new(width, height, channels, ColorSpace::default())?;
+        header.dictionary_id = state.dictionary_id();
+        if unlikely(data.len() != header.n_pixels()) {
+            return Err(Error::InvalidImageLength { size: data.len() * N, width, height });
+        }
+        let data = cast_slice(data);
+        let mut state = state;
+        state.set_palette(&[]);
+        Ok(Self {
+            data,
+            src_channels: channels,
+            header,
+            state,
+            progress: Progress::default(),
+            max_error: 0,
+            error: 0,
+            crc: 0,
+            metadata: Metadata::new(),
+            #[cfg(any(feature = "alloc", feature = "std"))]
+            staged: Vec::new(),
+            #[cfg(any(feature = "alloc", feature = "std"))]
+            palette: Vec::new(),
+        })
     }
 
     /// Returns a new encoder with modified color space.
@@ -181,12 +937,250 @@ impl<'a> Encoder<'a> {
         self
     }
 
-    /// Returns the inferred number of channels.
+    /// Returns a new encoder storing the image with the given number of channels.
+    ///
+    /// The pixel data is converted on the fly: when storing RGBA data as RGB, the alpha
+    /// channel is dropped; when storing RGB data as RGBA, it is set to 255.
+    #[inline]
+    pub const fn with_channels(mut self, channels: Channels) -> Self {
+        self.header.channels = channels;
+        self.progress.cursor = self.new_cursor();
+        self
+    }
+
+    /// Returns a new encoder storing RGBA data as RGB if its alpha channel is fully opaque.
+    ///
+    /// Note: this scans the whole image once. Otherwise, the number of channels of the
+    /// input data is kept.
+    #[inline]
+    pub fn with_auto_channels(mut self) -> Self {
+        self.header.channels =
+            if self.src_channels.is_rgba() && self.data.chunks_exact(4).all(|px| px[3] == 0xff) {
+                Channels::Rgb
+            } else {
+                self.src_channels
+            };
+        self.progress.cursor = self.new_cursor();
+        self
+    }
+
+    /// Returns a new encoder splitting the image into horizontal slices of the given height.
+    ///
+    /// Each slice is encoded independently of the others, and a table of their offsets is
+    /// stored after the header, so that large images can be decoded in parallel (and, with
+    /// the `rayon` feature, are also encoded in parallel). This costs a little compression.
+    /// Setting it to 0 (the default) disables slicing; see [`Header::with_slice_height`].
+    #[inline]
+    pub const fn with_slice_height(mut self, slice_height: u32) -> Self {
+        self.header = self.header.with_slice_height(slice_height);
+        self
+    }
+
+    /// Returns a new encoder splitting the image into tiles of the given size.
+    ///
+    /// Each tile is encoded independently of the others, and a table of their offsets is
+    /// stored after the header, so that a region of a large image can be decoded without
+    /// decoding the rest of it via [`Decoder::decode_region`](crate::Decoder::decode_region).
+    /// Setting either dimension to 0 (the default) disables tiling; see
+    /// [`Header::with_tile_size`].
+    #[inline]
+    pub const fn with_tile_size(mut self, tile_width: u32, tile_height: u32) -> Self {
+        self.header = self.header.with_tile_size(tile_width, tile_height);
+        self
+    }
+
+    /// Returns a new encoder with entropy coding of the op stream enabled or disabled.
+    ///
+    /// The ops are compressed with a static Huffman code in a second pass, which typically
+    /// saves a further 10-30% at the cost of speed; see [`Header::with_entropy_coding`].
+    /// The image is staged in memory when encoding incrementally or into a stream.
+    #[cfg(any(feature = "alloc", feature = "std"))]
+    #[inline]
+    pub const fn with_entropy_coding(mut self, entropy_coded: bool) -> Self {
+        self.header = self.header.with_entropy_coding(entropy_coded);
+        self
+    }
+
+    /// Returns a new encoder with vertical prediction enabled or disabled.
+    ///
+    /// Pixels may then also be stored as runs of the pixels above or as small differences
+    /// to the pixel above; see [`Header::with_vertical_prediction`]. Encoding this way is
+    /// always lossless, any [`Encoder::with_max_error`] setting is ignored. The image is
+    /// staged in memory when encoding incrementally.
+    #[cfg(any(feature = "alloc", feature = "std"))]
+    #[inline]
+    pub const fn with_vertical_prediction(mut self, vertical_prediction: bool) -> Self {
+        self.header = self.header.with_vertical_prediction(vertical_prediction);
+        self
+    }
+
+    /// Returns a new encoder with a modified predictor.
+    ///
+    /// `QOI_OP_DIFF` and `QOI_OP_LUMA` then store the difference to the prediction from the
+    /// neighbouring pixels; see [`Header::with_predictor`]. Like vertical prediction, any
+    /// predictor but [`Predictor::Left`] makes encoding lossless and stages the image in
+    /// memory when encoding incrementally.
+    #[cfg(any(feature = "alloc", feature = "std"))]
+    #[inline]
+    pub const fn with_predictor(mut self, predictor: Predictor) -> Self {
+        self.header = self.header.with_predictor(predictor);
+        self
+    }
+
+    /// Returns a new encoder storing the image with a palette if it has at most 256 colors.
+    ///
+    /// The palette is stored after the header and preloaded into the index, and pixels
+    /// that would otherwise take a full color are stored as their index into it; see
+    /// [`Header::palette_len`]. Decoders still output regular RGB(A) pixels. The image is
+    /// scanned right away, images with more colors are encoded as usual. Like vertical
+    /// prediction, encoding with a palette is lossless and stages the image in memory when
+    /// encoding incrementally.
+    #[cfg(any(feature = "alloc", feature = "std"))]
+    #[inline]
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn with_palette(mut self, palette: bool) -> Self {
+        self.palette = if palette {
+            find_palette(self.data, self.src_channels.as_u8() as usize).unwrap_or_default()
+        } else {
+            Vec::new()
+        };
+        self.header.palette_len = self.palette.len() as u32;
+        self
+    }
+
+    /// Returns a new encoder with a metadata chunk of the given kind appended, see
+    /// [`Metadata`].
+    ///
+    /// The chunks are stored right after the header, in the order they were added;
+    /// decoders read them back via [`Decoder::metadata`](crate::Decoder::metadata).
+    /// Fails with [`Error::InvalidMetadata`] if the chunks would take more than 4 GiB.
+    #[cfg(any(feature = "alloc", feature = "std"))]
+    #[inline]
+    pub fn with_chunk(mut self, chunk_type: ChunkType, data: &[u8]) -> Result<Self> {
+        self.metadata.push(chunk_type, data)?;
+        Ok(self.with_metadata_len())
+    }
+
+    /// Returns a new encoder with a key-value text chunk appended, see
+    /// [`Encoder::with_chunk`] and [`Metadata::push_text`].
+    #[cfg(any(feature = "alloc", feature = "std"))]
+    #[inline]
+    pub fn with_text(mut self, key: &str, value: &str) -> Result<Self> {
+        self.metadata.push_text(key, value)?;
+        Ok(self.with_metadata_len())
+    }
+
+    /// Returns a new encoder storing the given metadata chunks instead of the ones added
+    /// so far, e.g. to carry over those of a decoded image.
+    #[inline]
+    pub fn with_metadata(mut self, metadata: &Metadata) -> Self {
+        self.metadata = metadata.clone();
+        self.with_metadata_len()
+    }
+
+    /// Updates the header to announce the metadata chunks.
+    #[inline]
+    #[allow(clippy::cast_possible_truncation)]
+    fn with_metadata_len(mut self) -> Self {
+        self.header.metadata_len = self.metadata.as_bytes().len() as u32;
+        self
+    }
+
+    /// Returns the metadata chunks stored after the header.
+    #[inline]
+    pub const fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    /// Returns a new encoder starting from a dictionary trained via [`State::train`].
+    ///
+    /// The dictionary is copied into the index before encoding and again at the start of
+    /// each slice or tile, and its identifier is stored in the header; decoders have to
+    /// start from the same dictionary via
+    /// [`Decoder::with_dictionary`](crate::Decoder::with_dictionary).
+    #[inline]
+    pub fn with_dictionary(mut self, dictionary: &State) -> Self {
+        self.state = self.state.with_dictionary(dictionary);
+        self.header.dictionary_id = dictionary.dictionary_id();
+        self
+    }
+
+    /// Returns a new encoder allowing each channel of each pixel to deviate from the source
+    /// by up to `max_error` (near-lossless encoding).
+    ///
+    /// Pixels are nudged towards ones that can be stored as runs, cache lookups or small
+    /// differences instead of full colors. The output is a regular image that any decoder
+    /// reads as usual; the largest deviation actually made is reported by
+    /// [`Encoder::error`]. Setting it to 0 (the default) keeps encoding lossless.
+    ///
+    /// Only plain encoding is near-lossless: with [`Encoder::with_color_transform`],
+    /// [`Encoder::with_vertical_prediction`], [`Encoder::with_predictor`] or
+    /// [`Encoder::with_palette`], `max_error` is ignored, the image is encoded losslessly
+    /// and [`Encoder::error`] stays 0.
+    #[inline]
+    pub const fn with_max_error(mut self, max_error: u8) -> Self {
+        self.max_error = max_error;
+        self.progress.cursor = self.new_cursor();
+        self
+    }
+
+    /// Returns a new encoder with the reversible YCoCg-R color transform enabled or disabled.
+    ///
+    /// Each pixel is converted before choosing an op and converted back by the decoder;
+    /// see [`Header::with_color_transform`]. Encoding with the transform is always lossless,
+    /// any [`Encoder::with_max_error`] setting is ignored.
+    #[inline]
+    pub const fn with_color_transform(mut self, color_transform: bool) -> Self {
+        self.header = self.header.with_color_transform(color_transform);
+        self.progress.cursor = self.new_cursor();
+        self
+    }
+
+    /// Returns a new encoder with the pixel checksum enabled or disabled.
+    ///
+    /// A CRC-32 of the pixels is then stored after the padding, so that decoders can
+    /// detect corruption inside the op stream; see [`Header::with_checksum`]. With
+    /// near-lossless encoding, the checksum is that of the pixels as they are stored.
+    #[inline]
+    pub const fn with_checksum(mut self, checksum: bool) -> Self {
+        self.header = self.header.with_checksum(checksum);
+        self.progress.cursor = self.new_cursor();
+        self
+    }
+
+    /// Returns the checksum of the pixels stored after the padding, i.e. of the source pixels
+    /// corrected by the differences found while encoding them.
+    #[inline]
+    fn checksum(&self) -> u32 {
+        checksum(self.data, self.src_channels, &self.header) ^ self.crc
+    }
+
+    /// Returns a cursor at the start of the image with the current settings.
+    #[inline]
+    const fn new_cursor(&self) -> Cursor {
+        let crc_channels = if self.header.checksum { self.header.channels.as_u8() } else { 0 };
+        Cursor::new(self.max_error, self.header.color_transform, crc_channels)
+    }
+
+    /// Returns the largest difference between a channel of a source pixel and of the pixel
+    /// stored in its place, over the pixels encoded so far; always 0 for lossless encoding.
+    #[inline]
+    pub const fn error(&self) -> u8 {
+        self.error
+    }
+
+    /// Returns the number of channels the image will be stored with.
     #[inline]
     pub const fn channels(&self) -> Channels {
         self.header.channels
     }
 
+    /// Returns the number of channels of the input data.
+    #[inline]
+    pub const fn src_channels(&self) -> Channels {
+        self.src_channels
+    }
+
     /// Returns the header that will be stored in the encoded image.
     #[inline]
     pub const fn header(&self) -> &Header {
@@ -204,6 +1198,7 @@ impl<'a> Encoder<'a> {
     /// Encodes the image to a pre-allocated buffer and returns the number of bytes written.
     ///
     /// The minimum size of the buffer can be found via [`Encoder::required_buf_len`].
+    /// To encode into smaller buffers, use [`Encoder::encode_to_buf_partial`] instead.
     #[inline]
     pub fn encode_to_buf<const DATA_ONLY: bool>(
         &mut self, mut buf: impl AsMut<[u8]>,
@@ -215,22 +1210,322 @@ impl<'a> Encoder<'a> {
         }
         let mut n_written = 0;
         if !DATA_ONLY {
-            buf[..QOI_HEADER_SIZE].copy_from_slice(&self.header.encode());
-            n_written += QOI_HEADER_SIZE;
+            let (head, n) = self.header.encode();
+            buf[..n].copy_from_slice(&head[..n]);
+            n_written += n;
+            let metadata = self.metadata.as_bytes();
+            buf[n..n + metadata.len()].copy_from_slice(metadata);
+            n_written += metadata.len();
+        }
+        #[cfg(any(feature = "alloc", feature = "std"))]
+        if self.header.has_palette() {
+            let palette = self.load_palette();
+            if !DATA_ONLY {
+                for px in palette {
+                    let px = if self.header.color_transform { px.ycocg_to_rgb() } else { px };
+                    let rgba: [u8; 4] = px.into();
+                    buf[n_written..n_written + 4].copy_from_slice(&rgba);
+                    n_written += 4;
+                }
+            }
+        }
+        if !DATA_ONLY && self.header.table_len() != 0 {
+            n_written += self.encode_blocks(&mut buf[n_written..])?;
+        } else {
+            let out = BytesMut::new(&mut buf[n_written..]);
+            let cap = out.capacity();
+            let mut cursor = self.new_cursor();
+            let out = encode_impl_all(
+                &mut self.state,
+                &mut cursor,
+                out,
+                self.data,
+                self.src_channels,
+                &self.header,
+            )?;
+            (self.error, self.crc) = (cursor.error, cursor.crc);
+            n_written += cap.saturating_sub(out.capacity());
+        }
+        #[cfg(any(feature = "alloc", feature = "std"))]
+        if self.header.entropy_coded {
+            // the ops are entropy-coded in place, the offset table is kept as is
+            let start = if DATA_ONLY { 0 } else { self.header.encoded_len() };
+            let coded = crate::entropy::encode(&buf[start..n_written]);
+            buf[start..start + coded.len()].copy_from_slice(&coded);
+            n_written = start + coded.len();
         }
-        n_written += encode_impl_all(
-            &mut self.state,
-            BytesMut::new(&mut buf[n_written..]),
-            self.data,
-            self.header.channels,
-        )?;
         if !DATA_ONLY {
             buf[n_written..n_written + QOI_PADDING_SIZE].copy_from_slice(&QOI_PADDING);
             n_written += QOI_PADDING_SIZE;
         }
+        if !DATA_ONLY && self.header.checksum {
+            let checksum = self.checksum().to_be_bytes();
+            buf[n_written..n_written + QOI_CHECKSUM_SIZE].copy_from_slice(&checksum);
+            n_written += QOI_CHECKSUM_SIZE;
+        }
         Ok(n_written)
     }
 
+    /// Loads the palette into the state, in the color space the ops are coded in and
+    /// sorted, and returns it in that order.
+    #[cfg(any(feature = "alloc", feature = "std"))]
+    fn load_palette(&mut self) -> Vec<Pixel<4>> {
+        let n_read = self.src_channels.as_u8().min(self.header.channels.as_u8()) as usize;
+        let mut palette: Vec<_> = (self.palette.iter())
+            .map(|color| {
+                let mut px = Pixel::<4>::new().with_a(0xff);
+                px.read(&color[..n_read]);
+                if self.header.color_transform {
+                    px = px.rgb_to_ycocg();
+                }
+                px
+            })
+            .collect();
+        palette.sort_unstable_by_key(|&px| <[u8; 4]>::from(px));
+        self.state.set_palette(&palette);
+        palette
+    }
+
+    /// Writes the table of slice or tile offsets followed by the slices or tiles,
+    /// returns the number of bytes written.
+    #[allow(clippy::cast_possible_truncation)]
+    fn encode_blocks(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let (table, buf) = buf.split_at_mut(self.header.table_len());
+        let mut n_written = 0;
+        #[cfg(feature = "rayon")]
+        for (i, block) in self.encode_blocks_par()?.iter().enumerate() {
+            buf[n_written..n_written + block.len()].copy_from_slice(block);
+            n_written += block.len();
+            table[i * 4..i * 4 + 4].copy_from_slice(&(n_written as u32).to_be_bytes());
+        }
+        #[cfg(not(feature = "rayon"))]
+        {
+            let mut cursor = self.new_cursor();
+            for i in 0..self.header.n_blocks() {
+                if i != 0 {
+                    self.state.restart();
+                }
+                let out = BytesMut::new(&mut buf[n_written..]);
+                let cap = out.capacity();
+                let (state, data, header) = (&mut self.state, self.data, &self.header);
+                let out = encode_block_alone(
+                    state,
+                    &mut cursor,
+                    out,
+                    data,
+                    self.src_channels,
+                    header,
+                    i,
+                )?;
+                n_written += cap - out.capacity();
+                table[i * 4..i * 4 + 4].copy_from_slice(&(n_written as u32).to_be_bytes());
+            }
+            (self.error, self.crc) = (cursor.error, cursor.crc);
+        }
+        Ok(table.len() + n_written)
+    }
+
+    /// Encodes the slices or tiles on multiple threads, each into its own vector.
+    #[cfg(feature = "rayon")]
+    fn encode_blocks_par(&mut self) -> Result<Vec<Vec<u8>>> {
+        use rayon::prelude::*;
+
+        let (data, src_channels, header) = (self.data, self.src_channels, &self.header);
+        let (initial, n_blocks, cursor) = (&self.state, header.n_blocks(), self.new_cursor());
+        let (w, h) = header.block_size();
+        let max_len = w * h * (header.channels.as_u8() as usize + 1);
+        let blocks = (0..n_blocks)
+            .into_par_iter()
+            .map(|i| {
+                let mut state = if i == 0 { initial.clone() } else { initial.fresh() };
+                let mut block = vec![0; max_len];
+                let out = BytesMut::new(&mut block);
+                let cap = out.capacity();
+                let mut cursor = cursor;
+                let out = encode_block_alone(
+                    &mut state,
+                    &mut cursor,
+                    out,
+                    data,
+                    src_channels,
+                    header,
+                    i,
+                )?;
+                let n = cap - out.capacity();
+                block.truncate(n);
+                Ok((block, (cursor.error, cursor.crc), (i == n_blocks - 1).then(|| state)))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        (self.error, self.crc) = (0, 0);
+        Ok(blocks
+            .into_iter()
+            .map(|(block, (error, crc), state)| {
+                self.error = self.error.max(error);
+                // the differences in each block are positioned in the image already
+                self.crc ^= crc;
+                if let Some(state) = state {
+                    self.state = state;
+                }
+                block
+            })
+            .collect())
+    }
+
+    /// Encodes as much of the image as fits into a buffer of any size.
+    ///
+    /// If the buffer fills up before the image is complete, [`EncodeStatus::Incomplete`]
+    /// is returned; the caller can then drain the buffer and call this method again to
+    /// resume encoding where it left off, until [`EncodeStatus::Complete`] is returned.
+    /// The output is identical to that of [`Encoder::encode_to_buf`].
+    ///
+    /// The table of a sliced or tiled image precedes the blocks, so unless the buffer can
+    /// hold the table and the blocks in the worst case (as for [`Encoder::encode_to_buf`]),
+    /// each block is encoded twice: once to find its size, and once for real.
+    ///
+    /// Entropy coding, vertical prediction, predictors and palettes need the whole image
+    /// or whole blocks at once: with those, the image is encoded into an internal vector
+    /// of its full size on the first call, which is then handed out piecewise, so memory
+    /// use isn't bounded by the buffer.
+    ///
+    /// Note: an encoder used this way should not be used with other `encode_*` methods.
+    #[inline]
+    pub fn encode_to_buf_partial<const DATA_ONLY: bool>(
+        &mut self, mut buf: impl AsMut<[u8]>,
+    ) -> Result<EncodeStatus> {
+        let buf = buf.as_mut();
+        #[cfg(any(feature = "alloc", feature = "std"))]
+        if self.header.entropy_coded || self.header.is_block_coded() {
+            // the entropy coder needs all of the ops, and prediction from the row above
+            // or a palette can only encode whole slices or tiles at once
+            return self.encode_staged::<DATA_ONLY>(buf);
+        }
+        let cursor = self.new_cursor();
+        let mut n_written = self.progress.spill.drain_into(buf);
+        while n_written < buf.len() && self.progress.stage != EncodeStage::Done {
+            if self.progress.stage == EncodeStage::Table && self.fits_blocks(buf.len() - n_written)
+            {
+                // the blocks are encoded once and the table is filled in after them
+                n_written += self.encode_blocks(&mut buf[n_written..])?;
+                self.progress.stage = EncodeStage::Padding;
+                continue;
+            }
+            let progress = &mut self.progress;
+            let out = BytesSpill::new(&mut buf[n_written..], &mut progress.spill);
+            let cap = out.capacity();
+            let out = match progress.stage {
+                EncodeStage::Header => {
+                    if DATA_ONLY {
+                        progress.stage = EncodeStage::Data;
+                        out
+                    } else {
+                        progress.stage = EncodeStage::Metadata;
+                        let (head, n) = self.header.encode();
+                        out.write_many(&head[..n])?
+                    }
+                }
+                EncodeStage::Metadata => {
+                    // the chunks may be of any size, so they bypass the spill
+                    let metadata = &self.metadata.as_bytes()[progress.offset..];
+                    let n = metadata.len().min(out.capacity());
+                    progress.offset += n;
+                    if n == metadata.len() {
+                        let has_table = self.header.table_len() != 0;
+                        progress.stage =
+                            if has_table { EncodeStage::Table } else { EncodeStage::Data };
+                        progress.offset = 0;
+                    }
+                    out.write_many(&metadata[..n])?
+                }
+                EncodeStage::Table => {
+                    // the block sizes are only known after encoding them, so each block
+                    // is encoded twice: once here to find out its size, and once for real
+                    let i = progress.n_table;
+                    let mut state = if i == 0 { self.state.clone() } else { self.state.fresh() };
+                    let size = encode_block_alone(
+                        &mut state,
+                        &mut Cursor { ..cursor },
+                        Counter::default(),
+                        self.data,
+                        self.src_channels,
+                        &self.header,
+                        i,
+                    )?;
+                    progress.offset += size.0;
+                    progress.n_table += 1;
+                    if progress.n_table == self.header.n_blocks() {
+                        progress.stage = EncodeStage::Data;
+                    }
+                    #[allow(clippy::cast_possible_truncation)]
+                    out.write_many(&(progress.offset as u32).to_be_bytes())?
+                }
+                EncodeStage::Data => {
+                    let out = encode_impl_all(
+                        &mut self.state,
+                        &mut progress.cursor,
+                        out,
+                        self.data,
+                        self.src_channels,
+                        &self.header,
+                    )?;
+                    (self.error, self.crc) = (progress.cursor.error, progress.cursor.crc);
+                    if progress.cursor.is_done(self.header.n_pixels()) {
+                        progress.stage = EncodeStage::Padding;
+                    }
+                    out
+                }
+                EncodeStage::Padding | EncodeStage::Done => {
+                    progress.stage = EncodeStage::Done;
+                    if DATA_ONLY {
+                        out
+                    } else if self.header.checksum {
+                        let checksum =
+                            checksum(self.data, self.src_channels, &self.header) ^ self.crc;
+                        out.write_many(&QOI_PADDING)?.write_many(&checksum.to_be_bytes())?
+                    } else {
+                        out.write_many(&QOI_PADDING)?
+                    }
+                }
+            };
+            n_written += cap - out.capacity();
+        }
+        let progress = &self.progress;
+        Ok(if progress.stage == EncodeStage::Done && progress.spill.is_empty() {
+            EncodeStatus::Complete(n_written)
+        } else {
+            EncodeStatus::Incomplete(n_written)
+        })
+    }
+
+    /// Returns true if a buffer of `len` bytes can hold the whole table and all of the
+    /// blocks in the worst case, before any of the table has been written.
+    #[inline]
+    fn fits_blocks(&self, len: usize) -> bool {
+        let header = &self.header;
+        let blocks_len =
+            header.encode_max_len::<false>() - header.encoded_len() + header.table_len();
+        self.progress.n_table == 0 && len >= blocks_len
+    }
+
+    /// Encodes the whole image into memory on the first call, then hands it out piecewise.
+    #[cfg(any(feature = "alloc", feature = "std"))]
+    fn encode_staged<const DATA_ONLY: bool>(&mut self, buf: &mut [u8]) -> Result<EncodeStatus> {
+        if self.progress.stage == EncodeStage::Header {
+            self.staged = self.encode_to_vec::<DATA_ONLY>()?;
+            self.progress.stage = EncodeStage::Data;
+        }
+        let progress = &mut self.progress;
+        let staged = &self.staged[progress.offset..];
+        let n = staged.len().min(buf.len());
+        buf[..n].copy_from_slice(&staged[..n]);
+        progress.offset += n;
+        Ok(if n == staged.len() {
+            progress.stage = EncodeStage::Done;
+            EncodeStatus::Complete(n)
+        } else {
+            EncodeStatus::Incomplete(n)
+        })
+    }
+
     /// Encodes the image into a newly allocated vector of bytes and returns it.
     #[cfg(any(feature = "alloc", feature = "std"))]
     #[inline]
@@ -243,6 +1538,9 @@ impl<'a> Encoder<'a> {
 
     /// Encodes the image directly to a generic writer that implements [`Write`](std::io::Write).
     ///
+    /// The encoded bytes are staged in an internal buffer and written out in blocks,
+    /// so there's no need to wrap unbuffered writers into a `BufWriter`.
+    ///
     /// Note: while it's possible to pass a `&mut [u8]` slice here since it implements `Write`,
     /// it would more effficient to use a specialized method instead: [`Encoder::encode_to_buf`].
     #[cfg(feature = "std")]
@@ -250,22 +1548,46 @@ impl<'a> Encoder<'a> {
     pub fn encode_to_stream<W: Write, const DATA_ONLY: bool>(
         &mut self, mut writer: W,
     ) -> Result<usize> {
-        let mut n_written = 0;
+        let staged = self.header.entropy_coded || self.header.has_palette();
+        if (!DATA_ONLY && self.header.table_len() != 0) || staged {
+            // the offset table precedes the blocks, the entropy coder needs all of the ops
+            // and the palette is loaded along the way, so the image is encoded in memory first
+            let encoded = self.encode_to_vec::<DATA_ONLY>()?;
+            writer.write_all(&encoded)?;
+            writer.flush()?;
+            return Ok(encoded.len());
+        }
+        let mut out = GenericWriter::new(&mut writer);
         if !DATA_ONLY {
-            writer.write_all(&self.header.encode())?;
-            n_written += QOI_HEADER_SIZE;
+            let (head, n) = self.header.encode();
+            out = out.write_many(&head[..n])?.write_many(self.metadata.as_bytes())?;
         }
-        n_written += encode_impl_all(
+        let mut cursor = self.new_cursor();
+        out = encode_impl_all(
             &mut self.state,
-            GenericWriter::new(&mut writer),
+            &mut cursor,
+            out,
             self.data,
-            self.header.channels,
+            self.src_channels,
+            &self.header,
         )?;
+        (self.error, self.crc) = (cursor.error, cursor.crc);
         if !DATA_ONLY {
-            writer.write_all(&QOI_PADDING)?;
-            n_written += QOI_PADDING_SIZE;
+            out = out.write_many(&QOI_PADDING)?;
         }
-        Ok(n_written)
+        if !DATA_ONLY && self.header.checksum {
+            out = out.write_many(&self.checksum().to_be_bytes())?;
+        }
+        out.finish()
+    }
+
+    /// Converts the encoder into a reader that produces the encoded image lazily.
+    ///
+    /// See [`EncoderReader`] for details.
+    #[cfg(feature = "std")]
+    #[inline]
+    pub const fn into_reader<const DATA_ONLY: bool>(self) -> EncoderReader<'a, DATA_ONLY> {
+        EncoderReader { encoder: self, done: false }
     }
 
     #[inline]
@@ -273,3 +1595,57 @@ impl<'a> Encoder<'a> {
         self.state
     }
 }
+
+/// Pull-based encoder that implements [`Read`](std::io::Read).
+///
+/// The header, ops and padding are produced on the fly as the consumer reads from it,
+/// so the encoded image is never materialised in memory as a whole. This allows passing
+/// it directly to anything that consumes a reader, like an HTTP client body or a
+/// compressor.
+///
+/// Created via [`Encoder::into_reader`] or [`EncoderReader::new`].
+#[cfg(feature = "std")]
+pub struct EncoderReader<'a, const DATA_ONLY: bool = false> {
+    encoder: Encoder<'a>,
+    done: bool,
+}
+
+#[cfg(feature = "std")]
+impl<'a, const DATA_ONLY: bool> EncoderReader<'a, DATA_ONLY> {
+    /// Creates a new reader from a given array of pixel data and image dimensions.
+    ///
+    /// The number of channels will be inferred automatically, same as in [`Encoder::new`].
+    #[inline]
+    pub fn new(data: &'a (impl AsRef<[u8]> + ?Sized), width: u32, height: u32) -> Result<Self> {
+        Ok(Encoder::new(data, width, height)?.into_reader())
+    }
+
+    /// Returns the header that will be stored in the encoded image.
+    #[inline]
+    pub const fn header(&self) -> &Header {
+        self.encoder.header()
+    }
+
+    /// Returns true if the encoded image has been read completely.
+    #[inline]
+    pub const fn is_done(&self) -> bool {
+        self.done
+    }
+
+    #[inline]
+    pub fn into_state(self) -> State {
+        self.encoder.into_state()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<const DATA_ONLY: bool> Read for EncoderReader<'_, DATA_ONLY> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.done || buf.is_empty() {
+            return Ok(0);
+        }
+        let status = self.encoder.encode_to_buf_partial::<DATA_ONLY>(buf)?;
+        self.done = status.is_complete();
+        Ok(status.n_written())
+    }
+}
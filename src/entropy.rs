@@ -0,0 +1,181 @@
+//! Optional second stage: static canonical Huffman coding of the op byte stream.
+//!
+//! The coded stream consists of the number of op bytes (u32), the code lengths of all
+//! 256 byte values packed into nibbles (a length of 0 means the value doesn't occur),
+//! and then the codes themselves, most significant bit first, padded to a whole byte.
+
+use alloc::collections::BinaryHeap;
+use alloc::{vec, vec::Vec};
+use core::cmp::Reverse;
+
+use crate::consts::QOI_ENTROPY_HEAD_SIZE;
+use crate::error::{Error, Result};
+use crate::utils::unlikely;
+
+/// Maximum length of a code in bits, also the size of the decoding lookup in bits.
+const MAX_CODE_LEN: u32 = 12;
+
+/// Computes Huffman code lengths for the given byte frequencies, limited to
+/// [`MAX_CODE_LEN`] bits by flattening the frequencies until the tree is shallow enough.
+#[allow(clippy::cast_possible_truncation)]
+fn code_lengths(freqs: &[u64; 256]) -> [u8; 256] {
+    let mut freqs = *freqs;
+    loop {
+        let mut heap: BinaryHeap<_> =
+            (0..256).filter(|&i| freqs[i] != 0).map(|i| Reverse((freqs[i], i))).collect();
+        let mut lengths = [0_u8; 256];
+        if heap.len() == 1 {
+            lengths[heap.pop().map_or(0, |Reverse((_, i))| i)] = 1;
+            return lengths;
+        }
+        // nodes 0..256 are the leaves, internal nodes are appended as they are created
+        let mut parent = vec![usize::MAX; 256];
+        while let (Some(Reverse((f1, n1))), Some(Reverse((f2, n2)))) = (heap.pop(), heap.pop()) {
+            let node = parent.len();
+            parent.push(usize::MAX);
+            (parent[n1], parent[n2]) = (node, node);
+            heap.push(Reverse((f1 + f2, node)));
+        }
+        // parents are created after their children, so depths can be filled top-down
+        let mut depth = vec![0_u32; parent.len()];
+        for node in (0..parent.len()).rev() {
+            if parent[node] != usize::MAX {
+                depth[node] = depth[parent[node]] + 1;
+            }
+        }
+        if depth[..256].iter().all(|&d| d <= MAX_CODE_LEN) {
+            for (len, &d) in lengths.iter_mut().zip(&depth) {
+                *len = d as u8;
+            }
+            return lengths;
+        }
+        for f in freqs.iter_mut().filter(|f| **f != 0) {
+            *f = (*f + 1) / 2;
+        }
+    }
+}
+
+/// Assigns canonical codes to the given code lengths.
+fn canonical_codes(lengths: &[u8; 256]) -> [u16; 256] {
+    let mut count = [0_u16; MAX_CODE_LEN as usize + 1];
+    for &len in lengths.iter().filter(|&&len| len != 0) {
+        count[len as usize] += 1;
+    }
+    let mut next = [0_u16; MAX_CODE_LEN as usize + 1];
+    for len in 1..=MAX_CODE_LEN as usize {
+        next[len] = (next[len - 1] + count[len - 1]) << 1;
+    }
+    let mut codes = [0_u16; 256];
+    for (code, &len) in codes.iter_mut().zip(lengths).filter(|(_, &len)| len != 0) {
+        *code = next[len as usize];
+        next[len as usize] += 1;
+    }
+    codes
+}
+
+/// Entropy-codes the op stream; the output is at most [`QOI_ENTROPY_HEAD_SIZE`] bytes
+/// longer than the input.
+#[allow(clippy::cast_possible_truncation)]
+pub fn encode(ops: &[u8]) -> Vec<u8> {
+    let mut freqs = [0_u64; 256];
+    for &b in ops {
+        freqs[b as usize] += 1;
+    }
+    let mut lengths = if ops.is_empty() { [8; 256] } else { code_lengths(&freqs) };
+    let n_bits: u64 = freqs.iter().zip(&lengths).map(|(&f, &len)| f * u64::from(len)).sum();
+    if n_bits > ops.len() as u64 * 8 {
+        // never worse than storing the bytes as they are
+        lengths = [8; 256];
+    }
+    let codes = canonical_codes(&lengths);
+
+    let mut out = Vec::with_capacity(QOI_ENTROPY_HEAD_SIZE + ops.len());
+    out.extend_from_slice(&(ops.len() as u32).to_be_bytes());
+    out.extend(lengths.chunks_exact(2).map(|pair| pair[0] << 4 | pair[1]));
+    let (mut acc, mut n_acc) = (0_u32, 0_u32);
+    for &b in ops {
+        acc = acc << lengths[b as usize] | u32::from(codes[b as usize]);
+        n_acc += u32::from(lengths[b as usize]);
+        while n_acc >= 8 {
+            n_acc -= 8;
+            out.push((acc >> n_acc) as u8);
+        }
+    }
+    if n_acc != 0 {
+        out.push((acc << (8 - n_acc)) as u8);
+    }
+    out
+}
+
+/// Decodes an entropy-coded op stream, pulling the coded bytes one at a time so that
+/// nothing past its end is consumed; fails if it holds more than `max_len` op bytes, or
+/// more than `max_alloc` bytes can be allocated for them.
+#[allow(clippy::cast_possible_truncation)]
+pub fn decode(
+    mut next_byte: impl FnMut() -> Result<u8>, max_len: usize, max_alloc: usize,
+) -> Result<Vec<u8>> {
+    let mut head = [0_u8; QOI_ENTROPY_HEAD_SIZE];
+    for b in &mut head {
+        *b = next_byte()?;
+    }
+    let n_ops = u32::from_be_bytes([head[0], head[1], head[2], head[3]]) as usize;
+    if unlikely(n_ops > max_alloc) {
+        return Err(Error::LimitsExceeded);
+    }
+    if unlikely(n_ops > max_len) {
+        return Err(Error::InvalidEntropyCoding);
+    }
+    let mut lengths = [0_u8; 256];
+    for (pair, &b) in lengths.chunks_exact_mut(2).zip(&head[4..]) {
+        (pair[0], pair[1]) = (b >> 4, b & 0xf);
+    }
+    if unlikely(lengths.iter().any(|&len| u32::from(len) > MAX_CODE_LEN)) {
+        return Err(Error::InvalidEntropyCoding);
+    }
+    let kraft: u32 = lengths
+        .iter()
+        .filter(|&&len| len != 0)
+        .map(|&len| 1 << (MAX_CODE_LEN - u32::from(len)))
+        .sum();
+    if unlikely(kraft > 1 << MAX_CODE_LEN) {
+        return Err(Error::InvalidEntropyCoding);
+    }
+
+    // each entry holds the decoded byte and the code length (0 for unassigned codes)
+    let mut lookup = vec![0_u16; 1 << MAX_CODE_LEN];
+    let codes = canonical_codes(&lengths);
+    for (b, (&code, &len)) in
+        codes.iter().zip(&lengths).enumerate().filter(|(_, (_, &len))| len != 0)
+    {
+        let shift = MAX_CODE_LEN - u32::from(len);
+        let start = (code as usize) << shift;
+        lookup[start..start + (1 << shift)].fill(u16::from(len) << 8 | b as u16);
+    }
+
+    // the count comes from the input, so the output grows as the codes are decoded instead
+    // of being reserved up front
+    let mut out = Vec::new();
+    let (mut acc, mut n_acc) = (0_u32, 0_u32);
+    while out.len() < n_ops {
+        // with fewer bits than the longest code available, the rest is zero-padded; the
+        // lookup is still correct if the resulting code turns out to be short enough
+        let peek = if n_acc >= MAX_CODE_LEN {
+            acc >> (n_acc - MAX_CODE_LEN)
+        } else {
+            acc << (MAX_CODE_LEN - n_acc)
+        };
+        let entry = lookup[(peek & ((1 << MAX_CODE_LEN) - 1)) as usize];
+        let len = u32::from(entry >> 8);
+        if len != 0 && len <= n_acc {
+            out.push(entry as u8);
+            n_acc -= len;
+            acc &= (1 << n_acc) - 1;
+        } else if unlikely(n_acc >= MAX_CODE_LEN) {
+            return Err(Error::InvalidEntropyCoding);
+        } else {
+            acc = acc << 8 | u32::from(next_byte()?);
+            n_acc += 8;
+        }
+    }
+    Ok(out)
+}
@@ -12,6 +12,8 @@ pub enum Error {
     InvalidChannels { channels: u8 },
     /// Invalid color space: expected 0 or 1
     InvalidColorSpace { colorspace: u8 },
+    /// Invalid predictor: expected 0 to 4
+    InvalidPredictor { predictor: u8 },
     /// Invalid image dimensions: can't be empty or larger than 400Mp
     InvalidImageDimensions { width: u32, height: u32 },
     /// Image dimensions are inconsistent with image buffer length
@@ -22,6 +24,38 @@ pub enum Error {
     UnexpectedBufferEnd,
     /// Invalid stream end marker encountered when decoding
     InvalidPadding,
+    /// Image dimensions or required allocation exceed the decoding limits
+    LimitsExceeded,
+    /// Unknown feature flags or inconsistent values in the header extension
+    InvalidHeaderExtension,
+    /// Requested region is empty or extends past the image bounds
+    InvalidRegion { x: u32, y: u32, width: u32, height: u32 },
+    /// Tiled images can't be decoded in row order, e.g. row by row or pixel by pixel
+    UnsupportedTiling,
+    /// Entropy-coded op stream is malformed
+    InvalidEntropyCoding,
+    /// Entropy-coded images can't be decoded pixel by pixel, push-based, or without `alloc`
+    UnsupportedEntropyCoding,
+    /// Images with vertical prediction or a predictor other than the previous pixel can't
+    /// be decoded pixel by pixel, push-based, or without `alloc`
+    UnsupportedVerticalPrediction,
+    /// Images with a palette can't be decoded pixel by pixel, push-based, or without `alloc`
+    UnsupportedPalette,
+    /// An op refers to a color past the end of the palette
+    InvalidPalette { index: u8, len: usize },
+    /// The image was encoded starting from a different dictionary than the decoder's
+    DictionaryMismatch { expected: u32, found: u32 },
+    /// The checksum of the decoded pixels doesn't match the one stored after the padding
+    ChecksumMismatch { expected: u32, found: u32 },
+    /// Invalid metadata: truncated chunks, chunks over 4 GiB or invalid text keys
+    InvalidMetadata,
+    /// Invalid archive: bad magic, unknown flags, invalid names, too much data, or an image
+    /// with more pixels than its data can encode
+    InvalidArchive,
+    /// No image with the given name or position in the archive
+    MissingArchiveEntry,
+    /// An image with the given name is already in the archive
+    DuplicateArchiveEntry,
     #[cfg(feature = "std")]
     /// Generic I/O error from the wrapped reader/writer
     IoError(std::io::Error),
@@ -42,6 +76,9 @@ impl Display for Error {
             Self::InvalidColorSpace { colorspace } => {
                 write!(f, "invalid color space: {} (expected 0 or 1)", colorspace)
             }
+            Self::InvalidPredictor { predictor } => {
+                write!(f, "invalid predictor: {predictor} (expected 0 to 4)")
+            }
             Self::InvalidImageDimensions { width, height } => {
                 write!(f, "invalid image dimensions: {}x{}", width, height)
             }
@@ -57,6 +94,51 @@ impl Display for Error {
             Self::InvalidPadding => {
                 write!(f, "invalid padding (stream end marker mismatch)")
             }
+            Self::LimitsExceeded => {
+                write!(f, "image exceeds decoding limits")
+            }
+            Self::InvalidHeaderExtension => {
+                write!(f, "invalid header extension")
+            }
+            Self::InvalidRegion { x, y, width, height } => {
+                write!(f, "invalid region: {width}x{height} at ({x}, {y})")
+            }
+            Self::UnsupportedTiling => {
+                write!(f, "tiled images can only be decoded as a whole or by region")
+            }
+            Self::InvalidEntropyCoding => {
+                write!(f, "invalid entropy-coded data")
+            }
+            Self::UnsupportedEntropyCoding => {
+                write!(f, "entropy-coded images require decoding from memory or a reader")
+            }
+            Self::UnsupportedVerticalPrediction => {
+                write!(f, "images predicted from the row above require decoding whole rows")
+            }
+            Self::UnsupportedPalette => {
+                write!(f, "images with a palette require decoding whole slices or tiles")
+            }
+            Self::InvalidPalette { index, len } => {
+                write!(f, "invalid palette index: {index} (palette has {len} colors)")
+            }
+            Self::DictionaryMismatch { expected, found } => {
+                write!(f, "dictionary mismatch: expected {expected:#010x}, found {found:#010x}")
+            }
+            Self::ChecksumMismatch { expected, found } => {
+                write!(f, "checksum mismatch: expected {expected:#010x}, found {found:#010x}")
+            }
+            Self::InvalidMetadata => {
+                write!(f, "invalid metadata chunk")
+            }
+            Self::InvalidArchive => {
+                write!(f, "invalid archive")
+            }
+            Self::MissingArchiveEntry => {
+                write!(f, "no such image in the archive")
+            }
+            Self::DuplicateArchiveEntry => {
+                write!(f, "an image with the same name is already in the archive")
+            }
             #[cfg(feature = "std")]
             Self::IoError(ref err) => {
                 write!(f, "i/o error: {}", err)
@@ -80,3 +162,13 @@ impl From<std::io::Error> for Error {
         Self::IoError(err)
     }
 }
+
+#[cfg(feature = "std")]
+impl From<Error> for std::io::Error {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::IoError(err) => err,
+            err => Self::new(std::io::ErrorKind::InvalidData, err),
+        }
+    }
+}
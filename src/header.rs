@@ -1,11 +1,16 @@
-use core::convert::TryInto;
+use core::convert::{TryFrom, TryInto};
 
 use bytemuck::cast_slice;
 
-use crate::consts::{QOI_HEADER_SIZE, QOI_MAGIC, QOI_PIXELS_MAX};
+use crate::consts::{
+    QOI_CHECKSUM_SIZE, QOI_ENTROPY_HEAD_SIZE, QOI_EXT_CHECKSUM, QOI_EXT_COLOR_TRANSFORM,
+    QOI_EXT_DICTIONARY, QOI_EXT_ENTROPY, QOI_EXT_FLAG, QOI_EXT_METADATA, QOI_EXT_PALETTE,
+    QOI_EXT_PREDICTOR, QOI_EXT_SIZE, QOI_EXT_SLICES, QOI_EXT_TILES, QOI_EXT_VERTICAL,
+    QOI_HEADER_MAX_SIZE, QOI_HEADER_SIZE, QOI_MAGIC, QOI_PALETTE_MAX_LEN, QOI_PIXELS_MAX,
+};
 use crate::encode_max_len;
 use crate::error::{Error, Result};
-use crate::types::{Channels, ColorSpace};
+use crate::types::{Channels, ColorSpace, Predictor};
 use crate::utils::unlikely;
 
 /// Image header: dimensions, channels, color space.
@@ -14,7 +19,15 @@ use crate::utils::unlikely;
 /// A valid image header must satisfy the following conditions:
 /// * Both width and height must be non-zero.
 /// * Maximum number of pixels is 400Mp (=4e8 pixels).
+///
+/// Optional features are stored in a header extension right after the standard
+/// 14-byte header, announced by the high bit of the color space byte. More of them may
+/// be added, so headers are created with [`Header::try_new`] and the `with_*` builders
+/// (or by the encoder, for the palette, dictionary and metadata), and the extensions are
+/// read back with the getters of the same name, which keeps them consistent.
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+#[allow(clippy::struct_excessive_bools)]
 pub struct Header {
     /// Image width in pixels
     pub width: u32,
@@ -24,6 +37,33 @@ pub struct Header {
     pub channels: Channels,
     /// Color space (informative field, doesn't affect encoding)
     pub colorspace: ColorSpace,
+    /// Height of independently decodable slices in rows, 0 if the image isn't sliced
+    pub(crate) slice_height: u32,
+    /// Width of independently decodable tiles in pixels, 0 if the image isn't tiled
+    pub(crate) tile_width: u32,
+    /// Height of independently decodable tiles in pixels, 0 if the image isn't tiled
+    pub(crate) tile_height: u32,
+    /// Whether the op stream is entropy-coded, see [`Header::with_entropy_coding`]
+    pub(crate) entropy_coded: bool,
+    /// Whether pixels are stored in YCoCg-R, see [`Header::with_color_transform`]
+    pub(crate) color_transform: bool,
+    /// Whether ops referring to the pixel above are used, see
+    /// [`Header::with_vertical_prediction`]
+    pub(crate) vertical_prediction: bool,
+    /// Prediction that `QOI_OP_DIFF` and `QOI_OP_LUMA` are relative to, see
+    /// [`Header::with_predictor`]
+    pub(crate) predictor: Predictor,
+    /// Number of colors in the palette stored after the header, 0 if there's none; see
+    /// [`Encoder::with_palette`](crate::Encoder::with_palette)
+    pub(crate) palette_len: u32,
+    /// Identifier of the dictionary that encoding started from, 0 if there's none; see
+    /// [`State::train`](crate::State::train)
+    pub(crate) dictionary_id: u32,
+    /// Whether a checksum of the pixels follows the padding, see [`Header::with_checksum`]
+    pub(crate) checksum: bool,
+    /// Size in bytes of the metadata chunks stored after the header, 0 if there are none;
+    /// see [`Metadata`](crate::Metadata)
+    pub(crate) metadata_len: u32,
 }
 
 impl Default for Header {
@@ -34,6 +74,17 @@ impl Default for Header {
             height: 1,
             channels: Channels::default(),
             colorspace: ColorSpace::default(),
+            slice_height: 0,
+            tile_width: 0,
+            tile_height: 0,
+            entropy_coded: false,
+            color_transform: false,
+            vertical_prediction: false,
+            predictor: Predictor::Left,
+            palette_len: 0,
+            dictionary_id: 0,
+            checksum: false,
+            metadata_len: 0,
         }
     }
 }
@@ -48,7 +99,23 @@ impl Header {
         if unlikely(n_pixels == 0 || n_pixels > QOI_PIXELS_MAX) {
             return Err(Error::InvalidImageDimensions { width, height });
         }
-        Ok(Self { width, height, channels, colorspace })
+        Ok(Self {
+            width,
+            height,
+            channels,
+            colorspace,
+            slice_height: 0,
+            tile_width: 0,
+            tile_height: 0,
+            entropy_coded: false,
+            color_transform: false,
+            vertical_prediction: false,
+            predictor: Predictor::Left,
+            palette_len: 0,
+            dictionary_id: 0,
+            checksum: false,
+            metadata_len: 0,
+        })
     }
 
     /// Creates a new header with modified channels.
@@ -65,23 +132,403 @@ impl Header {
         self
     }
 
-    /// Serializes the header into a bytes array.
+    /// Creates a new header with modified slice height.
+    ///
+    /// Slices are horizontal bands of `slice_height` rows (the last one may be shorter)
+    /// that are encoded independently of each other and can be decoded in parallel.
+    /// Setting it to 0 disables slicing. Slicing replaces tiling.
+    #[inline]
+    pub const fn with_slice_height(mut self, slice_height: u32) -> Self {
+        self.slice_height = slice_height;
+        (self.tile_width, self.tile_height) = (0, 0);
+        self
+    }
+
+    /// Creates a new header with modified tile size.
+    ///
+    /// Tiles are rectangles of `tile_width` by `tile_height` pixels (the ones at the right
+    /// and bottom edges may be smaller) that are encoded independently of each other, so
+    /// that any region of the image can be decoded without decoding the rest of it. Setting
+    /// either dimension to 0 disables tiling. Tiling replaces slicing.
+    #[inline]
+    pub const fn with_tile_size(mut self, tile_width: u32, tile_height: u32) -> Self {
+        if tile_width == 0 || tile_height == 0 {
+            (self.tile_width, self.tile_height) = (0, 0);
+        } else {
+            (self.tile_width, self.tile_height) = (tile_width, tile_height);
+            self.slice_height = 0;
+        }
+        self
+    }
+
+    /// Creates a new header with entropy coding of the op stream enabled or disabled.
+    ///
+    /// When enabled, the ops are additionally compressed with a static Huffman code. This
+    /// makes the image noticeably smaller, but slower to encode and decode, and requires
+    /// the `alloc` feature on both sides. Offsets of slices and tiles refer to the decoded
+    /// op stream.
+    #[inline]
+    pub const fn with_entropy_coding(mut self, entropy_coded: bool) -> Self {
+        self.entropy_coded = entropy_coded;
+        self
+    }
+
+    /// Creates a new header with the reversible YCoCg-R color transform enabled or disabled.
+    ///
+    /// When enabled, the encoder converts each pixel to (Co, Y, Cg) before choosing an op,
+    /// and the decoder converts it back; the alpha channel is left as is. This decorrelates
+    /// the color channels, which may help with photographic images.
+    #[inline]
+    pub const fn with_color_transform(mut self, color_transform: bool) -> Self {
+        self.color_transform = color_transform;
+        self
+    }
+
+    /// Creates a new header with vertical prediction enabled or disabled.
+    ///
+    /// When enabled, part of the run ops is replaced by ops that repeat the pixels above or
+    /// store a small difference to the pixel above, which helps with images that have
+    /// vertical structure. Both sides have to keep the previous row of each slice or tile,
+    /// so this requires the `alloc` feature and decoding whole rows at a time.
+    #[inline]
+    pub const fn with_vertical_prediction(mut self, vertical_prediction: bool) -> Self {
+        self.vertical_prediction = vertical_prediction;
+        self
+    }
+
+    /// Creates a new header with a modified predictor.
+    ///
+    /// Small differences stored by `QOI_OP_DIFF` and `QOI_OP_LUMA` are then relative to
+    /// the prediction from the neighbouring pixels rather than to the previous pixel, which
+    /// may help with smooth gradients. Any predictor but [`Predictor::Left`] (the default)
+    /// needs the previous row of each slice or tile, just like vertical prediction.
+    #[inline]
+    pub const fn with_predictor(mut self, predictor: Predictor) -> Self {
+        self.predictor = predictor;
+        self
+    }
+
+    /// Creates a new header with the pixel checksum enabled or disabled.
+    ///
+    /// When enabled, a CRC-32 of the pixels (with the channels given in the header, row by
+    /// row) is stored after the padding, and the decoder returns
+    /// [`Error::ChecksumMismatch`] if the decoded pixels don't match it. This catches
+    /// corruption inside the op stream, which the padding alone can't. The checksum can't
+    /// be verified when decoding a region or dropping the alpha channel of an RGBA image.
+    #[inline]
+    pub const fn with_checksum(mut self, checksum: bool) -> Self {
+        self.checksum = checksum;
+        self
+    }
+
+    /// Returns the height of slices in rows, 0 if the image isn't sliced.
+    #[inline]
+    pub const fn slice_height(&self) -> u32 {
+        self.slice_height
+    }
+
+    /// Returns the width of tiles in pixels, 0 if the image isn't tiled.
+    #[inline]
+    pub const fn tile_width(&self) -> u32 {
+        self.tile_width
+    }
+
+    /// Returns the height of tiles in pixels, 0 if the image isn't tiled.
+    #[inline]
+    pub const fn tile_height(&self) -> u32 {
+        self.tile_height
+    }
+
+    /// Returns true if the op stream is entropy-coded.
+    #[inline]
+    pub const fn entropy_coded(&self) -> bool {
+        self.entropy_coded
+    }
+
+    /// Returns true if pixels are stored in YCoCg-R.
+    #[inline]
+    pub const fn color_transform(&self) -> bool {
+        self.color_transform
+    }
+
+    /// Returns true if ops referring to the pixel above are used.
+    #[inline]
+    pub const fn vertical_prediction(&self) -> bool {
+        self.vertical_prediction
+    }
+
+    /// Returns the prediction that `QOI_OP_DIFF` and `QOI_OP_LUMA` are relative to.
+    #[inline]
+    pub const fn predictor(&self) -> Predictor {
+        self.predictor
+    }
+
+    /// Returns the number of colors in the palette, 0 if there's none.
+    #[inline]
+    pub const fn palette_len(&self) -> u32 {
+        self.palette_len
+    }
+
+    /// Returns the identifier of the dictionary that encoding started from, 0 if there's
+    /// none.
+    #[inline]
+    pub const fn dictionary_id(&self) -> u32 {
+        self.dictionary_id
+    }
+
+    /// Returns true if a checksum of the pixels follows the padding.
+    #[inline]
+    pub const fn checksum(&self) -> bool {
+        self.checksum
+    }
+
+    /// Returns the size in bytes of the metadata chunks, 0 if there are none.
+    #[inline]
+    pub const fn metadata_len(&self) -> u32 {
+        self.metadata_len
+    }
+
+    /// Returns true if the image has a palette.
+    #[inline]
+    pub const fn has_palette(&self) -> bool {
+        self.palette_len != 0
+    }
+
+    /// Returns true if the image can only be encoded and decoded a whole slice or tile at
+    /// a time: decoding refers to the previous row, or to the palette.
+    #[inline]
+    pub(crate) const fn is_block_coded(&self) -> bool {
+        self.vertical_prediction || !self.predictor.is_left() || self.has_palette()
+    }
+
+    /// Returns the error for decoding a block-coded image pixel by pixel or push-based.
     #[inline]
-    pub(crate) fn encode(&self) -> [u8; QOI_HEADER_SIZE] {
-        let mut out = [0; QOI_HEADER_SIZE];
+    pub(crate) const fn block_coded_error(&self) -> Error {
+        if self.vertical_prediction || !self.predictor.is_left() {
+            Error::UnsupportedVerticalPrediction
+        } else {
+            Error::UnsupportedPalette
+        }
+    }
+
+    /// Returns true if the image is split into independently decodable slices.
+    #[inline]
+    pub const fn is_sliced(&self) -> bool {
+        self.slice_height != 0
+    }
+
+    /// Returns true if the image is split into independently decodable tiles.
+    #[inline]
+    pub const fn is_tiled(&self) -> bool {
+        self.tile_width != 0 && self.tile_height != 0
+    }
+
+    /// Returns the number of slices in the image (1 if it isn't sliced).
+    #[inline]
+    pub const fn n_slices(&self) -> usize {
+        if self.is_sliced() {
+            ((self.height - 1) / self.slice_height) as usize + 1
+        } else {
+            1
+        }
+    }
+
+    /// Returns the number of pixels in a single slice (the last one may be smaller).
+    #[inline]
+    pub const fn slice_len(&self) -> usize {
+        if self.is_sliced() && self.slice_height < self.height {
+            (self.width as usize).saturating_mul(self.slice_height as usize)
+        } else {
+            self.n_pixels()
+        }
+    }
+
+    /// Returns the number of tiles in the image (1 if it isn't tiled).
+    #[inline]
+    pub const fn n_tiles(&self) -> usize {
+        if self.is_tiled() {
+            self.n_blocks()
+        } else {
+            1
+        }
+    }
+
+    /// Returns the size of the blocks that are encoded independently: tiles, slices
+    /// (tiles spanning the full width), or the whole image.
+    #[inline]
+    pub(crate) const fn block_size(&self) -> (usize, usize) {
+        let (width, height) = (self.width as usize, self.height as usize);
+        let (w, h) = if self.is_tiled() {
+            (self.tile_width as usize, self.tile_height as usize)
+        } else if self.is_sliced() {
+            (width, self.slice_height as usize)
+        } else {
+            (width, height)
+        };
+        (if w < width { w } else { width }, if h < height { h } else { height })
+    }
+
+    /// Returns the number of independently encoded blocks, see [`Header::block_size`].
+    #[inline]
+    pub(crate) const fn n_blocks(&self) -> usize {
+        let (w, h) = self.block_size();
+        ((self.width as usize - 1) / w + 1) * ((self.height as usize - 1) / h + 1)
+    }
+
+    /// Returns the position and size of the `i`-th block as `(x, y, width, height)`.
+    ///
+    /// Blocks are stored in row-major order, and so are the pixels within a block.
+    #[inline]
+    #[allow(clippy::many_single_char_names)]
+    pub(crate) const fn block_rect(&self, i: usize) -> (usize, usize, usize, usize) {
+        let (width, height) = (self.width as usize, self.height as usize);
+        let (w, h) = self.block_size();
+        let n_cols = (width - 1) / w + 1;
+        let (x, y) = ((i % n_cols) * w, (i / n_cols) * h);
+        let (w, h) = (
+            if x + w < width { w } else { width - x },
+            if y + h < height { h } else { height - y },
+        );
+        (x, y, w, h)
+    }
+
+    /// Returns the index of the first pixel of the `i`-th block in encoding order.
+    #[inline]
+    pub(crate) const fn block_start(&self, i: usize) -> usize {
+        // all rows above the block's band come first, then the blocks to its left
+        let (x, y, _, h) = self.block_rect(i);
+        y * self.width as usize + x * h
+    }
+
+    /// Returns the index of the block containing the `pos`-th pixel in encoding order.
+    #[inline]
+    pub(crate) const fn block_at(&self, pos: usize) -> usize {
+        let (width, height) = (self.width as usize, self.height as usize);
+        let (w, h) = self.block_size();
+        let n_cols = (width - 1) / w + 1;
+        let row = pos / (h * width);
+        let band_height = if (row + 1) * h < height { h } else { height - row * h };
+        row * n_cols + (pos - row * h * width) / (w * band_height)
+    }
+
+    /// Returns the feature flags stored in the header extension.
+    #[inline]
+    const fn ext_flags(&self) -> u32 {
+        let layout = if self.is_tiled() {
+            QOI_EXT_TILES
+        } else if self.is_sliced() {
+            QOI_EXT_SLICES
+        } else {
+            0
+        };
+        layout
+            | if self.entropy_coded { QOI_EXT_ENTROPY } else { 0 }
+            | if self.color_transform { QOI_EXT_COLOR_TRANSFORM } else { 0 }
+            | if self.vertical_prediction { QOI_EXT_VERTICAL } else { 0 }
+            | if self.predictor.is_left() { 0 } else { QOI_EXT_PREDICTOR }
+            | if self.has_palette() { QOI_EXT_PALETTE } else { 0 }
+            | if self.dictionary_id == 0 { 0 } else { QOI_EXT_DICTIONARY }
+            | if self.checksum { QOI_EXT_CHECKSUM } else { 0 }
+            | if self.metadata_len == 0 { 0 } else { QOI_EXT_METADATA }
+    }
+
+    /// Returns the size of the serialized header, excluding the metadata, the palette and
+    /// the block table.
+    #[inline]
+    pub(crate) const fn fixed_len(&self) -> usize {
+        match self.ext_flags() {
+            0 => QOI_HEADER_SIZE,
+            flags => QOI_HEADER_SIZE + QOI_EXT_SIZE + ext_payload_len(flags),
+        }
+    }
+
+    /// Returns the size of the palette following the header and the metadata, 0 if there's
+    /// none.
+    #[inline]
+    pub(crate) const fn palette_size(&self) -> usize {
+        self.palette_len as usize * 4
+    }
+
+    /// Returns the size of the table of slice or tile offsets following the header,
+    /// the metadata and the palette, 0 if there's none.
+    #[inline]
+    pub(crate) const fn table_len(&self) -> usize {
+        if self.is_sliced() || self.is_tiled() {
+            self.n_blocks() * 4
+        } else {
+            0
+        }
+    }
+
+    /// Returns the total size of the header as stored in the encoded image.
+    #[inline]
+    pub const fn encoded_len(&self) -> usize {
+        self.fixed_len() + self.metadata_len as usize + self.palette_size() + self.table_len()
+    }
+
+    /// Serializes the header (excluding the block table) into a bytes array, and
+    /// returns it along with the number of bytes used.
+    #[inline]
+    pub(crate) fn encode(&self) -> ([u8; QOI_HEADER_MAX_SIZE], usize) {
+        let mut out = [0; QOI_HEADER_MAX_SIZE];
         out[..4].copy_from_slice(&QOI_MAGIC.to_be_bytes());
         out[4..8].copy_from_slice(&self.width.to_be_bytes());
         out[8..12].copy_from_slice(&self.height.to_be_bytes());
         out[12] = self.channels.into();
         out[13] = self.colorspace.into();
-        out
+        let flags = self.ext_flags();
+        if flags != 0 {
+            out[13] |= QOI_EXT_FLAG;
+            out[14..18].copy_from_slice(&flags.to_be_bytes());
+        }
+        let mut pos = QOI_HEADER_SIZE + QOI_EXT_SIZE;
+        if flags & QOI_EXT_SLICES != 0 {
+            out[pos..pos + 4].copy_from_slice(&self.slice_height.to_be_bytes());
+            pos += 4;
+        }
+        if flags & QOI_EXT_TILES != 0 {
+            out[pos..pos + 4].copy_from_slice(&self.tile_width.to_be_bytes());
+            out[pos + 4..pos + 8].copy_from_slice(&self.tile_height.to_be_bytes());
+            pos += 8;
+        }
+        if flags & QOI_EXT_PREDICTOR != 0 {
+            out[pos..pos + 4].copy_from_slice(&u32::from(self.predictor.as_u8()).to_be_bytes());
+            pos += 4;
+        }
+        if flags & QOI_EXT_PALETTE != 0 {
+            out[pos..pos + 4].copy_from_slice(&self.palette_len.to_be_bytes());
+            pos += 4;
+        }
+        if flags & QOI_EXT_DICTIONARY != 0 {
+            out[pos..pos + 4].copy_from_slice(&self.dictionary_id.to_be_bytes());
+            pos += 4;
+        }
+        if flags & QOI_EXT_METADATA != 0 {
+            out[pos..pos + 4].copy_from_slice(&self.metadata_len.to_be_bytes());
+        }
+        (out, self.fixed_len())
+    }
+
+    /// Returns the number of bytes required to decode the header, given its first bytes.
+    ///
+    /// Once at least this many bytes are available, the result no longer changes.
+    #[inline]
+    pub(crate) fn decode_len(data: &[u8]) -> usize {
+        if data.len() < QOI_HEADER_SIZE || data[13] & QOI_EXT_FLAG == 0 {
+            QOI_HEADER_SIZE
+        } else if data.len() < QOI_HEADER_SIZE + QOI_EXT_SIZE {
+            QOI_HEADER_SIZE + QOI_EXT_SIZE
+        } else {
+            let flags = u32::from_be_bytes([data[14], data[15], data[16], data[17]]);
+            QOI_HEADER_SIZE + QOI_EXT_SIZE + ext_payload_len(flags)
+        }
     }
 
-    /// Deserializes the header from a byte array.
+    /// Deserializes the header (excluding the block table) from a byte array.
     #[inline]
     pub(crate) fn decode(data: impl AsRef<[u8]>) -> Result<Self> {
         let data = data.as_ref();
-        if unlikely(data.len() < QOI_HEADER_SIZE) {
+        if unlikely(data.len() < Self::decode_len(data)) {
             return Err(Error::UnexpectedBufferEnd);
         }
         let v = cast_slice::<_, [u8; 4]>(&data[..12]);
@@ -89,11 +536,82 @@ impl Header {
         let width = u32::from_be_bytes(v[1]);
         let height = u32::from_be_bytes(v[2]);
         let channels = data[12].try_into()?;
-        let colorspace = data[13].try_into()?;
+        let colorspace = (data[13] & !QOI_EXT_FLAG).try_into()?;
         if unlikely(magic != QOI_MAGIC) {
             return Err(Error::InvalidMagic { magic });
         }
-        Self::try_new(width, height, channels, colorspace)
+        let mut header = Self::try_new(width, height, channels, colorspace)?;
+        if data[13] & QOI_EXT_FLAG != 0 {
+            let flags = u32::from_be_bytes([data[14], data[15], data[16], data[17]]);
+            let layout = QOI_EXT_SLICES | QOI_EXT_TILES;
+            let known = layout
+                | QOI_EXT_ENTROPY
+                | QOI_EXT_COLOR_TRANSFORM
+                | QOI_EXT_VERTICAL
+                | QOI_EXT_PREDICTOR
+                | QOI_EXT_PALETTE
+                | QOI_EXT_DICTIONARY
+                | QOI_EXT_CHECKSUM
+                | QOI_EXT_METADATA;
+            if unlikely(flags == 0 || flags & !known != 0 || (flags & layout).count_ones() > 1) {
+                return Err(Error::InvalidHeaderExtension);
+            }
+            let payload = &data[QOI_HEADER_SIZE + QOI_EXT_SIZE..];
+            let v = cast_slice::<_, [u8; 4]>(&payload[..ext_payload_len(flags)]);
+            if flags & QOI_EXT_SLICES != 0 {
+                header.slice_height = u32::from_be_bytes(v[0]);
+                if unlikely(header.slice_height == 0) {
+                    return Err(Error::InvalidHeaderExtension);
+                }
+            }
+            if flags & QOI_EXT_TILES != 0 {
+                header.tile_width = u32::from_be_bytes(v[0]);
+                header.tile_height = u32::from_be_bytes(v[1]);
+                if unlikely(!header.is_tiled()) {
+                    return Err(Error::InvalidHeaderExtension);
+                }
+            }
+            header.entropy_coded = flags & QOI_EXT_ENTROPY != 0;
+            header.color_transform = flags & QOI_EXT_COLOR_TRANSFORM != 0;
+            header.vertical_prediction = flags & QOI_EXT_VERTICAL != 0;
+            header.checksum = flags & QOI_EXT_CHECKSUM != 0;
+            if flags & QOI_EXT_PREDICTOR != 0 {
+                // the predictor comes after the layout payload
+                let predictor = u32::from_be_bytes(v[ext_payload_len(flags & layout) / 4]);
+                let predictor =
+                    u8::try_from(predictor).map_err(|_| Error::InvalidHeaderExtension)?;
+                header.predictor = predictor.try_into()?;
+                if unlikely(header.predictor.is_left()) {
+                    return Err(Error::InvalidHeaderExtension);
+                }
+            }
+            if flags & QOI_EXT_PALETTE != 0 {
+                // the number of colors comes last, the colors follow the header
+                let n = ext_payload_len(flags & (layout | QOI_EXT_PREDICTOR)) / 4;
+                header.palette_len = u32::from_be_bytes(v[n]);
+                if unlikely(
+                    header.palette_len == 0 || header.palette_len as usize > QOI_PALETTE_MAX_LEN,
+                ) {
+                    return Err(Error::InvalidHeaderExtension);
+                }
+            }
+            if flags & QOI_EXT_DICTIONARY != 0 {
+                let n = ext_payload_len(flags & !(QOI_EXT_DICTIONARY | QOI_EXT_METADATA)) / 4;
+                header.dictionary_id = u32::from_be_bytes(v[n]);
+                if unlikely(header.dictionary_id == 0) {
+                    return Err(Error::InvalidHeaderExtension);
+                }
+            }
+            if flags & QOI_EXT_METADATA != 0 {
+                // the length of the chunks comes last, the chunks follow the header
+                let n = ext_payload_len(flags & !QOI_EXT_METADATA) / 4;
+                header.metadata_len = u32::from_be_bytes(v[n]);
+                if unlikely(header.metadata_len == 0) {
+                    return Err(Error::InvalidHeaderExtension);
+                }
+            }
+        }
+        Ok(header)
     }
 
     /// Returns a number of pixels in the image.
@@ -115,6 +633,37 @@ impl Header {
     /// Can be used to pre-allocate the buffer to encode the image into.
     #[inline]
     pub fn encode_max_len<const DATA_ONLY: bool>(&self) -> usize {
+        let ext_len = if DATA_ONLY { 0 } else { self.encoded_len() - QOI_HEADER_SIZE };
+        let entropy_len = if self.entropy_coded { QOI_ENTROPY_HEAD_SIZE } else { 0 };
+        let checksum_len = if self.checksum && !DATA_ONLY { QOI_CHECKSUM_SIZE } else { 0 };
         encode_max_len::<DATA_ONLY>(self.width, self.height, self.channels)
+            + ext_len
+            + entropy_len
+            + checksum_len
+    }
+}
+
+/// Returns the size of the header extension payload for the given feature flags.
+#[inline]
+const fn ext_payload_len(flags: u32) -> usize {
+    let mut len = 0;
+    if flags & QOI_EXT_SLICES != 0 {
+        len += 4;
+    }
+    if flags & QOI_EXT_TILES != 0 {
+        len += 8;
+    }
+    if flags & QOI_EXT_PREDICTOR != 0 {
+        len += 4;
+    }
+    if flags & QOI_EXT_PALETTE != 0 {
+        len += 4;
+    }
+    if flags & QOI_EXT_DICTIONARY != 0 {
+        len += 4;
+    }
+    if flags & QOI_EXT_METADATA != 0 {
+        len += 4;
     }
+    len
 }
@@ -71,10 +71,17 @@ extern crate alloc;
 #[cfg(any(feature = "std", test))]
 extern crate std as alloc;
 
+#[cfg(any(feature = "std", feature = "alloc"))]
+mod archive;
+mod crc;
 mod decode;
 mod encode;
+#[cfg(any(feature = "std", feature = "alloc"))]
+mod entropy;
 mod error;
 mod header;
+mod limits;
+mod metadata;
 mod pixel;
 mod state;
 mod types;
@@ -84,14 +91,28 @@ mod utils;
 pub mod consts;
 
 #[cfg(any(feature = "alloc", feature = "std"))]
-pub use crate::decode::decode_to_vec;
-pub use crate::decode::{decode_header, decode_to_buf, Decoder};
+pub use crate::archive::{Archive, ArchiveEntry, ArchiveWriter};
+#[cfg(feature = "std")]
+pub use crate::decode::DecoderWriter;
+pub use crate::decode::{decode_header, decode_to_buf, Decoder, Pixels};
+#[cfg(any(feature = "alloc", feature = "std"))]
+pub use crate::decode::{decode_to_pixels, decode_to_vec};
 
+#[cfg(feature = "std")]
+pub use crate::encode::EncoderReader;
+pub use crate::encode::{
+    encode_iter_to_buf, encode_iter_to_buf_with, encode_max_len, encode_to_buf, encode_u32_to_buf,
+    EncodeStatus, Encoder,
+};
 #[cfg(any(feature = "alloc", feature = "std"))]
-pub use crate::encode::encode_to_vec;
-pub use crate::encode::{encode_max_len, encode_to_buf, Encoder};
+pub use crate::encode::{
+    encode_iter_to_vec, encode_iter_to_vec_with, encode_pixels_to_vec, encode_to_vec,
+    encode_u32_to_vec,
+};
 
 pub use crate::error::{Error, Result};
 pub use crate::header::Header;
+pub use crate::limits::Limits;
+pub use crate::metadata::{Chunk, ChunkType, Chunks, Metadata};
 pub use crate::state::State;
-pub use crate::types::{Channels, ColorSpace};
+pub use crate::types::{ByteOrder, Channels, ColorSpace, Predictor};
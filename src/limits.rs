@@ -0,0 +1,101 @@
+use crate::consts::QOI_PIXELS_MAX;
+use crate::error::{Error, Result};
+use crate::header::Header;
+use crate::types::Channels;
+use crate::utils::unlikely;
+
+/// Resource limits applied when decoding untrusted input.
+///
+/// All limits are checked against the image header before anything is decoded
+/// or allocated, so a hostile header can't make the decoder reserve gigabytes
+/// of memory.
+///
+/// ### Notes
+/// By default, the dimensions are only bounded by the format itself (400Mp),
+/// while allocations made by the decoder are capped at 512 MiB. Use
+/// [`Limits::none`] to lift the allocation limit as well; that's what the free
+/// functions like [`decode_to_vec`](crate::decode_to_vec) do. Either way, when
+/// decoding from a slice, the decoded image can't be larger than what the rest of
+/// the slice is able to encode.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Limits {
+    /// Maximum image width in pixels
+    pub max_width: u32,
+    /// Maximum image height in pixels
+    pub max_height: u32,
+    /// Maximum number of pixels in the image
+    pub max_pixels: usize,
+    /// Maximum number of bytes the decoder may allocate
+    pub max_alloc: usize,
+}
+
+impl Default for Limits {
+    #[inline]
+    fn default() -> Self {
+        Self::none().with_max_alloc(512 * 1024 * 1024)
+    }
+}
+
+impl Limits {
+    /// Creates limits that don't restrict anything beyond the format itself.
+    #[inline]
+    pub const fn none() -> Self {
+        Self {
+            max_width: u32::MAX,
+            max_height: u32::MAX,
+            max_pixels: QOI_PIXELS_MAX,
+            max_alloc: usize::MAX,
+        }
+    }
+
+    /// Returns new limits with modified maximum width.
+    #[inline]
+    pub const fn with_max_width(mut self, max_width: u32) -> Self {
+        self.max_width = max_width;
+        self
+    }
+
+    /// Returns new limits with modified maximum height.
+    #[inline]
+    pub const fn with_max_height(mut self, max_height: u32) -> Self {
+        self.max_height = max_height;
+        self
+    }
+
+    /// Returns new limits with modified maximum number of pixels.
+    #[inline]
+    pub const fn with_max_pixels(mut self, max_pixels: usize) -> Self {
+        self.max_pixels = max_pixels;
+        self
+    }
+
+    /// Returns new limits with modified maximum allocation size in bytes.
+    #[inline]
+    pub const fn with_max_alloc(mut self, max_alloc: usize) -> Self {
+        self.max_alloc = max_alloc;
+        self
+    }
+
+    /// Checks image dimensions from the header against the limits.
+    #[inline]
+    pub const fn check_header(&self, header: &Header) -> Result<()> {
+        if unlikely(
+            header.width > self.max_width
+                || header.height > self.max_height
+                || header.n_pixels() > self.max_pixels,
+        ) {
+            return Err(Error::LimitsExceeded);
+        }
+        Ok(())
+    }
+
+    /// Checks that decoding the image with the given number of channels into
+    /// a newly allocated buffer stays within the limits.
+    #[inline]
+    pub const fn check_alloc(&self, header: &Header, channels: Channels) -> Result<()> {
+        if unlikely(header.n_pixels().saturating_mul(channels.as_u8() as usize) > self.max_alloc) {
+            return Err(Error::LimitsExceeded);
+        }
+        self.check_header(header)
+    }
+}
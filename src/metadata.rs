@@ -0,0 +1,193 @@
+#[cfg(any(feature = "std", feature = "alloc"))]
+use alloc::vec::Vec;
+use core::str;
+
+use crate::consts::QOI_CHUNK_HEAD_SIZE;
+use crate::error::{Error, Result};
+use crate::utils::unlikely;
+
+/// Kind of a metadata chunk, stored as four bytes in front of it.
+///
+/// Decoders skip chunks of kinds they don't know about, so applications are free to
+/// define their own.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ChunkType(pub [u8; 4]);
+
+impl ChunkType {
+    /// ICC color profile, as embedded in PNG or JPEG files
+    pub const ICC: Self = Self(*b"iCCP");
+    /// EXIF data, starting with the TIFF header like in PNG files
+    pub const EXIF: Self = Self(*b"eXIf");
+    /// Key-value text: a UTF-8 key, a zero byte and a UTF-8 value
+    pub const TEXT: Self = Self(*b"tEXt");
+}
+
+/// A single metadata chunk, see [`Metadata`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Chunk<'a> {
+    /// Kind of the chunk
+    pub chunk_type: ChunkType,
+    /// Contents of the chunk
+    pub data: &'a [u8],
+}
+
+impl<'a> Chunk<'a> {
+    /// Returns the key and value of a text chunk, or `None` for other kinds of chunks
+    /// and malformed text.
+    #[inline]
+    pub fn text(&self) -> Option<(&'a str, &'a str)> {
+        if self.chunk_type != ChunkType::TEXT {
+            return None;
+        }
+        let sep = self.data.iter().position(|&b| b == 0)?;
+        let key = str::from_utf8(&self.data[..sep]).ok()?;
+        let value = str::from_utf8(&self.data[sep + 1..]).ok()?;
+        Some((key, value))
+    }
+}
+
+/// Iterator over the chunks of [`Metadata`], in the order they were added.
+#[derive(Clone, Debug)]
+pub struct Chunks<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Iterator for Chunks<'a> {
+    type Item = Chunk<'a>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Chunk<'a>> {
+        // the chunks have been validated when the metadata was decoded or built
+        if self.data.is_empty() {
+            return None;
+        }
+        let (head, rest) = self.data.split_at(QOI_CHUNK_HEAD_SIZE);
+        let chunk_type = ChunkType([head[0], head[1], head[2], head[3]]);
+        let len = u32::from_be_bytes([head[4], head[5], head[6], head[7]]) as usize;
+        let (data, rest) = rest.split_at(len);
+        self.data = rest;
+        Some(Chunk { chunk_type, data })
+    }
+}
+
+/// Metadata chunks stored after the header: ICC profiles, EXIF data, key-value text
+/// or chunks of any other kind.
+///
+/// Each chunk is stored as its [`ChunkType`], its length (u32) and its contents, and
+/// the whole section is announced by [`Header::metadata_len`](crate::Header::metadata_len),
+/// so decoders that don't care about metadata skip it at once. Chunks are attached via
+/// [`Encoder::with_chunk`](crate::Encoder::with_chunk) and friends, and read back via
+/// [`Decoder::metadata`](crate::Decoder::metadata).
+///
+/// Without the `alloc` feature, metadata can't be built and is skipped when decoding.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Metadata {
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    data: Vec<u8>,
+}
+
+impl Metadata {
+    /// Creates empty metadata.
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            #[cfg(any(feature = "std", feature = "alloc"))]
+            data: Vec::new(),
+        }
+    }
+
+    /// Validates a serialized chunk section and keeps a copy of it if possible.
+    #[inline]
+    pub(crate) fn decode(data: &[u8]) -> Result<Self> {
+        let mut rest = data;
+        while !rest.is_empty() {
+            if unlikely(rest.len() < QOI_CHUNK_HEAD_SIZE) {
+                return Err(Error::InvalidMetadata);
+            }
+            let len = u32::from_be_bytes([rest[4], rest[5], rest[6], rest[7]]) as usize;
+            if unlikely(rest.len() - QOI_CHUNK_HEAD_SIZE < len) {
+                return Err(Error::InvalidMetadata);
+            }
+            rest = &rest[QOI_CHUNK_HEAD_SIZE + len..];
+        }
+        Ok(Self {
+            #[cfg(any(feature = "std", feature = "alloc"))]
+            data: data.into(),
+        })
+    }
+
+    /// Returns the serialized chunk section as stored after the header.
+    #[inline]
+    pub(crate) fn as_bytes(&self) -> &[u8] {
+        #[cfg(any(feature = "std", feature = "alloc"))]
+        return &self.data;
+        #[cfg(not(any(feature = "std", feature = "alloc")))]
+        return &[];
+    }
+
+    /// Appends a chunk of the given kind.
+    ///
+    /// Fails with [`Error::InvalidMetadata`] if the chunks would take more than 4 GiB.
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn push(&mut self, chunk_type: ChunkType, data: &[u8]) -> Result<()> {
+        let len = self.data.len().saturating_add(QOI_CHUNK_HEAD_SIZE + data.len());
+        if unlikely(u32::try_from(len).is_err()) {
+            return Err(Error::InvalidMetadata);
+        }
+        self.data.extend(chunk_type.0);
+        self.data.extend((data.len() as u32).to_be_bytes());
+        self.data.extend(data);
+        Ok(())
+    }
+
+    /// Appends a key-value text chunk; the key must be non-empty and must not contain
+    /// zero bytes.
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    pub fn push_text(&mut self, key: &str, value: &str) -> Result<()> {
+        if unlikely(key.is_empty() || key.contains('\0')) {
+            return Err(Error::InvalidMetadata);
+        }
+        let mut data = Vec::with_capacity(key.len() + 1 + value.len());
+        data.extend(key.as_bytes());
+        data.push(0);
+        data.extend(value.as_bytes());
+        self.push(ChunkType::TEXT, &data)
+    }
+
+    /// Returns true if there are no chunks.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.as_bytes().is_empty()
+    }
+
+    /// Returns an iterator over all chunks, in the order they were added.
+    #[inline]
+    pub fn chunks(&self) -> Chunks<'_> {
+        Chunks { data: self.as_bytes() }
+    }
+
+    /// Returns the contents of the first chunk of the given kind.
+    #[inline]
+    pub fn get(&self, chunk_type: ChunkType) -> Option<&[u8]> {
+        self.chunks().find(|chunk| chunk.chunk_type == chunk_type).map(|chunk| chunk.data)
+    }
+
+    /// Returns the ICC color profile, if any.
+    #[inline]
+    pub fn icc_profile(&self) -> Option<&[u8]> {
+        self.get(ChunkType::ICC)
+    }
+
+    /// Returns the EXIF data, if any.
+    #[inline]
+    pub fn exif(&self) -> Option<&[u8]> {
+        self.get(ChunkType::EXIF)
+    }
+
+    /// Returns the value of the first text chunk with the given key.
+    #[inline]
+    pub fn text(&self, key: &str) -> Option<&str> {
+        self.chunks().filter_map(|chunk| chunk.text()).find(|&(k, _)| k == key).map(|(_, v)| v)
+    }
+}
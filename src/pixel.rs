@@ -1,6 +1,8 @@
 use crate::consts::{QOI_OP_DIFF, QOI_OP_LUMA, QOI_OP_RGB, QOI_OP_RGBA};
 use bytemuck::{cast, Pod};
 
+use crate::types::Predictor;
+
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
 #[repr(transparent)]
 pub struct Pixel<const N: usize>([u8; N]);
@@ -70,6 +72,15 @@ impl<const N: usize> Pixel<N> {
         self.0[2] = self.0[2].wrapping_add(vb);
     }
 
+    /// Applies the second byte of `QOI_OP_UP_DIFF`, the pixel being the one above.
+    #[inline]
+    pub fn update_up_diff(&mut self, b2: u8) {
+        let vg = (b2 >> 4).wrapping_sub(8);
+        self.0[0] = self.0[0].wrapping_add(vg).wrapping_add((b2 >> 2) & 0x03).wrapping_sub(2);
+        self.0[1] = self.0[1].wrapping_add(vg);
+        self.0[2] = self.0[2].wrapping_add(vg).wrapping_add(b2 & 0x03).wrapping_sub(2);
+    }
+
     #[inline]
     pub const fn as_rgba(self, with_a: u8) -> Pixel<4> {
         let mut i = 0;
@@ -170,6 +181,128 @@ impl<const N: usize> Pixel<N> {
     }
 }
 
+/// Halves a channel difference stored modulo 256, rounding towards negative infinity.
+#[inline]
+#[allow(clippy::cast_possible_wrap, clippy::cast_sign_loss)]
+const fn half(v: u8) -> u8 {
+    ((v as i8) >> 1) as u8
+}
+
+/// PNG's Paeth predictor for a single channel.
+#[inline]
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let (a16, b16, c16) = (i16::from(a), i16::from(b), i16::from(c));
+    let (pa, pb, pc) = ((b16 - c16).abs(), (a16 - c16).abs(), (a16 + b16 - 2 * c16).abs());
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/// The median edge detector of LOCO-I for a single channel.
+#[inline]
+const fn med(a: u8, b: u8, c: u8) -> u8 {
+    let (lo, hi) = if a < b { (a, b) } else { (b, a) };
+    if c >= hi {
+        lo
+    } else if c <= lo {
+        hi
+    } else {
+        a.wrapping_add(b).wrapping_sub(c)
+    }
+}
+
+impl Pixel<4> {
+    /// Returns the pixel that `QOI_OP_DIFF` and `QOI_OP_LUMA` are relative to, this being
+    /// the previous pixel: the color channels are predicted from the neighbours to the left,
+    /// above and above left, and the alpha is kept.
+    #[inline]
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn predict(self, predictor: Predictor, left: Self, up: Self, up_left: Self) -> Self {
+        let f: fn(u8, u8, u8) -> u8 = match predictor {
+            Predictor::Left => return self,
+            Predictor::Up => |_, b, _| b,
+            Predictor::Average => |a, b, _| ((u16::from(a) + u16::from(b)) / 2) as u8,
+            Predictor::Paeth => paeth,
+            Predictor::Med => med,
+        };
+        let (a, b, c) = (left.0, up.0, up_left.0);
+        Self([f(a[0], b[0], c[0]), f(a[1], b[1], c[1]), f(a[2], b[2], c[2]), self.0[3]])
+    }
+
+    /// Converts RGB to YCoCg-R, computed with the usual lifting steps but modulo 256 so
+    /// that it fits into bytes. The chroma is stored as (Y + Co, Y, Y + Cg), so that the
+    /// residuals of `QOI_OP_LUMA` relative to the middle channel are those of Co and Cg.
+    #[inline]
+    #[allow(clippy::many_single_char_names)]
+    pub const fn rgb_to_ycocg(self) -> Self {
+        let [r, g, b, a] = self.0;
+        let co = r.wrapping_sub(b);
+        let t = b.wrapping_add(half(co));
+        let cg = g.wrapping_sub(t);
+        let y = t.wrapping_add(half(cg));
+        Self([y.wrapping_add(co), y, y.wrapping_add(cg), a])
+    }
+
+    /// Inverse of [`Pixel::rgb_to_ycocg`].
+    #[inline]
+    pub const fn ycocg_to_rgb(self) -> Self {
+        let [y_co, y, y_cg, a] = self.0;
+        let (co, cg) = (y_co.wrapping_sub(y), y_cg.wrapping_sub(y));
+        let t = y.wrapping_sub(half(cg));
+        let b = t.wrapping_sub(half(co));
+        Self([b.wrapping_add(co), cg.wrapping_add(t), b, a])
+    }
+
+    /// Returns the second byte of `QOI_OP_UP_DIFF` if the pixel is close enough to the one
+    /// above: the same alpha, green within -8..7 and red and blue within -2..1 of green.
+    #[inline]
+    pub const fn encode_up_diff(self, px_up: Self) -> Option<u8> {
+        let vg = self.0[1].wrapping_sub(px_up.0[1]);
+        let vg_8 = vg.wrapping_add(8);
+        let vg_r = self.0[0].wrapping_sub(px_up.0[0]).wrapping_sub(vg).wrapping_add(2);
+        let vg_b = self.0[2].wrapping_sub(px_up.0[2]).wrapping_sub(vg).wrapping_add(2);
+        if self.0[3] == px_up.0[3] && vg_8 | 15 == 15 && vg_r | vg_b | 3 == 3 {
+            Some(vg_8 << 4 | vg_r << 2 | vg_b)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the largest absolute difference between the channels of two pixels.
+    #[inline]
+    pub fn max_diff(self, other: Self) -> u8 {
+        self.0.iter().zip(&other.0).map(|(a, b)| a.abs_diff(*b)).max().unwrap_or(0)
+    }
+
+    /// Looks for a pixel within `max_error` of this one that can be encoded relative to
+    /// the previous pixel as `QOI_OP_DIFF` or `QOI_OP_LUMA`, and returns it with the op.
+    #[inline]
+    #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap, clippy::cast_sign_loss)]
+    pub fn encode_near(self, px_prev: Self, max_error: u8) -> Option<(Self, usize, [u8; 2])> {
+        let delta = |i: usize| i16::from(self.0[i].wrapping_sub(px_prev.0[i]) as i8);
+        let (vr, vg, vb) = (delta(0), delta(1), delta(2));
+        let near = |vr: i16, vg: i16, vb: i16| {
+            let mut px = px_prev;
+            px.rgb_add(vr as u8, vg as u8, vb as u8);
+            Some(px).filter(|px| px.max_diff(self) <= max_error)
+        };
+        let (dr, dg, db) = (vr.clamp(-2, 1), vg.clamp(-2, 1), vb.clamp(-2, 1));
+        if let Some(px) = near(dr, dg, db) {
+            let op = QOI_OP_DIFF | ((dr + 2) << 4 | (dg + 2) << 2 | (db + 2)) as u8;
+            return Some((px, 1, [op, 0]));
+        }
+        let dg = vg.clamp(-32, 31);
+        let (dg_r, dg_b) = ((vr - dg).clamp(-7, 7), (vb - dg).clamp(-7, 7));
+        near(dg + dg_r, dg, dg + dg_b).map(|px| {
+            (px, 2, [QOI_OP_LUMA | (dg + 32) as u8, ((dg_r + 7) << 4 | (dg_b + 7)) as u8])
+        })
+    }
+}
+
 impl<const N: usize, const NN: usize> From<Pixel<N>> for [u8; NN] {
     #[inline(always)]
     fn from(px: Pixel<N>) -> Self {
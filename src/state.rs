@@ -1,10 +1,40 @@
+#[cfg(any(feature = "std", feature = "alloc"))]
+use alloc::{boxed::Box, vec::Vec};
+
+use crate::consts::QOI_PALETTE_MAX_LEN;
+use crate::error::{Error, Result};
+use crate::header::Header;
 use crate::pixel::Pixel;
+#[cfg(any(feature = "std", feature = "alloc"))]
+use crate::types::Channels;
+#[cfg(any(feature = "std", feature = "alloc"))]
+use crate::utils::unlikely;
 
 #[derive(Debug, Clone)]
 pub struct State {
     index_l1: [Pixel<4>; 0x40],
     index_l2: [Pixel<4>; 0x400],
+    dictionary_id: u32,
+    // the dictionary and the palette are kept out of line, so that states which use
+    // neither (and every state without `alloc`) stay as small as the caches
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    base: Option<Box<Base>>,
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    palette: Vec<Pixel<4>>,
+}
+
+/// The caches a dictionary starts each slice or tile from.
+#[derive(Debug, Clone, Copy)]
+struct Base {
+    index_l1: [Pixel<4>; 0x40],
+    index_l2: [Pixel<4>; 0x400],
+}
+impl Default for Base {
+    fn default() -> Self {
+        Self { index_l1: [Pixel::new(); 0x40], index_l2: [Pixel::new(); 0x400] }
+    }
 }
+
 impl State {
     pub(crate) fn index_l1(&mut self, hash_index: u16) -> &mut Pixel<4> {
         &mut self.index_l1[hash_index as usize & 0x3f]
@@ -12,9 +42,171 @@ impl State {
     pub(crate) fn index_l2(&mut self, hash_index: u16) -> &mut Pixel<4> {
         &mut self.index_l2[hash_index as usize & 0x03ff]
     }
+
+    /// Trains a dictionary on sample images with the given number of channels, so that
+    /// many small images with a common look don't each have to spell out their colors.
+    ///
+    /// The colors that most often start a new op in the samples are placed into the index,
+    /// the most frequent one of each slot into the first level and the next one into the
+    /// second level. Encoders and decoders started from the dictionary (see
+    /// [`Encoder::with_dictionary`](crate::Encoder::with_dictionary) and
+    /// [`Decoder::with_dictionary`](crate::Decoder::with_dictionary)) then find these
+    /// colors in the index from the first pixel on, and every slice or tile restarts from
+    /// it; the header of each image records the [`State::dictionary_id`] it requires.
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    pub fn train<S: AsRef<[u8]>>(samples: impl IntoIterator<Item = S>, channels: Channels) -> Self {
+        let n_channels = channels.as_u8() as usize;
+        let mut colors = Vec::new();
+        for sample in samples {
+            let mut px_prev = Pixel::<4>::new().with_a(0xff);
+            for chunk in sample.as_ref().chunks_exact(n_channels) {
+                let mut px = Pixel::<4>::new().with_a(0xff);
+                px.read(chunk);
+                if px != px_prev {
+                    colors.push(<[u8; 4]>::from(px));
+                }
+                px_prev = px;
+            }
+        }
+        colors.sort_unstable();
+        let mut counts: Vec<(usize, [u8; 4])> = Vec::new();
+        for color in colors {
+            match counts.last_mut() {
+                Some((count, last)) if *last == color => *count += 1,
+                _ => counts.push((1, color)),
+            }
+        }
+        counts.sort_unstable_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+
+        let mut base = Base::default();
+        let (mut used_l1, mut used_l2) = ([false; 0x40], [false; 0x400]);
+        for (_, color) in counts {
+            let mut px = Pixel::<4>::new();
+            px.read(&color);
+            let hash_index = px.hash_index() as usize;
+            if !used_l1[hash_index & 0x3f] {
+                used_l1[hash_index & 0x3f] = true;
+                base.index_l1[hash_index & 0x3f] = px;
+            } else if !used_l2[hash_index & 0x3ff] {
+                used_l2[hash_index & 0x3ff] = true;
+                base.index_l2[hash_index & 0x3ff] = px;
+            }
+        }
+        let mut state = Self { dictionary_id: base.hash(), ..Self::default() };
+        state.base = Some(Box::new(base));
+        state.restart();
+        state
+    }
+
+    /// Returns the identifier of the dictionary the state started from, 0 if there's none.
+    pub const fn dictionary_id(&self) -> u32 {
+        self.dictionary_id
+    }
+
+    /// Returns an error unless the state started from the dictionary the image requires.
+    pub(crate) const fn check_dictionary(&self, header: &Header) -> Result<()> {
+        if header.dictionary_id == self.dictionary_id {
+            Ok(())
+        } else {
+            Err(Error::DictionaryMismatch {
+                expected: header.dictionary_id,
+                found: self.dictionary_id,
+            })
+        }
+    }
+
+    /// Sets the palette of the image (empty if there's none) and preloads it into the
+    /// caches, each color into the slots of both levels given by its hash, so that later
+    /// colors win. Without `alloc`, images with a palette can't be decoded, so the palette
+    /// isn't kept for later slices or tiles.
+    pub(crate) fn set_palette(&mut self, palette: &[Pixel<4>]) {
+        let palette = &palette[..palette.len().min(QOI_PALETTE_MAX_LEN)];
+        #[cfg(any(feature = "std", feature = "alloc"))]
+        {
+            self.palette.clear();
+            self.palette.extend_from_slice(palette);
+        }
+        for &px in palette {
+            self.preload(px);
+        }
+    }
+
+    fn preload(&mut self, px: Pixel<4>) {
+        *self.index_l1(px.hash_index()) = px;
+        *self.index_l2(px.hash_index()) = px;
+    }
+
+    /// Restarts the caches for the next slice or tile in place: from the ones of the
+    /// dictionary (empty if there's none), with the same palette preloaded.
+    pub(crate) fn restart(&mut self) {
+        #[cfg(any(feature = "std", feature = "alloc"))]
+        let base = self.base.as_deref().copied().unwrap_or_default();
+        #[cfg(not(any(feature = "std", feature = "alloc")))]
+        let base = Base::default();
+        (self.index_l1, self.index_l2) = (base.index_l1, base.index_l2);
+        #[cfg(any(feature = "std", feature = "alloc"))]
+        for i in 0..self.palette.len() {
+            self.preload(self.palette[i]);
+        }
+    }
+
+    /// Returns the state to start the next slice or tile from, see [`State::restart`].
+    pub(crate) fn fresh(&self) -> Self {
+        let mut state = self.clone();
+        state.restart();
+        state
+    }
+
+    /// Returns the state to start an image from the given dictionary with, keeping the
+    /// palette of this one.
+    pub(crate) fn with_dictionary(&self, dictionary: &Self) -> Self {
+        let mut state = self.clone();
+        #[cfg(any(feature = "std", feature = "alloc"))]
+        state.base.clone_from(&dictionary.base);
+        state.dictionary_id = dictionary.dictionary_id;
+        state.restart();
+        state
+    }
+
+    /// Returns the palette color with the given index, failing for the ones past the end
+    /// of the palette.
+    #[cfg(any(feature = "alloc", feature = "std"))]
+    #[inline]
+    pub(crate) fn palette_px(&self, index: u8) -> Result<Pixel<4>> {
+        if unlikely(index as usize >= self.palette.len()) {
+            return Err(Error::InvalidPalette { index, len: self.palette.len() });
+        }
+        Ok(self.palette[index as usize])
+    }
+
+    /// Returns the index of a color in the palette, which has to be sorted.
+    #[cfg(any(feature = "alloc", feature = "std"))]
+    #[allow(clippy::cast_possible_truncation)]
+    pub(crate) fn palette_index(&self, px: Pixel<4>) -> Option<u8> {
+        let key = <[u8; 4]>::from(px);
+        self.palette.binary_search_by_key(&key, |&px| px.into()).ok().map(|i| i as u8)
+    }
 }
 impl Default for State {
     fn default() -> Self {
-        Self { index_l1: [Pixel::new(); 0x40], index_l2: [Pixel::new(); 0x400] }
+        Self {
+            index_l1: [Pixel::new(); 0x40],
+            index_l2: [Pixel::new(); 0x400],
+            dictionary_id: 0,
+            #[cfg(any(feature = "std", feature = "alloc"))]
+            base: None,
+            #[cfg(any(feature = "std", feature = "alloc"))]
+            palette: Vec::new(),
+        }
+    }
+}
+impl Base {
+    /// Returns a nonzero FNV-1a hash of the caches.
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    fn hash(&self) -> u32 {
+        let pixels = self.index_l1.iter().chain(&self.index_l2);
+        let bytes = pixels.flat_map(|&px| <[u8; 4]>::from(px));
+        let hash = bytes.fold(0x811c_9dc5_u32, |h, b| (h ^ u32::from(b)).wrapping_mul(0x0100_0193));
+        hash.max(1)
     }
 }
@@ -111,3 +111,100 @@ impl TryFrom<u8> for Channels {
         }
     }
 }
+
+/// Prediction of a pixel from its neighbours, which `QOI_OP_DIFF` and `QOI_OP_LUMA`
+/// store the difference to.
+///
+/// Each of the color channels is predicted on its own from the pixels to the left (`a`),
+/// above (`b`) and above left (`c`); the alpha channel is always that of the previous pixel.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum Predictor {
+    /// The previous pixel, as in plain QOI
+    Left = 0,
+    /// The pixel above, `b`
+    Up = 1,
+    /// The average of the pixels to the left and above, `(a + b) / 2`
+    Average = 2,
+    /// PNG's Paeth predictor, whichever of `a`, `b` and `c` is closest to `a + b - c`
+    Paeth = 3,
+    /// The median edge detector of LOCO-I and JPEG-LS, the median of `a`, `b` and `a + b - c`
+    Med = 4,
+}
+
+impl Predictor {
+    /// Returns true if the previous pixel is the prediction, as in plain QOI.
+    pub const fn is_left(self) -> bool {
+        matches!(self, Self::Left)
+    }
+
+    /// Converts to an integer (0 for the previous pixel).
+    pub const fn as_u8(self) -> u8 {
+        self as u8
+    }
+}
+
+impl Default for Predictor {
+    fn default() -> Self {
+        Self::Left
+    }
+}
+
+impl From<Predictor> for u8 {
+    #[inline]
+    fn from(predictor: Predictor) -> Self {
+        predictor as Self
+    }
+}
+
+impl TryFrom<u8> for Predictor {
+    type Error = Error;
+
+    #[inline]
+    fn try_from(predictor: u8) -> Result<Self> {
+        Ok(match predictor {
+            0 => Self::Left,
+            1 => Self::Up,
+            2 => Self::Average,
+            3 => Self::Paeth,
+            4 => Self::Med,
+            _ => return Err(Error::InvalidPredictor { predictor }),
+        })
+    }
+}
+
+/// Order in which the channels of an RGBA pixel are packed into a `u32`.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, PartialOrd, Ord)]
+pub enum ByteOrder {
+    /// Red in the most significant byte, `0xRRGGBBAA`
+    BigEndian,
+    /// Red in the least significant byte, `0xAABBGGRR`
+    LittleEndian,
+}
+
+impl ByteOrder {
+    /// Byte order matching the in-memory layout of RGBA pixels on the target platform.
+    #[cfg(target_endian = "big")]
+    pub const NATIVE: Self = Self::BigEndian;
+    /// Byte order matching the in-memory layout of RGBA pixels on the target platform.
+    #[cfg(target_endian = "little")]
+    pub const NATIVE: Self = Self::LittleEndian;
+
+    /// Packs an RGBA pixel into an integer.
+    #[inline]
+    pub const fn pack(self, px: [u8; 4]) -> u32 {
+        match self {
+            Self::BigEndian => u32::from_be_bytes(px),
+            Self::LittleEndian => u32::from_le_bytes(px),
+        }
+    }
+
+    /// Unpacks an integer into an RGBA pixel.
+    #[inline]
+    pub const fn unpack(self, px: u32) -> [u8; 4] {
+        match self {
+            Self::BigEndian => px.to_be_bytes(),
+            Self::LittleEndian => px.to_le_bytes(),
+        }
+    }
+}
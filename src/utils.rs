@@ -1,6 +1,7 @@
 #[cfg(feature = "std")]
 use std::io::Write;
 
+use crate::consts::QOI_HEADER_MAX_SIZE;
 use crate::error::Result;
 
 #[inline(always)]
@@ -28,6 +29,12 @@ pub trait Writer: Sized {
     fn write_one(self, v: u8) -> Result<Self>;
     fn write_many(self, v: &[u8]) -> Result<Self>;
     fn capacity(&self) -> usize;
+
+    /// Returns true if the writer can't accept any more bytes without spilling.
+    #[inline]
+    fn is_full(&self) -> bool {
+        false
+    }
 }
 
 pub struct BytesMut<'a>(&'a mut [u8]);
@@ -76,31 +83,161 @@ impl<'a> Writer for BytesMut<'a> {
     }
 }
 
+/// Bytes that didn't fit into the output buffer, to be flushed on the next call.
+///
+/// The largest single write is the image header, so that's what it can hold.
+#[derive(Copy, Clone, Debug)]
+pub struct Spill {
+    data: [u8; QOI_HEADER_MAX_SIZE],
+    start: usize,
+    end: usize,
+}
+
+impl Default for Spill {
+    #[inline]
+    fn default() -> Self {
+        Self { data: [0; QOI_HEADER_MAX_SIZE], start: 0, end: 0 }
+    }
+}
+
+impl Spill {
+    #[inline]
+    pub const fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// Moves as many spilled bytes as possible into the buffer and returns their number.
+    #[inline]
+    pub fn drain_into(&mut self, buf: &mut [u8]) -> usize {
+        let n = (self.end - self.start).min(buf.len());
+        buf[..n].copy_from_slice(&self.data[self.start..self.start + n]);
+        self.start += n;
+        n
+    }
+
+    #[inline]
+    fn push(&mut self, v: &[u8]) {
+        if self.is_empty() {
+            self.start = 0;
+            self.end = 0;
+        }
+        self.data[self.end..self.end + v.len()].copy_from_slice(v);
+        self.end += v.len();
+    }
+}
+
+/// Writer into a limited buffer; bytes that don't fit are diverted into a [`Spill`].
+pub struct BytesSpill<'a> {
+    buf: BytesMut<'a>,
+    spill: &'a mut Spill,
+}
+
+impl<'a> BytesSpill<'a> {
+    pub fn new(buf: &'a mut [u8], spill: &'a mut Spill) -> Self {
+        Self { buf: BytesMut::new(buf), spill }
+    }
+}
+
+impl Writer for BytesSpill<'_> {
+    #[inline]
+    fn write_one(self, v: u8) -> Result<Self> {
+        self.write_many(&[v])
+    }
+
+    #[inline]
+    fn write_many(mut self, v: &[u8]) -> Result<Self> {
+        let n = v.len().min(self.buf.0.len());
+        self.buf = self.buf.write_many(&v[..n]);
+        if unlikely(n < v.len()) {
+            self.spill.push(&v[n..]);
+        }
+        Ok(self)
+    }
+
+    #[inline]
+    fn capacity(&self) -> usize {
+        self.buf.0.len()
+    }
+
+    #[inline]
+    fn is_full(&self) -> bool {
+        self.buf.0.is_empty()
+    }
+}
+
+/// Writer that discards its output and only counts the bytes.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Counter(pub usize);
+
+impl Writer for Counter {
+    #[inline]
+    fn write_one(self, _: u8) -> Result<Self> {
+        Ok(Self(self.0 + 1))
+    }
+
+    #[inline]
+    fn write_many(self, v: &[u8]) -> Result<Self> {
+        Ok(Self(self.0 + v.len()))
+    }
+
+    #[inline]
+    fn capacity(&self) -> usize {
+        usize::MAX - self.0
+    }
+}
+
+/// Size of the staging buffer used by [`GenericWriter`].
+#[cfg(feature = "std")]
+const GENERIC_WRITER_BUF_SIZE: usize = 0x2000;
+
+/// Writer adapter that stages the output and flushes it to the wrapped writer in blocks.
 #[cfg(feature = "std")]
-pub struct GenericWriter<W> {
+pub struct GenericWriter<W: Write> {
     writer: W,
+    buf: Vec<u8>,
     n_written: usize,
 }
 
 #[cfg(feature = "std")]
 impl<W: Write> GenericWriter<W> {
-    pub const fn new(writer: W) -> Self {
-        Self { writer, n_written: 0 }
+    pub fn new(writer: W) -> Self {
+        Self { writer, buf: Vec::with_capacity(GENERIC_WRITER_BUF_SIZE), n_written: 0 }
+    }
+
+    #[inline]
+    fn flush_if_full(mut self) -> Result<Self> {
+        if unlikely(self.buf.len() >= GENERIC_WRITER_BUF_SIZE) {
+            self.writer.write_all(&self.buf)?;
+            self.buf.clear();
+        }
+        Ok(self)
+    }
+
+    /// Flushes the staged bytes and returns the total number of bytes written.
+    pub fn finish(mut self) -> Result<usize> {
+        self.writer.write_all(&self.buf)?;
+        self.writer.flush()?;
+        Ok(self.n_written)
     }
 }
 
 #[cfg(feature = "std")]
 impl<W: Write> Writer for GenericWriter<W> {
+    #[inline]
     fn write_one(mut self, v: u8) -> Result<Self> {
         self.n_written += 1;
-        self.writer.write_all(&[v]).map(|_| self).map_err(Into::into)
+        self.buf.push(v);
+        self.flush_if_full()
     }
 
+    #[inline]
     fn write_many(mut self, v: &[u8]) -> Result<Self> {
         self.n_written += v.len();
-        self.writer.write_all(v).map(|_| self).map_err(Into::into)
+        self.buf.extend_from_slice(v);
+        self.flush_if_full()
     }
 
+    #[inline]
     fn capacity(&self) -> usize {
         usize::MAX - self.n_written
     }
@@ -1,3 +1,11 @@
+#![allow(unused_imports)]
+
+use std::io::{BufReader, Write};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use qoi::{decode_to_vec, Decoder, DecoderWriter, Header, Predictor};
+
 #[allow(unused)]
 pub fn hash<const N: usize>(px: [u8; N]) -> u8 {
     let r = px[0];
@@ -10,3 +18,133 @@ pub fn hash<const N: usize>(px: [u8; N]) -> u8 {
     let am = a.wrapping_mul(11);
     rm.wrapping_add(gm).wrapping_add(bm).wrapping_add(am) % 64
 }
+
+#[allow(unused)]
+pub fn read_png(path: &str) -> (Vec<u8>, u32, u32) {
+    let decoder = png::Decoder::new(std::fs::File::open(path).unwrap());
+    let mut reader = decoder.read_info().unwrap();
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf).unwrap();
+    buf.truncate(info.buffer_size());
+    (buf, info.width, info.height)
+}
+
+/// Smooth gradients with a few flat areas and sparse noise, so that all kinds of ops show
+/// up and predicting from the pixels above pays off.
+#[allow(unused)]
+pub fn gen_image(width: usize, height: usize, channels: usize, seed: u64) -> Vec<u8> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut img = Vec::with_capacity(width * height * channels);
+    for y in 0..height {
+        for x in 0..width {
+            let px = if (x / 16 + y / 16) % 5 == 0 {
+                [0x40, 0x80, 0xc0, 0xff]
+            } else {
+                let noise = if rng.gen_ratio(1, 20) { rng.gen_range(0..4) } else { 0 };
+                let v = (x * 7 % 251) as u8;
+                let (g, b) = (v.wrapping_add((y / 8) as u8), (x / 13 * 40) as u8 + noise);
+                [v, g, b, 0xff - (x / 20) as u8]
+            };
+            img.extend(&px[..channels]);
+        }
+    }
+    img
+}
+
+/// Patches in a few of `n_colors` random colors, with the other ones scattered around.
+#[allow(unused)]
+pub fn gen_indexed(
+    width: usize, height: usize, channels: usize, n_colors: usize, seed: u64,
+) -> Vec<u8> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let colors: Vec<[u8; 4]> = (0..n_colors).map(|_| rng.gen()).collect();
+    let mut img = Vec::with_capacity(width * height * channels);
+    for y in 0..height {
+        for x in 0..width {
+            let i = if rng.gen_ratio(1, 6) {
+                rng.gen_range(0..n_colors)
+            } else {
+                (x / 17 + y / 11) % n_colors.min(8)
+            };
+            img.extend(&colors[i][..channels]);
+        }
+    }
+    img
+}
+
+/// Runs of random colors and small or large steps between them, some runs long enough to
+/// cross the chunks of any reader or writer.
+#[allow(unused)]
+pub fn gen_runs(rng: &mut impl Rng, n_pixels: usize, channels: usize) -> Vec<u8> {
+    let mut px = vec![0_u8; channels];
+    let mut out = Vec::with_capacity(n_pixels * channels);
+    while out.len() < n_pixels * channels {
+        match rng.gen_range(0..4) {
+            0 => px.iter_mut().for_each(|c| *c = rng.gen()),
+            1 => px.iter_mut().for_each(|c| *c = c.wrapping_add(rng.gen_range(0..3))),
+            2 => px.iter_mut().for_each(|c| *c = c.wrapping_add(rng.gen_range(0..20))),
+            _ => {
+                for _ in 0..rng.gen_range(1..1500) {
+                    out.extend(&px);
+                }
+            }
+        }
+        out.extend(&px);
+    }
+    out.truncate(n_pixels * channels);
+    out
+}
+
+/// Boxes in colors picked from a shared theme, like icons or sprites with a common look.
+#[allow(unused)]
+pub fn gen_boxes(
+    rng: &mut impl Rng, theme: &[[u8; 4]], width: usize, height: usize, channels: usize,
+    n_boxes: usize,
+) -> Vec<u8> {
+    let mut img = vec![0; width * height * channels];
+    for _ in 0..n_boxes {
+        let color = theme[rng.gen_range(0..theme.len())];
+        let (x0, y0) = (rng.gen_range(0..width), rng.gen_range(0..height));
+        let (w, h) = (rng.gen_range(1..=width - x0), rng.gen_range(1..=height - y0));
+        for y in y0..y0 + h {
+            for x in x0..x0 + w {
+                let pos = (y * width + x) * channels;
+                img[pos..pos + channels].copy_from_slice(&color[..channels]);
+            }
+        }
+    }
+    img
+}
+
+/// Checks that every decoder which supports the image decodes it to `expected`: the
+/// decoders for slices and for readers (with ops split across its chunks), row by row
+/// unless it's tiled, and push-based unless it's entropy or block coded. Returns the header.
+#[allow(unused)]
+pub fn decode_all(encoded: &[u8], expected: &[u8]) -> Header {
+    let (header, decoded) = decode_to_vec::<false>(encoded).unwrap();
+    assert_eq!(decoded, expected);
+    let decoded = Decoder::new(encoded).unwrap().decode_to_vec::<false>().unwrap();
+    assert_eq!(decoded, expected);
+    let reader = BufReader::with_capacity(3, encoded);
+    let decoded = Decoder::from_buf_read(reader).unwrap().decode_to_vec::<false>().unwrap();
+    assert_eq!(decoded, expected);
+    if !header.is_tiled() {
+        let mut rows: Vec<u8> = vec![];
+        let mut decoder = Decoder::new(encoded).unwrap();
+        decoder.decode_rows::<false>(|_, row| rows.extend(row)).unwrap();
+        assert_eq!(rows, expected);
+    }
+    let block_coded = header.vertical_prediction()
+        || header.predictor() != Predictor::Left
+        || header.has_palette();
+    if !header.is_tiled() && !header.entropy_coded() && !block_coded {
+        let mut out = vec![0; expected.len()];
+        let mut writer = DecoderWriter::new(&mut out);
+        for chunk in encoded.chunks(7) {
+            writer.write_all(chunk).unwrap();
+        }
+        assert_eq!(writer.finish().unwrap(), header);
+        assert_eq!(out, expected);
+    }
+    header
+}
@@ -0,0 +1,120 @@
+mod common;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use qoi::{Archive, ArchiveWriter, Channels, Error, Limits};
+
+use self::common::gen_boxes;
+
+/// Sprites of varying sizes drawn as boxes in colors picked from a shared theme.
+fn gen_sprites(n: usize, seed: u64) -> Vec<(Vec<u8>, u32, u32)> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let theme: Vec<[u8; 4]> = (0..40).map(|_| rng.gen()).collect();
+    (0..n)
+        .map(|i| {
+            let (width, height) = (rng.gen_range(4..24), rng.gen_range(4..24));
+            let img = gen_boxes(&mut rng, &theme, width, height, 3 + i % 2, 8);
+            (img, width as u32, height as u32)
+        })
+        .collect()
+}
+
+#[test]
+fn test_archive_roundtrip() {
+    let sprites = gen_sprites(60, 0);
+    let mut sizes = vec![];
+    for chained in [false, true] {
+        let mut writer = ArchiveWriter::new(chained);
+        for (i, (img, width, height)) in sprites.iter().enumerate() {
+            writer.append(&format!("sprite_{}.png", i), img, *width, *height).unwrap();
+        }
+        let bytes = writer.finish();
+        sizes.push(bytes.len());
+
+        let archive = Archive::new(&bytes).unwrap();
+        assert_eq!(archive.is_chained(), chained);
+        assert_eq!(archive.entries(), writer.entries());
+        assert_eq!(archive.entries().len(), sprites.len());
+        for (i, (img, width, height)) in sprites.iter().enumerate().rev() {
+            let entry = &archive.entries()[i];
+            assert_eq!((entry.header.width, entry.header.height), (*width, *height));
+            assert_eq!(entry.header.channels, Channels::try_from(3 + i as u8 % 2).unwrap());
+            assert_eq!(&archive.extract(&format!("sprite_{}.png", i)).unwrap(), img);
+        }
+    }
+    // sprites sharing colors are cheaper when they share the index
+    assert!(sizes[1] < sizes[0]);
+}
+
+#[test]
+fn test_archive_append() {
+    let sprites = gen_sprites(20, 1);
+    for chained in [false, true] {
+        let mut writer = ArchiveWriter::new(chained);
+        for (i, (img, width, height)) in sprites.iter().enumerate() {
+            writer.append(&i.to_string(), img, *width, *height).unwrap();
+        }
+        let expected = writer.finish();
+
+        let mut writer = ArchiveWriter::new(chained);
+        for (i, (img, width, height)) in sprites[..12].iter().enumerate() {
+            writer.append(&i.to_string(), img, *width, *height).unwrap();
+        }
+        let bytes = writer.finish();
+        let mut writer = ArchiveWriter::from_archive(&Archive::new(&bytes).unwrap()).unwrap();
+        for (i, (img, width, height)) in sprites.iter().enumerate().skip(12) {
+            writer.append(&i.to_string(), img, *width, *height).unwrap();
+        }
+        assert_eq!(writer.finish(), expected);
+
+        let (img, width, height) = &sprites[0];
+        assert!(matches!(
+            writer.append("3", img, *width, *height),
+            Err(Error::DuplicateArchiveEntry)
+        ));
+        assert!(matches!(
+            writer.append("x", &img[1..], *width, *height),
+            Err(Error::InvalidImageLength { .. })
+        ));
+        assert_eq!(writer.entries().len(), 20);
+    }
+}
+
+#[test]
+fn test_archive_invalid() {
+    let sprites = gen_sprites(3, 2);
+    let mut writer = ArchiveWriter::new(false);
+    for (i, (img, width, height)) in sprites.iter().enumerate() {
+        writer.append(&i.to_string(), img, *width, *height).unwrap();
+    }
+    let bytes = writer.finish();
+    let archive = Archive::new(&bytes).unwrap();
+    assert!(matches!(archive.extract("3"), Err(Error::MissingArchiveEntry)));
+    assert!(matches!(archive.extract_at(3), Err(Error::MissingArchiveEntry)));
+
+    let mut bad_magic = bytes.clone();
+    bad_magic[0] = b'x';
+    assert!(matches!(Archive::new(&bad_magic), Err(Error::InvalidArchive)));
+    let mut bad_flags = bytes.clone();
+    bad_flags[7] |= 0x02;
+    assert!(matches!(Archive::new(&bad_flags), Err(Error::InvalidArchive)));
+    for len in [0, 11, 20, bytes.len() - 1] {
+        assert!(matches!(Archive::new(&bytes[..len]), Err(Error::UnexpectedBufferEnd)));
+    }
+
+    // the table starts after the 12-byte head, each entry with a 2-byte name length
+    let second_name = 12 + (20 + 1) + 2;
+    assert_eq!(bytes[second_name], b'1');
+    let mut duplicate = bytes.clone();
+    duplicate[second_name] = b'0';
+    assert!(matches!(Archive::new(&duplicate), Err(Error::DuplicateArchiveEntry)));
+    // a few bytes of ops can't hold a 20000x20000 image
+    let mut too_large = bytes.clone();
+    too_large[15..23].copy_from_slice(&[0, 0, 0x4e, 0x20, 0, 0, 0x4e, 0x20]);
+    assert!(matches!(Archive::new(&too_large), Err(Error::InvalidArchive)));
+
+    let archive = Archive::new(&bytes).unwrap().with_limits(Limits::default().with_max_pixels(15));
+    assert!(matches!(archive.extract("0"), Err(Error::LimitsExceeded)));
+    let archive = archive.with_limits(Limits::default());
+    assert_eq!(archive.extract("0").unwrap(), sprites[0].0);
+}
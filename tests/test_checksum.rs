@@ -0,0 +1,184 @@
+mod common;
+
+use std::io::Write;
+
+use qoi::{decode_to_vec, Channels, Decoder, DecoderWriter, EncodeStatus, Encoder, Error};
+
+use self::common::{decode_all, gen_image};
+
+#[test]
+fn test_checksum_roundtrip() {
+    for channels in [3, 4] {
+        let img = gen_image(67, 45, channels, channels as u64);
+        let new = || Encoder::new(&img, 67, 45).unwrap();
+        let plain = new().encode_to_vec::<false>().unwrap();
+        let encoded = new().with_checksum(true).encode_to_vec::<false>().unwrap();
+        // the flags word and the checksum itself
+        assert_eq!(encoded.len(), plain.len() + 4 + 4);
+        assert!(decode_all(&encoded, &img).checksum());
+
+        let mut decoder = Decoder::new(&encoded).unwrap();
+        let pixels: Vec<u8> = match channels {
+            3 => decoder.pixels::<false, 3>().unwrap().flat_map(Result::unwrap).collect(),
+            _ => decoder.pixels::<false, 4>().unwrap().flat_map(Result::unwrap).collect(),
+        };
+        assert_eq!(pixels, img);
+
+        let mut streamed = Vec::new();
+        new().with_checksum(true).encode_to_stream::<_, false>(&mut streamed).unwrap();
+        assert_eq!(streamed, encoded);
+        let mut encoder = new().with_checksum(true);
+        let (mut partial, mut buf) = (Vec::<u8>::new(), [0; 11]);
+        loop {
+            let status = encoder.encode_to_buf_partial::<false>(&mut buf).unwrap();
+            partial.extend(&buf[..status.n_written()]);
+            if let EncodeStatus::Complete(_) = status {
+                break;
+            }
+        }
+        assert_eq!(partial, encoded);
+
+        // raw op streams don't carry the checksum
+        let data_only = new().with_checksum(true).encode_to_vec::<true>().unwrap();
+        let plain_data_only = new().encode_to_vec::<true>().unwrap();
+        assert_eq!(data_only, plain_data_only);
+    }
+}
+
+#[test]
+fn test_checksum_value() {
+    // the standard CRC-32 check value, with "123456789" as a 3x1 RGB image
+    let mut encoder = Encoder::new(b"123456789", 3, 1).unwrap().with_checksum(true);
+    let encoded = encoder.encode_to_vec::<false>().unwrap();
+    assert_eq!(encoded[encoded.len() - 4..], 0xcbf4_3926_u32.to_be_bytes());
+}
+
+#[test]
+fn test_checksum_paths() {
+    let (width, height) = (83, 57);
+    let img = gen_image(width, height, 4, 5);
+    let new = || Encoder::new(&img, width as u32, height as u32).unwrap().with_checksum(true);
+    let encoders = [
+        new().with_slice_height(10),
+        new().with_tile_size(20, 16),
+        new().with_entropy_coding(true),
+        new().with_color_transform(true),
+        new().with_vertical_prediction(true).with_palette(true),
+    ];
+    for mut encoder in encoders {
+        assert_eq!(encoder.error(), 0);
+        let encoded = encoder.encode_to_vec::<false>().unwrap();
+        assert!(decode_all(&encoded, &img).checksum());
+    }
+}
+
+#[test]
+fn test_checksum_near_lossless() {
+    // the checksum covers the pixels as stored, not the source ones
+    let (width, height) = (83, 57);
+    for (src_channels, channels) in [(3, 3), (4, 4), (3, 4), (4, 3)] {
+        let img = gen_image(width, height, src_channels, 8);
+        let new = || {
+            Encoder::new(&img, width as u32, height as u32)
+                .unwrap()
+                .with_checksum(true)
+                .with_max_error(4)
+                .with_channels(Channels::try_from(channels).unwrap())
+        };
+        let encoders = [
+            new(),
+            new().with_slice_height(10),
+            new().with_tile_size(20, 16),
+            new().with_tile_size(20, 16).with_entropy_coding(true),
+        ];
+        for mut encoder in encoders {
+            let encoded = encoder.encode_to_vec::<false>().unwrap();
+            assert!((1..=4).contains(&encoder.error()));
+            let (_, decoded) = decode_to_vec::<false>(&encoded).unwrap();
+            let source = img.chunks(src_channels).flat_map(|px| {
+                let px = [px[0], px[1], px[2], *px.get(3).unwrap_or(&0xff)];
+                px.into_iter().take(channels as usize)
+            });
+            assert!(decoded.iter().zip(source).all(|(&a, b)| a.abs_diff(b) <= 4));
+            assert!(decode_all(&encoded, &decoded).checksum());
+        }
+        for tile_size in [0, 20] {
+            let new = || new().with_tile_size(tile_size, tile_size);
+            let encoded = new().encode_to_vec::<false>().unwrap();
+            let mut encoder = new();
+            let (mut partial, mut buf) = (Vec::<u8>::new(), [0; 11]);
+            loop {
+                let status = encoder.encode_to_buf_partial::<false>(&mut buf).unwrap();
+                partial.extend(&buf[..status.n_written()]);
+                if let EncodeStatus::Complete(_) = status {
+                    break;
+                }
+            }
+            assert_eq!(partial, encoded);
+            assert!(decode_to_vec::<false>(&partial).is_ok());
+        }
+        let mut streamed = Vec::new();
+        new().encode_to_stream::<_, false>(&mut streamed).unwrap();
+        assert_eq!(streamed, new().encode_to_vec::<false>().unwrap());
+    }
+}
+
+#[test]
+fn test_checksum_channels() {
+    let img = gen_image(40, 30, 3, 6);
+    let mut encoder = Encoder::new(&img, 40, 30).unwrap().with_checksum(true);
+    let encoded = encoder.encode_to_vec::<false>().unwrap();
+    // the alpha channel added when decoding doesn't affect the checksum
+    let expected: Vec<u8> = img.chunks(3).flat_map(|px| [px[0], px[1], px[2], 0xff]).collect();
+    let mut decoder = Decoder::new(&encoded).unwrap().with_channels(Channels::Rgba);
+    assert_eq!(decoder.decode_to_vec::<false>().unwrap(), expected);
+
+    // dropping the alpha channel skips verification instead of failing it
+    let img = gen_image(40, 30, 4, 7);
+    let mut encoder = Encoder::new(&img, 40, 30).unwrap().with_checksum(true);
+    let mut encoded = encoder.encode_to_vec::<false>().unwrap();
+    let n = encoded.len();
+    encoded[n - 1] ^= 0x01;
+    let expected: Vec<u8> = img.chunks(4).flat_map(|px| &px[..3]).copied().collect();
+    let mut decoder = Decoder::new(&encoded).unwrap().with_channels(Channels::Rgb);
+    assert_eq!(decoder.decode_to_vec::<false>().unwrap(), expected);
+    assert!(matches!(decode_to_vec::<false>(&encoded), Err(Error::ChecksumMismatch { .. })));
+}
+
+#[test]
+fn test_checksum_mismatch() {
+    let (width, height) = (64, 48);
+    let img = gen_image(width, height, 4, 8);
+    let mut encoder = Encoder::new(&img, width as u32, height as u32).unwrap().with_checksum(true);
+    let encoded = encoder.encode_to_vec::<false>().unwrap();
+    let n = encoded.len();
+
+    // the first pixel is stored as QOI_OP_RGB right after the header and the flags word;
+    // changing its red channel goes unnoticed by the padding but not by the checksum
+    let mut corrupted = encoded.clone();
+    assert_eq!(corrupted[18], 0xfe);
+    corrupted[19] ^= 0x10;
+    let mut trailer = encoded.clone();
+    trailer[n - 2] ^= 0x10;
+    for bad in [&corrupted, &trailer] {
+        assert!(matches!(decode_to_vec::<false>(bad), Err(Error::ChecksumMismatch { .. })));
+        let result = Decoder::from_stream(bad.as_slice()).unwrap().decode_to_vec::<false>();
+        assert!(matches!(result, Err(Error::ChecksumMismatch { .. })));
+        let result = Decoder::new(bad).unwrap().decode_rows::<false>(|_, _| {});
+        assert!(matches!(result, Err(Error::ChecksumMismatch { .. })));
+        let mut decoder = Decoder::new(bad).unwrap();
+        let result: Result<Vec<_>, _> = decoder.pixels::<false, 4>().unwrap().collect();
+        assert!(matches!(result, Err(Error::ChecksumMismatch { .. })));
+        let mut out = vec![0; img.len()];
+        let mut writer = DecoderWriter::new(&mut out);
+        writer.write_all(bad).unwrap();
+        assert!(matches!(writer.finish(), Err(Error::ChecksumMismatch { .. })));
+    }
+    let stored =
+        u32::from_be_bytes([encoded[n - 4], encoded[n - 3], encoded[n - 2], encoded[n - 1]]);
+    assert!(matches!(
+        decode_to_vec::<false>(&trailer),
+        Err(Error::ChecksumMismatch { expected, found }) if found == stored && expected != stored
+    ));
+    assert!(matches!(decode_to_vec::<false>(&encoded[..n - 1]), Err(Error::UnexpectedBufferEnd)));
+}
@@ -0,0 +1,63 @@
+mod common;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use qoi::{decode_to_vec, Channels, Decoder, EncodeStatus, Encoder};
+
+use self::common::{decode_all, read_png};
+
+#[test]
+fn test_color_transform_roundtrip() {
+    for name in ["dice", "kodim10", "kodim23", "qoi_logo", "testcard"] {
+        let (img, width, height) = read_png(&format!("assets/{}.png", name));
+        let mut encoder = Encoder::new(&img, width, height).unwrap().with_color_transform(true);
+        let encoded = encoder.encode_to_vec::<false>().unwrap();
+        assert!(decode_all(&encoded, &img).color_transform());
+
+        // incremental decoders undo the transform too, including across split runs
+        let n = img.len() / (width * height) as usize;
+        let mut decoder = Decoder::new(&encoded).unwrap();
+        let pixels: Vec<u8> = match n {
+            3 => decoder.pixels::<false, 3>().unwrap().flat_map(Result::unwrap).collect(),
+            _ => decoder.pixels::<false, 4>().unwrap().flat_map(Result::unwrap).collect(),
+        };
+        assert_eq!(pixels, img);
+    }
+}
+
+#[test]
+fn test_color_transform_paths() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let (width, height) = (83, 57);
+    let img: Vec<u8> = (0..width * height * 4).map(|_| rng.gen()).collect();
+    let new = || Encoder::new(&img, width, height).unwrap().with_color_transform(true);
+    for encoder in [new(), new().with_slice_height(10), new().with_tile_size(20, 16)] {
+        // the color transform is always lossless
+        let mut encoder = encoder.with_max_error(4);
+        let encoded = encoder.encode_to_vec::<false>().unwrap();
+        assert_eq!(encoder.error(), 0);
+        let decoder = Decoder::new(&encoded).unwrap().with_channels(Channels::Rgb);
+        let region = decoder.decode_region(15, 9, 30, 20).unwrap();
+        let expected: Vec<u8> = (9..29)
+            .flat_map(|y| {
+                img[(y * width as usize + 15) * 4..(y * width as usize + 45) * 4].chunks(4)
+            })
+            .flat_map(|px| &px[..3])
+            .copied()
+            .collect();
+        assert_eq!(region, expected);
+        decode_all(&encoded, &img);
+
+        let mut encoder = new().with_max_error(4);
+        let (mut partial, mut buf) = (Vec::<u8>::new(), [0; 11]);
+        loop {
+            let status = encoder.encode_to_buf_partial::<false>(&mut buf).unwrap();
+            partial.extend(&buf[..status.n_written()]);
+            if let EncodeStatus::Complete(_) = status {
+                break;
+            }
+        }
+        let (_, decoded) = decode_to_vec::<false>(&partial).unwrap();
+        assert_eq!(decoded, img);
+    }
+}
@@ -0,0 +1,99 @@
+mod common;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use qoi::{decode_to_vec, Channels, Decoder, Encoder, Error, State};
+
+use self::common::gen_boxes;
+
+/// Small icons drawn as boxes in colors picked from a shared theme.
+fn gen_icons(n: usize, size: usize, seed: u64) -> Vec<Vec<u8>> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let theme: Vec<[u8; 4]> = (0..40).map(|_| rng.gen()).collect();
+    (0..n).map(|_| gen_boxes(&mut rng, &theme, size, size, 4, 12)).collect()
+}
+
+#[test]
+fn test_dictionary_roundtrip() {
+    let icons = gen_icons(100, 16, 0);
+    let dictionary = State::train(&icons[..80], Channels::Rgba);
+    assert_ne!(dictionary.dictionary_id(), 0);
+    let retrained = State::train(&icons[..80], Channels::Rgba);
+    assert_eq!(retrained.dictionary_id(), dictionary.dictionary_id());
+
+    let (mut plain_len, mut dict_len) = (0, 0);
+    for img in &icons[80..] {
+        plain_len += Encoder::new(img, 16, 16).unwrap().encode_to_vec::<false>().unwrap().len();
+        let mut encoder = Encoder::new(img, 16, 16).unwrap().with_dictionary(&dictionary);
+        let encoded = encoder.encode_to_vec::<false>().unwrap();
+        dict_len += encoded.len();
+
+        let decoder = Decoder::new(&encoded).unwrap();
+        assert_eq!(decoder.header().dictionary_id(), dictionary.dictionary_id());
+        let mut decoder = decoder.with_dictionary(&dictionary);
+        assert_eq!(&decoder.decode_to_vec::<false>().unwrap(), img);
+        let decoder = Decoder::from_stream(encoded.as_slice()).unwrap();
+        let mut decoder = decoder.with_dictionary(&dictionary);
+        assert_eq!(&decoder.decode_to_vec::<false>().unwrap(), img);
+
+        let mut encoder = Encoder::new_with(dictionary.clone(), img, 16, 16).unwrap();
+        assert_eq!(encoder.encode_to_vec::<false>().unwrap(), encoded);
+    }
+    assert!(dict_len * 10 < plain_len * 9);
+}
+
+#[test]
+fn test_dictionary_paths() {
+    let icons = gen_icons(30, 48, 1);
+    let dictionary = State::train(&icons[..20], Channels::Rgba);
+    for img in &icons[20..] {
+        let new = || Encoder::new(img, 48, 48).unwrap().with_dictionary(&dictionary);
+        let encoders = [
+            new().with_slice_height(10),
+            new().with_tile_size(16, 16),
+            new().with_entropy_coding(true),
+            new().with_vertical_prediction(true).with_palette(true),
+            new().with_channels(Channels::Rgb),
+        ];
+        for mut encoder in encoders {
+            let channels = encoder.channels().as_u8() as usize;
+            let expected: Vec<u8> = img.chunks(4).flat_map(|px| &px[..channels]).copied().collect();
+            let encoded = encoder.encode_to_vec::<false>().unwrap();
+            let decoder = Decoder::new(&encoded).unwrap().with_dictionary(&dictionary);
+            let region = decoder.decode_region(0, 0, 48, 48).unwrap();
+            assert_eq!(region, expected);
+            let mut decoder = decoder.with_dictionary(&dictionary);
+            assert_eq!(decoder.decode_to_vec::<false>().unwrap(), expected);
+        }
+    }
+}
+
+#[test]
+fn test_dictionary_mismatch() {
+    let icons = gen_icons(10, 16, 2);
+    let dictionary = State::train(&icons, Channels::Rgba);
+    let other = State::train(gen_icons(10, 16, 3), Channels::Rgba);
+    let mut encoder = Encoder::new(&icons[0], 16, 16).unwrap().with_dictionary(&dictionary);
+    let encoded = encoder.encode_to_vec::<false>().unwrap();
+    let (id, other_id) = (dictionary.dictionary_id(), other.dictionary_id());
+    assert!(matches!(
+        decode_to_vec::<false>(&encoded),
+        Err(Error::DictionaryMismatch { expected, found: 0 }) if expected == id
+    ));
+    let mut decoder = Decoder::new(&encoded).unwrap().with_dictionary(&other);
+    assert!(matches!(
+        decoder.decode_to_vec::<false>(),
+        Err(Error::DictionaryMismatch { expected, found }) if expected == id && found == other_id
+    ));
+    // images encoded without a dictionary can't be decoded with one either
+    let plain = Encoder::new(&icons[0], 16, 16).unwrap().encode_to_vec::<false>().unwrap();
+    let mut decoder = Decoder::new(&plain).unwrap().with_dictionary(&dictionary);
+    assert!(matches!(decoder.decode_to_vec::<false>(), Err(Error::DictionaryMismatch { .. })));
+}
+
+#[test]
+fn test_dictionary_state_size() {
+    // dictionaries and palettes live out of line, a state is little more than its caches
+    let caches = std::mem::size_of::<[u8; 4]>() * (0x40 + 0x400);
+    assert!(std::mem::size_of::<State>() <= caches + 64);
+}
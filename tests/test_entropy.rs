@@ -0,0 +1,95 @@
+mod common;
+
+use std::io::{BufReader, Read, Write};
+
+use qoi::{decode_to_vec, Decoder, DecoderWriter, Encoder, Error, Limits};
+
+use self::common::{decode_all, read_png};
+
+#[test]
+fn test_entropy_roundtrip() {
+    for name in ["dice", "kodim10", "kodim23", "qoi_logo", "testcard"] {
+        let (img, width, height) = read_png(&format!("assets/{}.png", name));
+        let plain = Encoder::new(&img, width, height).unwrap().encode_to_vec::<false>().unwrap();
+        let mut encoder = Encoder::new(&img, width, height).unwrap().with_entropy_coding(true);
+        let encoded = encoder.encode_to_vec::<false>().unwrap();
+        assert!(encoded.len() < plain.len(), "{}: {} vs {}", name, encoded.len(), plain.len());
+
+        assert!(decode_all(&encoded, &img).entropy_coded());
+
+        // all other ways of encoding agree
+        let mut encoder = Encoder::new(&img, width, height).unwrap().with_entropy_coding(true);
+        let mut streamed: Vec<u8> = vec![];
+        encoder.encode_to_stream::<_, false>(&mut streamed).unwrap();
+        assert_eq!(streamed, encoded);
+        let encoder = Encoder::new(&img, width, height).unwrap().with_entropy_coding(true);
+        let mut read: Vec<u8> = vec![];
+        BufReader::with_capacity(5, encoder.into_reader::<false>()).read_to_end(&mut read).unwrap();
+        assert_eq!(read, encoded);
+
+        // incremental decoders would have to stage the image in memory, so they refuse
+        let mut decoder = Decoder::new(&encoded).unwrap();
+        assert!(matches!(decoder.pixels::<false, 4>(), Err(Error::UnsupportedEntropyCoding)));
+        let mut out = vec![0; img.len()];
+        assert!(DecoderWriter::new(&mut out).write_all(&encoded).is_err());
+    }
+}
+
+#[test]
+fn test_entropy_layouts() {
+    let (img, width, height) = read_png("assets/kodim23.png");
+    let tiled = Encoder::new(&img, width, height).unwrap().with_tile_size(100, 64);
+    let sliced = Encoder::new(&img, width, height).unwrap().with_slice_height(50);
+    for encoder in [tiled, sliced] {
+        let mut encoder = encoder.with_entropy_coding(true);
+        let header = *encoder.header();
+        let encoded = encoder.encode_to_vec::<false>().unwrap();
+        let decoder = Decoder::new(&encoded).unwrap();
+        assert_eq!(*decoder.header(), header);
+        let region = decoder.decode_region(90, 60, 30, 20).unwrap();
+        decode_all(&encoded, &img);
+        let n = img.len() / (width * height) as usize;
+        let row_len = width as usize * n;
+        let expected: Vec<u8> = (60..80)
+            .flat_map(|y| &img[y * row_len + 90 * n..y * row_len + 120 * n])
+            .copied()
+            .collect();
+        assert_eq!(region, expected);
+    }
+}
+
+#[test]
+fn test_entropy_invalid() {
+    let img: Vec<u8> = (0..64 * 64 * 3).map(|i| (i % 251 * 7) as u8).collect();
+    let mut encoder = Encoder::new(&img, 64, 64).unwrap().with_entropy_coding(true);
+    let encoded = encoder.encode_to_vec::<false>().unwrap();
+    assert!(decode_to_vec::<false>(&encoded).is_ok());
+    // the coded stream starts after the 18-byte header with the op stream length
+    let mut too_long = encoded.clone();
+    too_long[18..22].copy_from_slice(&u32::MAX.to_be_bytes());
+    assert!(matches!(decode_to_vec::<false>(&too_long), Err(Error::InvalidEntropyCoding)));
+    let mut oversubscribed = encoded.clone();
+    oversubscribed[22..22 + 128].fill(0x11);
+    assert!(matches!(decode_to_vec::<false>(&oversubscribed), Err(Error::InvalidEntropyCoding)));
+    let truncated = &encoded[..encoded.len() - 20];
+    assert!(matches!(decode_to_vec::<false>(truncated), Err(Error::UnexpectedBufferEnd)));
+}
+
+#[test]
+fn test_entropy_hostile_len() {
+    // a 20000x20000 image claiming a billion op bytes in a few dozen bytes of input
+    let mut encoder = Encoder::new(&[0; 3], 1, 1).unwrap().with_entropy_coding(true);
+    let mut hostile = encoder.encode_to_vec::<false>().unwrap();
+    hostile[4..12].copy_from_slice(&[0, 0, 0x4e, 0x20, 0, 0, 0x4e, 0x20]);
+    hostile[18..22].copy_from_slice(&1_000_000_000_u32.to_be_bytes());
+    let decode_rows = |decoder: &mut Decoder<_>| decoder.decode_rows::<false>(|_, _| {});
+    let mut decoder = Decoder::new(&hostile).unwrap();
+    assert!(matches!(decode_rows(&mut decoder), Err(Error::LimitsExceeded)));
+    assert!(matches!(decoder.decode_region(0, 0, 8, 8), Err(Error::LimitsExceeded)));
+    // without limits, the count is still bounded by the input, or read as it is decoded
+    let mut decoder = Decoder::new(&hostile).unwrap().with_limits(Limits::none());
+    assert!(matches!(decode_rows(&mut decoder), Err(Error::InvalidEntropyCoding)));
+    let mut decoder = Decoder::from_stream(hostile.as_slice()).unwrap().with_limits(Limits::none());
+    let result = decoder.decode_rows::<false>(|_, _| {});
+    assert!(matches!(result, Err(Error::UnexpectedBufferEnd)));
+}
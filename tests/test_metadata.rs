@@ -0,0 +1,182 @@
+mod common;
+
+use std::io::BufReader;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use qoi::{Channels, Chunk, ChunkType, Decoder, EncodeStatus, Encoder, Error, Limits, Metadata};
+
+use self::common::{decode_all, gen_indexed};
+
+fn gen_metadata(seed: u64) -> Metadata {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let icc: Vec<u8> = (0..3144).map(|_| rng.gen()).collect();
+    let exif: Vec<u8> = b"MM\0*".iter().copied().chain((0..200).map(|_| rng.gen())).collect();
+    let mut metadata = Metadata::new();
+    metadata.push(ChunkType::ICC, &icc).unwrap();
+    metadata.push(ChunkType::EXIF, &exif).unwrap();
+    metadata.push_text("timestamp", "2026-10-18T09:30:00.125Z").unwrap();
+    metadata.push_text("camera", "left, 1920x1080").unwrap();
+    metadata.push(ChunkType(*b"zzzz"), &[]).unwrap();
+    metadata.push_text("comment", "").unwrap();
+    metadata
+}
+
+#[test]
+fn test_metadata_roundtrip() {
+    let img = gen_indexed(57, 43, 4, 16, 0);
+    let metadata = gen_metadata(1);
+    let icc = metadata.icc_profile().unwrap().to_vec();
+    let new = || Encoder::new(&img, 57, 43).unwrap();
+    let plain = new().encode_to_vec::<false>().unwrap();
+    let mut encoder = new()
+        .with_chunk(ChunkType::ICC, &icc)
+        .unwrap()
+        .with_chunk(ChunkType::EXIF, metadata.exif().unwrap())
+        .unwrap()
+        .with_text("timestamp", "2026-10-18T09:30:00.125Z")
+        .unwrap()
+        .with_text("camera", "left, 1920x1080")
+        .unwrap()
+        .with_chunk(ChunkType(*b"zzzz"), &[])
+        .unwrap()
+        .with_text("comment", "")
+        .unwrap();
+    assert_eq!(encoder.metadata(), &metadata);
+    let encoded = encoder.encode_to_vec::<false>().unwrap();
+    let section_len = 6 * 8 + 3144 + 204 + 34 + 22 + 8;
+    assert_eq!(encoder.header().metadata_len() as usize, section_len);
+    // the flags word, the length of the chunks and the chunks themselves
+    assert_eq!(encoded.len(), plain.len() + 4 + 4 + section_len);
+
+    let decoders = [
+        Decoder::new(&encoded).unwrap().metadata().clone(),
+        Decoder::from_stream(encoded.as_slice()).unwrap().metadata().clone(),
+        Decoder::from_buf_read(BufReader::with_capacity(3, encoded.as_slice()))
+            .unwrap()
+            .metadata()
+            .clone(),
+    ];
+    for decoded in decoders {
+        assert_eq!(decoded, metadata);
+        assert_eq!(decoded.icc_profile(), Some(icc.as_slice()));
+        assert_eq!(decoded.exif().unwrap()[..4], *b"MM\0*");
+        assert_eq!(decoded.text("timestamp"), Some("2026-10-18T09:30:00.125Z"));
+        assert_eq!(decoded.text("camera"), Some("left, 1920x1080"));
+        assert_eq!(decoded.text("comment"), Some(""));
+        assert_eq!(decoded.text("lens"), None);
+        assert_eq!(decoded.get(ChunkType(*b"zzzz")), Some(&[][..]));
+        let types: Vec<_> = decoded.chunks().map(|chunk| chunk.chunk_type).collect();
+        assert_eq!(types.len(), 6);
+        assert_eq!(types[..3], [ChunkType::ICC, ChunkType::EXIF, ChunkType::TEXT]);
+    }
+
+    // decoders that don't care about metadata just skip it
+    assert_eq!(decode_all(&encoded, &img).metadata_len() as usize, section_len);
+
+    // the chunks can be carried over to another image
+    let decoder = Decoder::new(&encoded).unwrap();
+    let mut encoder = new().with_metadata(decoder.metadata());
+    assert_eq!(encoder.encode_to_vec::<false>().unwrap(), encoded);
+    let mut encoder = new().with_metadata(decoder.metadata()).with_metadata(&Metadata::new());
+    assert_eq!(encoder.encode_to_vec::<false>().unwrap(), plain);
+    assert!(Decoder::new(&plain).unwrap().metadata().is_empty());
+}
+
+#[test]
+fn test_metadata_paths() {
+    let (width, height) = (83, 57);
+    let img = gen_indexed(width, height, 4, 16, 2);
+    let metadata = gen_metadata(3);
+    let new = |i| {
+        let encoder = Encoder::new(&img, width as u32, height as u32).unwrap();
+        let encoder = encoder.with_metadata(&metadata);
+        match i {
+            0 => encoder,
+            1 => encoder.with_slice_height(10),
+            2 => encoder.with_tile_size(20, 16),
+            3 => encoder.with_entropy_coding(true),
+            4 => encoder.with_vertical_prediction(true).with_palette(true),
+            _ => encoder.with_checksum(true).with_channels(Channels::Rgb),
+        }
+    };
+    for i in 0..6 {
+        let mut encoder = new(i);
+        let n_channels = encoder.channels().as_u8() as usize;
+        let expected: Vec<u8> = img.chunks(4).flat_map(|px| &px[..n_channels]).copied().collect();
+        let encoded = encoder.encode_to_vec::<false>().unwrap();
+        let decoder = Decoder::new(&encoded).unwrap();
+        assert_eq!(decoder.metadata(), &metadata);
+        let region = decoder.decode_region(15, 9, 30, 20).unwrap();
+        let expected_region: Vec<u8> = (9..29)
+            .flat_map(|y| &expected[(y * width + 15) * n_channels..(y * width + 45) * n_channels])
+            .copied()
+            .collect();
+        assert_eq!(region, expected_region);
+        decode_all(&encoded, &expected);
+        let decoder = Decoder::from_stream(encoded.as_slice()).unwrap();
+        assert_eq!(decoder.metadata(), &metadata);
+
+        let mut streamed = Vec::new();
+        new(i).encode_to_stream::<_, false>(&mut streamed).unwrap();
+        assert_eq!(streamed, encoded);
+        let (mut encoder, mut partial, mut buf) = (new(i), Vec::<u8>::new(), [0; 11]);
+        loop {
+            let status = encoder.encode_to_buf_partial::<false>(&mut buf).unwrap();
+            partial.extend(&buf[..status.n_written()]);
+            if let EncodeStatus::Complete(_) = status {
+                break;
+            }
+        }
+        assert_eq!(partial, encoded);
+    }
+}
+
+#[test]
+fn test_metadata_invalid() {
+    let mut metadata = Metadata::new();
+    assert!(matches!(metadata.push_text("", "value"), Err(Error::InvalidMetadata)));
+    assert!(matches!(metadata.push_text("a\0b", "value"), Err(Error::InvalidMetadata)));
+    assert!(metadata.is_empty());
+    let chunk = Chunk { chunk_type: ChunkType::ICC, data: b"key\0value" };
+    assert_eq!(chunk.text(), None);
+    let chunk = Chunk { chunk_type: ChunkType::TEXT, data: b"key value" };
+    assert_eq!(chunk.text(), None);
+
+    let img = gen_indexed(16, 16, 4, 16, 4);
+    let mut encoder = Encoder::new(&img, 16, 16).unwrap().with_text("key", "value").unwrap();
+    let encoded = encoder.encode_to_vec::<false>().unwrap();
+    // flags word, length of the chunks, then a single chunk: type, length, key, value
+    assert_eq!(encoded[16..18], [0x02, 0x00]);
+    assert_eq!(encoded[18..22], 17_u32.to_be_bytes());
+    assert_eq!(encoded[22..26], *b"tEXt");
+    assert_eq!(encoded[26..30], 9_u32.to_be_bytes());
+    assert_eq!(encoded[30..39], *b"key\0value");
+
+    let mut bad_chunk = encoded.clone();
+    bad_chunk[29] = 10;
+    assert!(matches!(Decoder::new(&bad_chunk), Err(Error::InvalidMetadata)));
+    assert!(matches!(Decoder::from_stream(bad_chunk.as_slice()), Err(Error::InvalidMetadata)));
+    let mut bad_len = encoded.clone();
+    bad_len[21] = 0;
+    assert!(matches!(Decoder::new(&bad_len), Err(Error::InvalidHeaderExtension)));
+    // streams can't tell a bogus length from the rest of the input, only from the limits
+    let mut huge_len = encoded.clone();
+    huge_len[18..22].copy_from_slice(&u32::MAX.to_be_bytes());
+    assert!(matches!(Decoder::new(&huge_len), Err(Error::UnexpectedBufferEnd)));
+    let result = Decoder::from_stream(huge_len.as_slice());
+    assert!(matches!(result, Err(Error::LimitsExceeded)));
+    let result = Decoder::from_stream_with_limits(huge_len.as_slice(), Limits::none());
+    assert!(matches!(result, Err(Error::UnexpectedBufferEnd)));
+    let limits = Limits::default().with_max_alloc(16);
+    let result = Decoder::from_buf_read_with_limits(encoded.as_slice(), limits);
+    assert!(matches!(result, Err(Error::LimitsExceeded)));
+    let limits = Limits::default().with_max_alloc(17);
+    let decoder = Decoder::from_buf_read_with_limits(encoded.as_slice(), limits).unwrap();
+    assert_eq!(decoder.limits(), &limits);
+    for len in [22, 30, 38] {
+        assert!(matches!(Decoder::new(&encoded[..len]), Err(Error::UnexpectedBufferEnd)));
+        let result = Decoder::from_stream(&encoded[..len]);
+        assert!(matches!(result, Err(Error::UnexpectedBufferEnd)));
+    }
+}
@@ -3,4 +3,112 @@ fn test_new_encoder() {
     // this used to fail due to `Bytes` not being `pub`
     let arr = [0u8];
     let _ = qoi::Decoder::new(&arr[..]);
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_decode_limits() {
+    use qoi::{decode_to_vec, encode_to_vec, Decoder, Error, Limits};
+
+    let pixels = vec![0x42_u8; 16 * 8 * 3];
+    let encoded = encode_to_vec::<false>(&pixels, 16, 8).unwrap();
+
+    let decode_with = |limits: Limits| {
+        Decoder::new(&encoded).unwrap().with_limits(limits).decode_to_vec::<false>()
+    };
+    assert_eq!(decode_with(Limits::none()).unwrap(), pixels);
+    assert_eq!(decode_with(Limits::none().with_max_width(16)).unwrap(), pixels);
+    assert!(matches!(decode_with(Limits::none().with_max_width(15)), Err(Error::LimitsExceeded)));
+    assert!(matches!(decode_with(Limits::none().with_max_height(7)), Err(Error::LimitsExceeded)));
+    assert!(matches!(decode_with(Limits::none().with_max_pixels(127)), Err(Error::LimitsExceeded)));
+    assert!(matches!(decode_with(Limits::none().with_max_alloc(383)), Err(Error::LimitsExceeded)));
+    let limits = Limits::none().with_max_width(15);
+    let mut decoder = Decoder::from_stream_with_limits(encoded.as_slice(), limits).unwrap();
+    assert!(matches!(decoder.decode_to_vec::<false>(), Err(Error::LimitsExceeded)));
+
+    // a bare header claiming a huge image must be rejected before allocating, even
+    // without limits
+    let mut hostile = encoded[..14].to_vec();
+    hostile[4..8].copy_from_slice(&20_000_u32.to_be_bytes());
+    hostile[8..12].copy_from_slice(&20_000_u32.to_be_bytes());
+    assert_eq!(decode_with(Limits::default()).unwrap(), pixels);
+    assert!(matches!(decode_to_vec::<false>(&hostile), Err(Error::UnexpectedBufferEnd)));
+    let mut decoder = Decoder::new(&hostile).unwrap();
+    assert!(matches!(decoder.decode_to_vec::<false>(), Err(Error::LimitsExceeded)));
+    let mut decoder = Decoder::new(&hostile).unwrap().with_limits(Limits::none());
+    assert!(matches!(decoder.decode_to_vec::<false>(), Err(Error::UnexpectedBufferEnd)));
+    let result = Decoder::new(&hostile).unwrap().decode_rows::<false>(|_, _| {});
+    assert!(matches!(result, Err(Error::UnexpectedBufferEnd)));
+}
+
+#[test]
+fn test_typed_pixels() {
+    use qoi::{
+        decode_to_pixels, encode_pixels_to_vec, encode_to_vec, encode_u32_to_vec, ByteOrder,
+        Channels, Decoder, Encoder, Error,
+    };
+
+    // 48 bytes: a valid RGBA 4x3 image, but also a valid RGB 4x4 one
+    let rgba: Vec<[u8; 4]> = (0..12_u8).map(|i| [i, i * 2, i * 3, 0xff - i]).collect();
+    let rgb: Vec<[u8; 3]> = rgba.iter().map(|px| [px[0], px[1], px[2]]).collect();
+
+    let encoded = encode_pixels_to_vec::<false, 4>(&rgba, 4, 3).unwrap();
+    assert_eq!(encoded, encode_to_vec::<false>(rgba.concat(), 4, 3).unwrap());
+    let encoded_rgb = encode_pixels_to_vec::<false, 3>(&rgb, 4, 3).unwrap();
+    assert_eq!(encoded_rgb, encode_to_vec::<false>(rgb.concat(), 4, 3).unwrap());
+    // with inferred channels, the wrong dimensions silently pass
+    assert_eq!(Encoder::new(&rgba.concat(), 4, 4).unwrap().channels(), Channels::Rgb);
+    assert!(matches!(
+        encode_pixels_to_vec::<false, 4>(&rgba, 4, 4),
+        Err(Error::InvalidImageLength { .. })
+    ));
+
+    let (header, decoded) = decode_to_pixels::<false, 4>(&encoded).unwrap();
+    assert_eq!((header.channels, decoded.as_slice()), (Channels::Rgba, rgba.as_slice()));
+    let (_, decoded) = decode_to_pixels::<false, 3>(&encoded).unwrap();
+    assert_eq!(decoded, rgb);
+
+    for order in [ByteOrder::BigEndian, ByteOrder::LittleEndian] {
+        let packed: Vec<u32> = rgba.iter().map(|&px| order.pack(px)).collect();
+        assert_eq!(encode_u32_to_vec::<false>(&packed, 4, 3, order).unwrap(), encoded);
+        let decoded = Decoder::new(&encoded).unwrap().decode_to_u32::<false>(order).unwrap();
+        assert_eq!(decoded, packed);
+    }
+    assert_eq!(ByteOrder::BigEndian.pack([1, 2, 3, 4]), 0x0102_0304);
+    assert_eq!(ByteOrder::LittleEndian.pack([1, 2, 3, 4]), 0x0403_0201);
+}
+
+#[test]
+fn test_encoder_channels() {
+    use qoi::{decode_to_vec, encode_to_vec, Channels, Encoder};
+
+    let rgb: Vec<u8> = (0..16 * 9 * 3).map(|i| (i % 7 * 40) as u8).collect();
+    let rgba: Vec<u8> = rgb.chunks_exact(3).flat_map(|px| [px[0], px[1], px[2], 0xff]).collect();
+    let expected_rgb = encode_to_vec::<false>(&rgb, 16, 9).unwrap();
+
+    let mut encoder = Encoder::new(&rgba, 16, 9).unwrap().with_channels(Channels::Rgb);
+    assert_eq!((encoder.src_channels(), encoder.channels()), (Channels::Rgba, Channels::Rgb));
+    let encoded = encoder.encode_to_vec::<false>().unwrap();
+    assert_eq!(encoded, expected_rgb);
+    let mut encoder = Encoder::new(&rgba, 16, 9).unwrap().with_channels(Channels::Rgb);
+    let mut streamed = vec![];
+    encoder.encode_to_stream::<_, false>(&mut streamed).unwrap();
+    assert_eq!(streamed, expected_rgb);
+
+    let encoded = Encoder::new(&rgb, 16, 9)
+        .unwrap()
+        .with_channels(Channels::Rgba)
+        .encode_to_vec::<false>()
+        .unwrap();
+    let (header, decoded) = decode_to_vec::<false>(&encoded).unwrap();
+    assert_eq!((header.channels, decoded), (Channels::Rgba, rgba.clone()));
+
+    // automatic mode only drops alpha if it's opaque everywhere
+    let encoder = Encoder::new(&rgba, 16, 9).unwrap().with_auto_channels();
+    assert_eq!(encoder.channels(), Channels::Rgb);
+    let mut translucent = rgba.clone();
+    translucent[4 * 100 + 3] = 0xfe;
+    let encoder = Encoder::new(&translucent, 16, 9).unwrap().with_auto_channels();
+    assert_eq!(encoder.channels(), Channels::Rgba);
+    let encoder = Encoder::new(&rgb, 16, 9).unwrap().with_auto_channels();
+    assert_eq!(encoder.channels(), Channels::Rgb);
+}
@@ -0,0 +1,108 @@
+mod common;
+
+use std::io::{BufReader, Read};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use qoi::{decode_to_vec, Channels, EncodeStatus, Encoder, Predictor};
+
+use self::common::{gen_indexed, read_png};
+
+fn max_diff(a: &[u8], b: &[u8]) -> u8 {
+    assert_eq!(a.len(), b.len());
+    a.iter().zip(b).map(|(x, y)| x.abs_diff(*y)).max().unwrap_or(0)
+}
+
+#[test]
+fn test_near_lossless_assets() {
+    for name in ["dice", "kodim10", "kodim23", "qoi_logo", "testcard"] {
+        let (img, width, height) = read_png(&format!("assets/{}.png", name));
+        let mut encoder = Encoder::new(&img, width, height).unwrap();
+        let lossless = encoder.encode_to_vec::<false>().unwrap();
+        assert_eq!(encoder.error(), 0);
+        let mut prev_len = lossless.len();
+        for max_error in [1, 2, 4, 8] {
+            let mut encoder = Encoder::new(&img, width, height).unwrap().with_max_error(max_error);
+            let encoded = encoder.encode_to_vec::<false>().unwrap();
+            let (_, decoded) = decode_to_vec::<false>(&encoded).unwrap();
+            let error = max_diff(&decoded, &img);
+            assert_eq!(encoder.error(), error, "{}: k={}", name, max_error);
+            assert!(error <= max_error);
+            assert!(encoded.len() < prev_len, "{}: k={}", name, max_error);
+            prev_len = encoded.len();
+        }
+    }
+}
+
+#[test]
+fn test_near_lossless_paths() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let (width, height) = (97, 61);
+    for channels in [3, 4] {
+        let img: Vec<u8> = (0..width * height * channels)
+            .map(|i| (i / channels % 50) as u8 * 5 + rng.gen_range(0..6))
+            .collect();
+        for (slice_height, max_error) in [(0, 3), (16, 3), (0, 255)] {
+            let new = || {
+                Encoder::new(&img, width as u32, height as u32)
+                    .unwrap()
+                    .with_slice_height(slice_height)
+                    .with_max_error(max_error)
+            };
+            let mut encoder = new();
+            let encoded = encoder.encode_to_vec::<false>().unwrap();
+            let (_, decoded) = decode_to_vec::<false>(&encoded).unwrap();
+            assert_eq!(encoder.error(), max_diff(&decoded, &img));
+            assert!(encoder.error() <= max_error);
+
+            // incremental and streaming encoders make the same choices
+            let mut encoder = new();
+            let (mut partial, mut buf) = (Vec::<u8>::new(), [0; 13]);
+            loop {
+                let status = encoder.encode_to_buf_partial::<false>(&mut buf).unwrap();
+                partial.extend(&buf[..status.n_written()]);
+                if let EncodeStatus::Complete(_) = status {
+                    break;
+                }
+            }
+            assert_eq!(partial, encoded);
+            assert_eq!(encoder.error(), max_diff(&decoded, &img));
+            let mut read = vec![];
+            BufReader::new(new().into_reader::<false>()).read_to_end(&mut read).unwrap();
+            assert_eq!(read, encoded);
+        }
+    }
+}
+
+#[test]
+fn test_near_lossless_ignored() {
+    // modes that only encode losslessly ignore the max error instead of failing
+    let img = gen_indexed(97, 61, 4, 40, 0);
+    let modes: [fn(Encoder) -> Encoder; 4] = [
+        |e| e.with_color_transform(true),
+        |e| e.with_vertical_prediction(true),
+        |e| e.with_predictor(Predictor::Paeth),
+        |e| e.with_palette(true),
+    ];
+    for mode in modes {
+        let new = |max_error| mode(Encoder::new(&img, 97, 61).unwrap().with_max_error(max_error));
+        let lossless = new(0).encode_to_vec::<false>().unwrap();
+        let mut encoder = new(4);
+        assert_eq!(encoder.encode_to_vec::<false>().unwrap(), lossless);
+        assert_eq!(encoder.error(), 0);
+        assert_eq!(decode_to_vec::<false>(&lossless).unwrap().1, img);
+    }
+}
+
+#[test]
+fn test_near_lossless_channels() {
+    // alpha isn't touched when storing RGB data as RGBA or RGBA data as RGB
+    let (img, width, height) = read_png("assets/kodim23.png");
+    let mut encoder =
+        Encoder::new(&img, width, height).unwrap().with_channels(Channels::Rgba).with_max_error(2);
+    let encoded = encoder.encode_to_vec::<false>().unwrap();
+    let (_, decoded) = decode_to_vec::<false>(&encoded).unwrap();
+    assert!(decoded.chunks_exact(4).all(|px| px[3] == 0xff));
+    let rgb: Vec<u8> = decoded.chunks_exact(4).flat_map(|px| &px[..3]).copied().collect();
+    assert_eq!(encoder.error(), max_diff(&rgb, &img));
+}
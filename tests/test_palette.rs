@@ -0,0 +1,116 @@
+mod common;
+
+use qoi::consts::QOI_OP_PALETTE;
+use qoi::{decode_to_vec, Channels, Decoder, EncodeStatus, Encoder, Error, Predictor};
+
+use self::common::{decode_all, gen_indexed, read_png};
+
+#[test]
+fn test_palette_roundtrip() {
+    for channels in [3, 4] {
+        let img = gen_indexed(201, 89, channels, 48, channels as u64);
+        let new = || Encoder::new(&img, 201, 89).unwrap();
+        let plain = new().encode_to_vec::<false>().unwrap();
+        let mut encoder = new().with_palette(true);
+        assert_eq!(encoder.header().palette_len(), 48);
+        let encoded = encoder.encode_to_vec::<false>().unwrap();
+        assert!(encoded.len() < plain.len() * 9 / 10);
+        assert!(decode_all(&encoded, &img).has_palette());
+        let mut streamed = Vec::new();
+        new().with_palette(true).encode_to_stream::<_, false>(&mut streamed).unwrap();
+        assert_eq!(streamed, encoded);
+    }
+    for name in ["kodim10", "qoi_logo"] {
+        let (img, width, height) = read_png(&format!("assets/{}.png", name));
+        let mut encoder = Encoder::new(&img, width, height).unwrap().with_palette(true);
+        let encoded = encoder.encode_to_vec::<false>().unwrap();
+        let (header, decoded) = decode_to_vec::<false>(&encoded).unwrap();
+        // photos have far too many colors for a palette
+        assert_eq!(header.has_palette(), name != "kodim10");
+        assert_eq!(decoded, img, "{}", name);
+    }
+}
+
+#[test]
+fn test_palette_paths() {
+    let (width, height) = (83, 57);
+    let img = gen_indexed(width, height, 4, 48, 5);
+    for (slice_height, tile_size) in [(0, (0, 0)), (10, (0, 0)), (0, (20, 16))] {
+        for (entropy, transform, predictor) in [
+            (false, false, Predictor::Left),
+            (true, false, Predictor::Left),
+            (false, true, Predictor::Med),
+        ] {
+            let new = || {
+                Encoder::new(&img, width as u32, height as u32)
+                    .unwrap()
+                    .with_slice_height(slice_height)
+                    .with_tile_size(tile_size.0, tile_size.1)
+                    .with_entropy_coding(entropy)
+                    .with_color_transform(transform)
+                    .with_predictor(predictor)
+                    .with_palette(true)
+            };
+            let encoded = new().encode_to_vec::<false>().unwrap();
+            assert!(decode_all(&encoded, &img).has_palette());
+
+            let decoder = Decoder::new(&encoded).unwrap().with_channels(Channels::Rgb);
+            let region = decoder.decode_region(15, 9, 30, 20).unwrap();
+            let expected: Vec<u8> = (9..29)
+                .flat_map(|y| img[(y * width + 15) * 4..(y * width + 45) * 4].chunks(4))
+                .flat_map(|px| &px[..3])
+                .copied()
+                .collect();
+            assert_eq!(region, expected);
+
+            let mut encoder = new();
+            let (mut partial, mut buf) = (Vec::<u8>::new(), [0; 11]);
+            loop {
+                let status = encoder.encode_to_buf_partial::<false>(&mut buf).unwrap();
+                partial.extend(&buf[..status.n_written()]);
+                if let EncodeStatus::Complete(_) = status {
+                    break;
+                }
+            }
+            assert_eq!(partial, encoded);
+        }
+    }
+}
+
+#[test]
+fn test_palette_channels() {
+    // dropping alpha may merge colors of the palette, which is fine
+    let img = gen_indexed(64, 48, 4, 48, 6);
+    let encoder = Encoder::new(&img, 64, 48).unwrap().with_palette(true);
+    let encoded = encoder.with_channels(Channels::Rgb).encode_to_vec::<false>().unwrap();
+    let expected: Vec<u8> = img.chunks(4).flat_map(|px| &px[..3]).copied().collect();
+    assert_eq!(decode_to_vec::<false>(&encoded).unwrap().1, expected);
+
+    let mut encoder = Encoder::new(&img, 64, 48).unwrap().with_palette(true).with_palette(false);
+    assert!(!encoder.header().has_palette());
+    let encoded = encoder.encode_to_vec::<false>().unwrap();
+    assert!(!decode_to_vec::<false>(&encoded).unwrap().0.has_palette());
+}
+
+#[test]
+fn test_palette_unsupported() {
+    let img = gen_indexed(16, 16, 4, 48, 7);
+    let mut encoder = Encoder::new(&img, 16, 16).unwrap().with_palette(true);
+    let encoded = encoder.encode_to_vec::<false>().unwrap();
+    let mut decoder = Decoder::new(&encoded).unwrap();
+    assert!(matches!(decoder.pixels::<false, 4>(), Err(Error::UnsupportedPalette)));
+}
+
+#[test]
+fn test_palette_invalid_index() {
+    let mut encoder = Encoder::new(&[10, 20, 30], 1, 1).unwrap().with_palette(true);
+    let mut encoded = encoder.encode_to_vec::<false>().unwrap();
+    assert_eq!(encoder.header().palette_len(), 1);
+    // replace the only op, right before the padding, by one for the 6th color
+    let n = encoded.len();
+    encoded.splice(n - 9..n - 8, [QOI_OP_PALETTE, 5]);
+    let result = decode_to_vec::<false>(&encoded);
+    assert!(matches!(result, Err(Error::InvalidPalette { index: 5, len: 1 })));
+    let result = Decoder::from_stream(encoded.as_slice()).unwrap().decode_to_vec::<false>();
+    assert!(matches!(result, Err(Error::InvalidPalette { index: 5, len: 1 })));
+}
@@ -0,0 +1,107 @@
+mod common;
+
+use qoi::{
+    decode_header, decode_to_vec, Channels, Decoder, EncodeStatus, Encoder, Error, Predictor,
+};
+
+use self::common::{decode_all, gen_image, read_png};
+
+const PREDICTORS: [Predictor; 4] =
+    [Predictor::Up, Predictor::Average, Predictor::Paeth, Predictor::Med];
+
+#[test]
+fn test_predictor_roundtrip() {
+    for name in ["dice", "kodim10", "qoi_logo", "testcard"] {
+        let (img, width, height) = read_png(&format!("assets/{}.png", name));
+        for predictor in PREDICTORS {
+            let mut encoder = Encoder::new(&img, width, height).unwrap().with_predictor(predictor);
+            let encoded = encoder.encode_to_vec::<false>().unwrap();
+            let (header, decoded) = decode_to_vec::<false>(&encoded).unwrap();
+            assert_eq!(header.predictor(), predictor);
+            assert_eq!(decoded, img, "{}: {:?}", name, predictor);
+        }
+    }
+    for channels in [3, 4] {
+        let img = gen_image(157, 83, channels, 0);
+        for predictor in PREDICTORS {
+            let mut encoder = Encoder::new(&img, 157, 83).unwrap().with_predictor(predictor);
+            let encoded = encoder.encode_to_vec::<false>().unwrap();
+            assert_eq!(decode_to_vec::<false>(&encoded).unwrap().1, img);
+        }
+    }
+
+    // smooth photos benefit the most
+    let (img, width, height) = read_png("assets/kodim23.png");
+    let new = || Encoder::new(&img, width, height).unwrap();
+    let plain = new().encode_to_vec::<false>().unwrap();
+    let med = new().with_predictor(Predictor::Med).encode_to_vec::<false>().unwrap();
+    assert!(med.len() < plain.len() * 95 / 100);
+}
+
+#[test]
+fn test_predictor_paths() {
+    let (width, height) = (83, 57);
+    let img = gen_image(width, height, 4, 1);
+    for (slice_height, tile_size) in [(0, (0, 0)), (10, (0, 0)), (0, (20, 16))] {
+        for (vertical, entropy, transform) in
+            [(false, false, false), (true, false, false), (false, true, true)]
+        {
+            let new = || {
+                Encoder::new(&img, width as u32, height as u32)
+                    .unwrap()
+                    .with_slice_height(slice_height)
+                    .with_tile_size(tile_size.0, tile_size.1)
+                    .with_predictor(Predictor::Med)
+                    .with_vertical_prediction(vertical)
+                    .with_entropy_coding(entropy)
+                    .with_color_transform(transform)
+            };
+            let encoded = new().encode_to_vec::<false>().unwrap();
+            assert_eq!(decode_all(&encoded, &img).predictor(), Predictor::Med);
+
+            let decoder = Decoder::new(&encoded).unwrap().with_channels(Channels::Rgb);
+            let region = decoder.decode_region(15, 9, 30, 20).unwrap();
+            let expected: Vec<u8> = (9..29)
+                .flat_map(|y| img[(y * width + 15) * 4..(y * width + 45) * 4].chunks(4))
+                .flat_map(|px| &px[..3])
+                .copied()
+                .collect();
+            assert_eq!(region, expected);
+
+            let mut encoder = new();
+            let (mut partial, mut buf) = (Vec::<u8>::new(), [0; 11]);
+            loop {
+                let status = encoder.encode_to_buf_partial::<false>(&mut buf).unwrap();
+                partial.extend(&buf[..status.n_written()]);
+                if let EncodeStatus::Complete(_) = status {
+                    break;
+                }
+            }
+            assert_eq!(partial, encoded);
+        }
+    }
+}
+
+#[test]
+fn test_predictor_header() {
+    let img = gen_image(16, 16, 4, 2);
+    let encoded = Encoder::new(&img, 16, 16)
+        .unwrap()
+        .with_predictor(Predictor::Paeth)
+        .encode_to_vec::<false>()
+        .unwrap();
+    assert_eq!(decode_header(&encoded).unwrap().predictor(), Predictor::Paeth);
+    let mut decoder = Decoder::new(&encoded).unwrap();
+    assert!(matches!(decoder.pixels::<false, 4>(), Err(Error::UnsupportedVerticalPrediction)));
+
+    // the predictor is stored in the header extension, and the previous pixel is implied
+    let mut invalid = encoded.clone();
+    let n = invalid.len();
+    let pos = 14 + 4;
+    assert_eq!(&invalid[pos..pos + 4], &[0, 0, 0, 3]);
+    invalid[pos + 3] = 5;
+    assert!(matches!(decode_header(&invalid), Err(Error::InvalidPredictor { predictor: 5 })));
+    invalid[pos + 3] = 0;
+    assert!(matches!(decode_header(&invalid), Err(Error::InvalidHeaderExtension)));
+    assert_eq!(invalid.len(), n);
+}
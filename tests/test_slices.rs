@@ -0,0 +1,235 @@
+mod common;
+
+use std::io::{BufReader, Read, Write};
+
+use rand::{rngs::StdRng, SeedableRng};
+
+use qoi::{decode_to_vec, Decoder, DecoderWriter, Encoder, Error};
+
+use self::common::{decode_all, gen_runs};
+
+/// Wraps a single slice into a standalone image.
+fn standalone(slice: &[u8], width: u32, height: u32, channels: u8) -> Vec<u8> {
+    let mut out = b"qoif".to_vec();
+    out.extend(width.to_be_bytes());
+    out.extend(height.to_be_bytes());
+    out.extend([channels, 0]);
+    out.extend(slice);
+    out.extend([0, 0, 0, 0, 0, 0, 0, 1]);
+    out
+}
+
+fn encoder_max_len(img: &[u8], width: u32, height: u32) -> usize {
+    Encoder::new(img, width, height).unwrap().required_buf_len::<false>()
+}
+
+/// Encodes in pieces of `buf_size` bytes, returns the output and whether it took one call.
+fn encode_partial(mut encoder: Encoder, buf_size: usize) -> (Vec<u8>, bool) {
+    let (mut out, mut buf, mut n_calls) = (Vec::new(), vec![0; buf_size], 0);
+    loop {
+        let status = encoder.encode_to_buf_partial::<false>(&mut buf).unwrap();
+        out.extend(&buf[..status.n_written()]);
+        n_calls += 1;
+        if status.is_complete() {
+            return (out, n_calls == 1);
+        }
+    }
+}
+
+#[test]
+fn test_slices_roundtrip() {
+    let mut rng = StdRng::seed_from_u64(0);
+    for channels in [3, 4] {
+        let (width, height) = (67_u32, 45_u32);
+        let img = gen_runs(&mut rng, (width * height) as usize, channels);
+        let unsliced = Encoder::new(&img, width, height).unwrap().encode_to_vec::<false>().unwrap();
+
+        for slice_height in [1, 8, 44, 45, 100] {
+            let mut encoder = Encoder::new(&img, width, height).unwrap();
+            encoder = encoder.with_slice_height(slice_height);
+            let n_slices = encoder.header().n_slices();
+            assert_eq!(n_slices, ((height - 1) / slice_height + 1) as usize);
+            let encoded = encoder.encode_to_vec::<false>().unwrap();
+            assert_ne!(encoded, unsliced);
+
+            let header = decode_all(&encoded, &img);
+            assert_eq!(header.slice_height(), slice_height);
+
+            // every slice decodes on its own
+            let table_start = header.encoded_len() - n_slices * 4;
+            let data = &encoded[header.encoded_len()..encoded.len() - 8];
+            let row_len = width as usize * channels;
+            let mut start = 0;
+            for (i, end) in encoded[table_start..header.encoded_len()].chunks(4).enumerate() {
+                let end = u32::from_be_bytes(end.try_into().unwrap()) as usize;
+                let y0 = i * slice_height as usize;
+                let rows = (height as usize - y0).min(slice_height as usize);
+                let slice = standalone(&data[start..end], width, rows as _, channels as _);
+                let (_, decoded) = decode_to_vec::<false>(&slice).unwrap();
+                assert_eq!(decoded, img[y0 * row_len..(y0 + rows) * row_len]);
+                start = end;
+            }
+            assert_eq!(start, data.len());
+
+            // all other ways of encoding and decoding agree
+            let mut encoder = Encoder::new(&img, width, height).unwrap();
+            let mut streamed: Vec<u8> = vec![];
+            encoder = encoder.with_slice_height(slice_height);
+            encoder.encode_to_stream::<_, false>(&mut streamed).unwrap();
+            assert_eq!(streamed, encoded);
+            let encoder = Encoder::new(&img, width, height).unwrap();
+            let mut reader = encoder.with_slice_height(slice_height).into_reader::<false>();
+            let mut read: Vec<u8> = vec![];
+            BufReader::with_capacity(5, &mut reader).read_to_end(&mut read).unwrap();
+            assert_eq!(read, encoded);
+            // with room for the worst case, the blocks are encoded once in a single call
+            for buf_size in [13, 500, encoder_max_len(&img, width, height)] {
+                let encoder = Encoder::new(&img, width, height).unwrap();
+                let partial = encode_partial(encoder.with_slice_height(slice_height), buf_size);
+                assert_eq!(partial, (encoded.clone(), buf_size > encoded.len()));
+            }
+        }
+    }
+}
+
+#[test]
+fn test_slices_invalid() {
+    let img = vec![0x10_u8; 16 * 16 * 3];
+    let encoded =
+        Encoder::new(&img, 16, 16).unwrap().with_slice_height(4).encode_to_vec::<false>().unwrap();
+    assert_eq!(encoded[13], 0x80);
+
+    let mut bad_flags = encoded.clone();
+    bad_flags[16] |= 0x04;
+    assert!(matches!(Decoder::new(&bad_flags), Err(Error::InvalidHeaderExtension)));
+    let mut bad_height = encoded.clone();
+    bad_height[18..22].copy_from_slice(&[0; 4]);
+    assert!(matches!(Decoder::new(&bad_height), Err(Error::InvalidHeaderExtension)));
+    assert!(matches!(Decoder::new(&encoded[..30]), Err(Error::UnexpectedBufferEnd)));
+    assert!(Decoder::from_stream(&encoded[..30]).is_err());
+}
+
+fn crop(img: &[u8], width: u32, channels: usize, rect: (u32, u32, u32, u32)) -> Vec<u8> {
+    let (x, y, w, h) = rect;
+    let row_len = width as usize * channels;
+    (y..y + h)
+        .flat_map(|r| {
+            let start = r as usize * row_len + x as usize * channels;
+            img[start..start + w as usize * channels].iter().copied()
+        })
+        .collect()
+}
+
+#[test]
+fn test_tiles_roundtrip() {
+    let mut rng = StdRng::seed_from_u64(1);
+    for channels in [3, 4] {
+        let (width, height) = (67_u32, 45_u32);
+        let img = gen_runs(&mut rng, (width * height) as usize, channels);
+
+        for (tile_width, tile_height) in [(16, 16), (1, 45), (67, 8), (5, 100), (100, 100)] {
+            let encoder = Encoder::new(&img, width, height).unwrap();
+            let mut encoder = encoder.with_slice_height(4).with_tile_size(tile_width, tile_height);
+            let header = *encoder.header();
+            assert_eq!((header.slice_height(), header.is_tiled()), (0, true));
+            let n_cols = (width - 1) / tile_width + 1;
+            let n_tiles = (n_cols * ((height - 1) / tile_height + 1)) as usize;
+            assert_eq!(header.n_tiles(), n_tiles);
+            let encoded = encoder.encode_to_vec::<false>().unwrap();
+
+            assert_eq!(decode_all(&encoded, &img), header);
+
+            // every tile decodes on its own
+            let table_start = header.encoded_len() - n_tiles * 4;
+            let data = &encoded[header.encoded_len()..encoded.len() - 8];
+            let mut start = 0;
+            for (i, end) in encoded[table_start..header.encoded_len()].chunks(4).enumerate() {
+                let end = u32::from_be_bytes(end.try_into().unwrap()) as usize;
+                let (x, y) = (i as u32 % n_cols * tile_width, i as u32 / n_cols * tile_height);
+                let (w, h) = (tile_width.min(width - x), tile_height.min(height - y));
+                let tile = standalone(&data[start..end], w, h, channels as _);
+                let (_, decoded) = decode_to_vec::<false>(&tile).unwrap();
+                assert_eq!(decoded, crop(&img, width, channels, (x, y, w, h)));
+                start = end;
+            }
+            assert_eq!(start, data.len());
+
+            // all other ways of encoding agree
+            let mut encoder = Encoder::new(&img, width, height).unwrap();
+            let mut streamed: Vec<u8> = vec![];
+            encoder = encoder.with_tile_size(tile_width, tile_height);
+            encoder.encode_to_stream::<_, false>(&mut streamed).unwrap();
+            assert_eq!(streamed, encoded);
+            let encoder = Encoder::new(&img, width, height).unwrap();
+            let mut reader = encoder.with_tile_size(tile_width, tile_height).into_reader::<false>();
+            let mut read: Vec<u8> = vec![];
+            BufReader::with_capacity(5, &mut reader).read_to_end(&mut read).unwrap();
+            assert_eq!(read, encoded);
+            for buf_size in [13, 500, encoder_max_len(&img, width, height)] {
+                let encoder = Encoder::new(&img, width, height).unwrap();
+                let encoder = encoder.with_tile_size(tile_width, tile_height);
+                assert_eq!(
+                    encode_partial(encoder, buf_size),
+                    (encoded.clone(), buf_size > encoded.len())
+                );
+            }
+
+            // dropping alpha on the fly works per tile too
+            if channels == 4 {
+                let rgb: Vec<u8> = img.chunks_exact(4).flat_map(|px| &px[..3]).copied().collect();
+                let expected = Encoder::new(&rgb, width, height)
+                    .unwrap()
+                    .with_tile_size(tile_width, tile_height)
+                    .encode_to_vec::<false>()
+                    .unwrap();
+                let converted = Encoder::new(&img, width, height)
+                    .unwrap()
+                    .with_tile_size(tile_width, tile_height)
+                    .with_channels(qoi::Channels::Rgb)
+                    .encode_to_vec::<false>()
+                    .unwrap();
+                assert_eq!(converted, expected);
+            }
+
+            // tiles are not stored in row order, so row-order decoding is refused
+            let mut decoder = Decoder::new(&encoded).unwrap();
+            let result = decoder.decode_rows::<false>(|_, _| {});
+            assert!(matches!(result, Err(Error::UnsupportedTiling)));
+            assert!(matches!(decoder.pixels::<false, 4>(), Err(Error::UnsupportedTiling)));
+            let mut out = vec![0; img.len()];
+            let mut writer = DecoderWriter::new(&mut out);
+            assert!(writer.write_all(&encoded).is_err());
+        }
+    }
+}
+
+#[test]
+fn test_decode_region() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let (width, height) = (67_u32, 45_u32);
+    let img = gen_runs(&mut rng, (width * height) as usize, 4);
+    let regions = [(0, 0, 67, 45), (0, 0, 1, 1), (66, 44, 1, 1), (10, 5, 20, 13), (31, 16, 36, 2)];
+
+    let plain = Encoder::new(&img, width, height).unwrap();
+    let sliced = Encoder::new(&img, width, height).unwrap().with_slice_height(6);
+    let tiled = Encoder::new(&img, width, height).unwrap().with_tile_size(16, 8);
+    for mut encoder in [plain, sliced, tiled] {
+        let encoded = encoder.encode_to_vec::<false>().unwrap();
+        let decoder = Decoder::new(&encoded).unwrap();
+        for (x, y, w, h) in regions {
+            let region = decoder.decode_region(x, y, w, h).unwrap();
+            assert_eq!(region, crop(&img, width, 4, (x, y, w, h)));
+        }
+        let decoder = decoder.with_channels(qoi::Channels::Rgb);
+        let rgb: Vec<u8> = img.chunks_exact(4).flat_map(|px| &px[..3]).copied().collect();
+        assert_eq!(
+            decoder.decode_region(3, 4, 50, 30).unwrap(),
+            crop(&rgb, width, 3, (3, 4, 50, 30))
+        );
+
+        for (x, y, w, h) in [(0, 0, 0, 1), (0, 0, 68, 1), (60, 0, 8, 1), (0, 45, 1, 1)] {
+            let result = decoder.decode_region(x, y, w, h);
+            assert!(matches!(result, Err(Error::InvalidRegion { .. })));
+        }
+    }
+}
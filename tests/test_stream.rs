@@ -0,0 +1,371 @@
+mod common;
+
+use std::io::{BufReader, Read, Write};
+
+use rand::{rngs::StdRng, SeedableRng};
+
+use qoi::{
+    encode_iter_to_buf_with, encode_iter_to_vec, encode_iter_to_vec_with, encode_to_vec, Channels,
+    Decoder, DecoderWriter, EncodeStatus, Encoder, EncoderReader, Error, State,
+};
+
+use self::common::gen_runs;
+
+/// Reader that hands out at most a few bytes at a time.
+struct Trickle<'a>(&'a [u8], usize);
+
+impl Read for Trickle<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.1.min(buf.len()).min(self.0.len());
+        buf[..n].copy_from_slice(&self.0[..n]);
+        self.0 = &self.0[n..];
+        Ok(n)
+    }
+}
+
+/// Writer that records the number of write calls it receives.
+#[derive(Default)]
+struct CountingWriter(Vec<u8>, usize);
+
+impl Write for CountingWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.1 += 1;
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_decode_stream_chunked() {
+    let mut rng = StdRng::seed_from_u64(0);
+    for channels in [3, 4] {
+        let (width, height) = (123, 45);
+        let img = gen_runs(&mut rng, width * height, channels);
+        let encoded = encode_to_vec::<false>(&img, width as _, height as _).unwrap();
+        let mut data = encoded.clone();
+        data.extend(b"tail");
+
+        for capacity in [1, 2, 3, 4, 5, 7, 64, 4096] {
+            let reader = BufReader::with_capacity(capacity, data.as_slice());
+            let mut decoder = Decoder::from_buf_read(reader).unwrap();
+            assert_eq!(decoder.decode_to_vec::<false>().unwrap(), img);
+            let mut tail = vec![];
+            decoder.into_reader().read_to_end(&mut tail).unwrap();
+            assert_eq!(tail, b"tail");
+
+            let mut decoder = Decoder::from_stream(Trickle(&data, capacity)).unwrap();
+            assert_eq!(decoder.decode_to_vec::<false>().unwrap(), img);
+            let _: Trickle = decoder.into_reader();
+        }
+
+        let truncated = &encoded[..encoded.len() - 20];
+        let mut decoder = Decoder::from_stream(truncated).unwrap();
+        assert!(matches!(decoder.decode_to_vec::<false>(), Err(Error::UnexpectedBufferEnd)));
+    }
+}
+
+#[test]
+fn test_decode_stream_concatenated() {
+    let mut rng = StdRng::seed_from_u64(1);
+    let images: Vec<_> = [(31, 17, 3), (40, 9, 4), (5, 5, 3)]
+        .iter()
+        .map(|&(width, height, channels)| {
+            let img = gen_runs(&mut rng, width * height, channels);
+            let encoded = encode_to_vec::<false>(&img, width as _, height as _).unwrap();
+            (img, encoded)
+        })
+        .collect();
+    let data: Vec<u8> = images.iter().flat_map(|(_, encoded)| encoded).copied().collect();
+
+    // the decoder reads ahead, so the next image starts in its buffer
+    let (mut reader, mut buffered) = (Trickle(&data, 100), Vec::new());
+    for (img, _) in &images {
+        let stream = buffered.as_slice().chain(&mut reader);
+        let mut decoder = Decoder::from_stream(stream).unwrap();
+        assert_eq!(&decoder.decode_to_vec::<false>().unwrap(), img);
+        let (_, rest) = decoder.into_parts();
+        buffered = rest;
+    }
+    assert!(buffered.is_empty());
+    assert!(reader.0.is_empty());
+    let mut decoder = Decoder::from_stream(Trickle(&data, 100)).unwrap();
+    decoder.decode_to_vec::<false>().unwrap();
+    let rest = decoder.into_reader().0;
+    assert!(rest.len() < data.len() - images[0].1.len());
+
+    let mut reader = BufReader::with_capacity(64, data.as_slice());
+    for (img, _) in &images {
+        let mut decoder = Decoder::from_buf_read(&mut reader).unwrap();
+        assert_eq!(&decoder.decode_to_vec::<false>().unwrap(), img);
+        let (_, rest) = decoder.into_parts();
+        assert!(rest.is_empty());
+    }
+}
+
+#[test]
+fn test_encode_stream_buffered() {
+    let mut rng = StdRng::seed_from_u64(1);
+    for channels in [3, 4] {
+        let (width, height) = (300, 200);
+        let img = gen_runs(&mut rng, width * height, channels);
+        let expected = encode_to_vec::<false>(&img, width as _, height as _).unwrap();
+
+        let mut writer = CountingWriter::default();
+        let mut encoder = Encoder::new(&img, width as _, height as _).unwrap();
+        let n_written = encoder.encode_to_stream::<_, false>(&mut writer).unwrap();
+        assert_eq!(n_written, expected.len());
+        assert_eq!(writer.0, expected);
+        assert!(writer.1 <= 1 + expected.len() / 4096, "too many writes: {}", writer.1);
+
+        let mut writer = CountingWriter::default();
+        let mut encoder = Encoder::new(&img, width as _, height as _).unwrap();
+        let n_written = encoder.encode_to_stream::<_, true>(&mut writer).unwrap();
+        assert_eq!(n_written, expected.len() - 22);
+        assert_eq!(writer.0, expected[14..expected.len() - 8]);
+    }
+}
+
+#[test]
+fn test_encode_partial() {
+    let mut rng = StdRng::seed_from_u64(2);
+    for channels in [3, 4] {
+        let (width, height) = (97, 31);
+        let img = gen_runs(&mut rng, width * height, channels);
+        let expected = encode_to_vec::<false>(&img, width as _, height as _).unwrap();
+
+        for buf_size in (1..=16).chain([64, 1000, 100_000]) {
+            let mut encoder = Encoder::new(&img, width as _, height as _).unwrap();
+            let mut buf = vec![0; buf_size];
+            let mut out: Vec<u8> = vec![];
+            loop {
+                let status = encoder.encode_to_buf_partial::<false>(&mut buf).unwrap();
+                out.extend(&buf[..status.n_written()]);
+                if status.is_complete() {
+                    break;
+                }
+                assert_eq!(status, EncodeStatus::Incomplete(buf_size));
+            }
+            assert_eq!(out, expected, "buf_size={}", buf_size);
+
+            let mut encoder = Encoder::new(&img, width as _, height as _).unwrap();
+            let mut out: Vec<u8> = vec![];
+            while {
+                let status = encoder.encode_to_buf_partial::<true>(&mut buf).unwrap();
+                out.extend(&buf[..status.n_written()]);
+                !status.is_complete()
+            } {}
+            assert_eq!(out, expected[14..expected.len() - 8]);
+        }
+    }
+}
+
+#[test]
+fn test_encoder_reader() {
+    let mut rng = StdRng::seed_from_u64(3);
+    for channels in [3, 4] {
+        let (width, height) = (211, 17);
+        let img = gen_runs(&mut rng, width * height, channels);
+        let expected = encode_to_vec::<false>(&img, width as _, height as _).unwrap();
+
+        let mut reader = EncoderReader::<false>::new(&img, width as _, height as _).unwrap();
+        let mut out = vec![];
+        reader.read_to_end(&mut out).unwrap();
+        assert!(reader.is_done());
+        assert_eq!(out, expected);
+
+        let reader = Encoder::new(&img, width as _, height as _).unwrap().into_reader::<true>();
+        let mut out = vec![];
+        BufReader::with_capacity(3, reader).read_to_end(&mut out).unwrap();
+        assert_eq!(out, expected[14..expected.len() - 8]);
+
+        // the reader can feed the stream decoder directly
+        let reader = EncoderReader::<false>::new(&img, width as _, height as _).unwrap();
+        let decoded = Decoder::from_stream(reader).unwrap().decode_to_vec::<false>().unwrap();
+        assert_eq!(decoded, img);
+    }
+}
+
+#[test]
+fn test_decoder_writer() {
+    let mut rng = StdRng::seed_from_u64(4);
+    for channels in [3, 4] {
+        let (width, height) = (53, 29);
+        let img = gen_runs(&mut rng, width * height, channels);
+        let encoded = encode_to_vec::<false>(&img, width as _, height as _).unwrap();
+
+        for chunk_size in [1, 2, 3, 5, 13, 1000, encoded.len()] {
+            let mut out = vec![0; img.len()];
+            let mut writer = DecoderWriter::new(&mut out);
+            for chunk in encoded.chunks(chunk_size) {
+                writer.write_all(chunk).unwrap();
+            }
+            let header = writer.finish().unwrap();
+            assert_eq!((header.width, header.height), (width as _, height as _));
+            assert_eq!(out, img);
+
+            let mut rows: Vec<u8> = vec![];
+            let mut writer = DecoderWriter::new_rows(|y, row: &[u8]| {
+                assert_eq!(y as usize, rows.len() / (width * channels));
+                rows.extend(row);
+            });
+            for chunk in encoded.chunks(chunk_size) {
+                writer.write_all(chunk).unwrap();
+            }
+            writer.finish().unwrap();
+            assert_eq!(rows, img);
+        }
+
+        // channel conversion and trailing data
+        let mut out = vec![0; width * height * 4];
+        let mut writer = DecoderWriter::new(&mut out).with_channels(Channels::Rgba);
+        let mut data = encoded.clone();
+        data.extend(b"tail");
+        assert_eq!(writer.write(&data).unwrap(), encoded.len());
+        writer.finish().unwrap();
+        let expected = Decoder::new(&encoded)
+            .unwrap()
+            .with_channels(Channels::Rgba)
+            .decode_to_vec::<false>()
+            .unwrap();
+        assert_eq!(out, expected);
+
+        // truncated input, bad padding, small buffer
+        let mut out = vec![0; img.len()];
+        let mut writer = DecoderWriter::new(&mut out);
+        writer.write_all(&encoded[..encoded.len() - 1]).unwrap();
+        assert!(matches!(writer.finish(), Err(Error::UnexpectedBufferEnd)));
+        writer.write_all(&[0xff]).unwrap();
+        assert!(matches!(writer.finish(), Err(Error::InvalidPadding)));
+        let mut out = vec![0; img.len() - 1];
+        let mut writer = DecoderWriter::new(&mut out);
+        assert!(writer.write_all(&encoded).is_err());
+    }
+}
+
+#[test]
+fn test_decode_rows() {
+    let mut rng = StdRng::seed_from_u64(5);
+    for channels in [3, 4] {
+        let (width, height) = (37, 41);
+        let img = gen_runs(&mut rng, width * height, channels);
+        let encoded = encode_to_vec::<false>(&img, width as _, height as _).unwrap();
+
+        let mut rows: Vec<u8> = vec![];
+        let mut decoder = Decoder::new(&encoded).unwrap();
+        assert_eq!(decoder.required_row_len(), width * channels);
+        decoder
+            .decode_rows::<false>(|y, row| {
+                assert_eq!(y as usize * width * channels, rows.len());
+                rows.extend(row);
+            })
+            .unwrap();
+        assert_eq!(rows, img);
+
+        let mut rows: Vec<u8> = vec![];
+        let reader = BufReader::with_capacity(7, encoded.as_slice());
+        let mut decoder = Decoder::from_buf_read(reader).unwrap().with_channels(Channels::Rgba);
+        let mut scratch = [0; 37 * 4];
+        decoder.decode_rows_with_buf::<false>(&mut scratch, |_, row| rows.extend(row)).unwrap();
+        let expected = Decoder::new(&encoded)
+            .unwrap()
+            .with_channels(Channels::Rgba)
+            .decode_to_vec::<false>()
+            .unwrap();
+        assert_eq!(rows, expected);
+
+        let mut decoder = Decoder::new(&encoded).unwrap();
+        let result = decoder.decode_rows_with_buf::<false>(&mut scratch[..10], |_, _| {});
+        assert!(matches!(result, Err(Error::OutputBufferTooSmall { .. })));
+        let mut decoder = Decoder::new(&encoded[..encoded.len() - 9]).unwrap();
+        let result = decoder.decode_rows::<false>(|_, _| {});
+        assert!(matches!(result, Err(Error::UnexpectedBufferEnd)));
+    }
+}
+
+#[test]
+fn test_pixel_iter() {
+    let mut rng = StdRng::seed_from_u64(6);
+    let (width, height) = (71, 23);
+    let img = gen_runs(&mut rng, width * height, 4);
+    let expected = encode_to_vec::<false>(&img, width as _, height as _).unwrap();
+
+    let pixels = img.chunks_exact(4).map(|px| [px[0], px[1], px[2], px[3]]);
+    let encoded = encode_iter_to_vec::<false, 4>(pixels, width as _, height as _).unwrap();
+    assert_eq!(encoded, expected);
+
+    let mut decoder = Decoder::new(&encoded).unwrap();
+    let decoded: Vec<[u8; 4]> = decoder.pixels::<false, 4>().unwrap().map(Result::unwrap).collect();
+    assert_eq!(decoded.concat(), img);
+
+    // channel conversion on both ends
+    let rgb: Vec<u8> = img.chunks_exact(4).flat_map(|px| [px[0], px[1], px[2]]).collect();
+    let expected = encode_to_vec::<false>(&rgb, width as _, height as _).unwrap();
+    let pixels = rgb.chunks_exact(3).map(|px| [px[0], px[1], px[2]]);
+    let encoded = encode_iter_to_vec::<false, 3>(pixels, width as _, height as _).unwrap();
+    assert_eq!(encoded, expected);
+    let reader = BufReader::with_capacity(5, encoded.as_slice());
+    let mut decoder = Decoder::from_buf_read(reader).unwrap();
+    let decoded: Vec<[u8; 4]> = decoder.pixels::<false, 4>().unwrap().map(Result::unwrap).collect();
+    let expected = Decoder::new(&encoded)
+        .unwrap()
+        .with_channels(Channels::Rgba)
+        .decode_to_vec::<false>()
+        .unwrap();
+    assert_eq!(decoded.concat(), expected);
+
+    // wrong number of pixels, truncated input
+    let short = img.chunks_exact(4).skip(1).map(|px| [px[0], px[1], px[2], px[3]]);
+    let result = encode_iter_to_vec::<false, 4>(short, width as _, height as _);
+    assert!(matches!(result, Err(Error::InvalidImageLength { .. })));
+    let long =
+        img.chunks_exact(4).chain([[0; 4].as_slice()]).map(|px| [px[0], px[1], px[2], px[3]]);
+    let result = encode_iter_to_vec::<false, 4>(long, width as _, height as _);
+    assert!(matches!(result, Err(Error::InvalidImageLength { .. })));
+    let mut decoder = Decoder::from_stream(&encoded[..encoded.len() - 9]).unwrap();
+    let last = decoder.pixels::<false, 3>().unwrap().last().unwrap();
+    assert!(matches!(last, Err(Error::UnexpectedBufferEnd)));
+}
+
+#[test]
+fn test_pixel_iter_with_state() {
+    let mut rng = StdRng::seed_from_u64(7);
+    let (width, height) = (29, 19);
+    let frames: Vec<_> = (0..3).map(|_| gen_runs(&mut rng, width * height, 4)).collect();
+    let to_pixels = |img: &[u8]| -> Vec<[u8; 4]> {
+        img.chunks_exact(4).map(|px| [px[0], px[1], px[2], px[3]]).collect()
+    };
+
+    // chained frames, each one starting from the state the previous one left behind
+    let (mut state, mut expected_state) = (State::default(), State::default());
+    for img in &frames {
+        let mut encoder = Encoder::new_with(expected_state, img, width as _, height as _).unwrap();
+        let expected = encoder.encode_to_vec::<true>().unwrap();
+        expected_state = encoder.into_state();
+        let pixels = to_pixels(img);
+        let encoded =
+            encode_iter_to_vec_with::<true, 4>(&mut state, pixels, width as _, height as _)
+                .unwrap();
+        assert_eq!(encoded, expected);
+    }
+
+    // a dictionary is announced in the header
+    let dictionary = State::train(&frames, Channels::Rgba);
+    let encoder = Encoder::new(&frames[0], width as _, height as _).unwrap();
+    let expected = encoder.with_dictionary(&dictionary).encode_to_vec::<false>().unwrap();
+    let mut buf = vec![0; width * height * 5 + 64];
+    let pixels = to_pixels(&frames[0]);
+    let n = encode_iter_to_buf_with::<false, 4>(
+        &mut dictionary.clone(),
+        &mut buf,
+        pixels,
+        width as _,
+        height as _,
+    )
+    .unwrap();
+    assert_eq!(buf[..n], expected);
+    let mut decoder = Decoder::new(&expected).unwrap().with_dictionary(&dictionary);
+    assert_eq!(decoder.decode_to_vec::<false>().unwrap(), frames[0]);
+}
@@ -0,0 +1,115 @@
+mod common;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use qoi::{decode_to_vec, Channels, Decoder, EncodeStatus, Encoder, Error, Limits};
+
+use self::common::{decode_all, gen_image, read_png};
+
+#[test]
+fn test_vertical_roundtrip() {
+    for name in ["dice", "kodim10", "kodim23", "qoi_logo", "testcard"] {
+        let (img, width, height) = read_png(&format!("assets/{}.png", name));
+        let mut encoder = Encoder::new(&img, width, height).unwrap().with_vertical_prediction(true);
+        let encoded = encoder.encode_to_vec::<false>().unwrap();
+        let (header, decoded) = decode_to_vec::<false>(&encoded).unwrap();
+        assert!(header.vertical_prediction());
+        assert_eq!(decoded, img, "{}", name);
+        let mut decoder = Decoder::from_stream(encoded.as_slice()).unwrap();
+        assert_eq!(decoder.decode_to_vec::<false>().unwrap(), img);
+    }
+    for channels in [3, 4] {
+        let img = gen_image(211, 97, channels, 0);
+        let new = || Encoder::new(&img, 211, 97).unwrap();
+        let plain = new().encode_to_vec::<false>().unwrap();
+        let vertical = new().with_vertical_prediction(true).encode_to_vec::<false>().unwrap();
+        assert!(vertical.len() < plain.len() / 2);
+        assert_eq!(decode_to_vec::<false>(&vertical).unwrap().1, img);
+    }
+}
+
+#[test]
+fn test_vertical_paths() {
+    let (width, height) = (83, 57);
+    let img = gen_image(width, height, 4, 1);
+    for (slice_height, tile_size) in [(0, (0, 0)), (10, (0, 0)), (0, (20, 16))] {
+        for (entropy, transform) in [(false, false), (true, false), (false, true)] {
+            let new = || {
+                Encoder::new(&img, width as u32, height as u32)
+                    .unwrap()
+                    .with_slice_height(slice_height)
+                    .with_tile_size(tile_size.0, tile_size.1)
+                    .with_vertical_prediction(true)
+                    .with_entropy_coding(entropy)
+                    .with_color_transform(transform)
+            };
+            let encoded = new().encode_to_vec::<false>().unwrap();
+            assert!(decode_all(&encoded, &img).vertical_prediction());
+
+            let decoder = Decoder::new(&encoded).unwrap().with_channels(Channels::Rgb);
+            let region = decoder.decode_region(15, 9, 30, 20).unwrap();
+            let expected: Vec<u8> = (9..29)
+                .flat_map(|y| img[(y * width + 15) * 4..(y * width + 45) * 4].chunks(4))
+                .flat_map(|px| &px[..3])
+                .copied()
+                .collect();
+            assert_eq!(region, expected);
+
+            let mut encoder = new();
+            let (mut partial, mut buf) = (Vec::<u8>::new(), [0; 11]);
+            loop {
+                let status = encoder.encode_to_buf_partial::<false>(&mut buf).unwrap();
+                partial.extend(&buf[..status.n_written()]);
+                if let EncodeStatus::Complete(_) = status {
+                    break;
+                }
+            }
+            assert_eq!(partial, encoded);
+        }
+    }
+}
+
+#[test]
+fn test_vertical_long_runs() {
+    // flat areas mix runs along and across rows, spanning row ends
+    let (width, height) = (300, 40);
+    let mut rng = StdRng::seed_from_u64(2);
+    let mut img = vec![0x80; width * height * 3];
+    for _ in 0..200 {
+        let i = rng.gen_range(0..img.len());
+        img[i] = rng.gen();
+    }
+    let mut encoder =
+        Encoder::new(&img, width as u32, height as u32).unwrap().with_vertical_prediction(true);
+    let encoded = encoder.encode_to_vec::<false>().unwrap();
+    assert_eq!(decode_to_vec::<false>(&encoded).unwrap().1, img);
+}
+
+#[test]
+fn test_vertical_unsupported() {
+    let img = gen_image(16, 16, 4, 3);
+    let mut encoder = Encoder::new(&img, 16, 16).unwrap().with_vertical_prediction(true);
+    let encoded = encoder.encode_to_vec::<false>().unwrap();
+    let mut decoder = Decoder::new(&encoded).unwrap();
+    assert!(matches!(decoder.pixels::<false, 4>(), Err(Error::UnsupportedVerticalPrediction)));
+}
+
+#[test]
+fn test_vertical_limits() {
+    let img = gen_image(100, 10, 3, 4);
+    let mut encoder = Encoder::new(&img, 100, 10).unwrap().with_vertical_prediction(true);
+    let encoded = encoder.encode_to_vec::<false>().unwrap();
+    // the row above takes 4 bytes per pixel, besides the caller's buffer
+    let decode = |max_alloc| {
+        let limits = Limits::default().with_max_alloc(max_alloc);
+        let mut decoder = Decoder::new(&encoded).unwrap().with_limits(limits);
+        let mut buf = vec![0; img.len()];
+        decoder.decode_to_buf::<false>(&mut buf).map(|_| buf)
+    };
+    assert_eq!(decode(400).unwrap(), img);
+    assert!(matches!(decode(399), Err(Error::LimitsExceeded)));
+    let limits = Limits::default().with_max_alloc(399);
+    let mut decoder = Decoder::new(&encoded).unwrap().with_limits(limits);
+    let result = decoder.decode_rows_with_buf::<false>(vec![0; 300], |_, _| {});
+    assert!(matches!(result, Err(Error::LimitsExceeded)));
+}